@@ -0,0 +1,26 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use quickjs::Runtime;
+
+const RULE: &str = "(1 + 2) * 3 > 8 && 'ok'.length === 2";
+
+fn eval(c: &mut Criterion) {
+    let mut rt = Runtime::default();
+    let mut ctx = rt.context().unwrap();
+
+    c.bench_function("eval", |b| {
+        b.iter(|| ctx.eval(black_box(RULE), "<rule>", false, false).unwrap())
+    });
+}
+
+fn compiled(c: &mut Criterion) {
+    let mut rt = Runtime::default();
+    let ctx = rt.context().unwrap();
+    let script = ctx.compile(RULE, "<rule>").unwrap();
+
+    c.bench_function("compiled", |b| b.iter(|| script.run().unwrap()));
+}
+
+criterion_group!(benches, eval, compiled);
+criterion_main!(benches);
@@ -0,0 +1,488 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::error::Error;
+use crate::object::Object;
+use crate::promise::JobWakers;
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+use crate::weakref::WeakTarget;
+
+/// Installs `AbortController` and `AbortSignal`. Returns a function
+/// creating a signal for a given `AbortHandle`.
+const PRELUDE: &str = r#"(function (newHandle, setAborted, abortedReason) {
+    const token = Symbol("AbortSignal");
+
+    function abortError(message) {
+        const err = new Error(message);
+
+        err.name = "AbortError";
+        return err;
+    }
+
+    class AbortSignal {
+        constructor(key, handle) {
+            if (key !== token) {
+                throw new TypeError("Illegal constructor");
+            }
+            Object.defineProperty(this, "_handle", {
+                value: handle === undefined ? newHandle() : handle,
+            });
+            this._aborted = false;
+            this._reason = undefined;
+            this._listeners = [];
+            this.onabort = null;
+        }
+
+        static abort(reason) {
+            const signal = new AbortSignal(token);
+
+            signal._abort(reason, true);
+            return signal;
+        }
+
+        static timeout(millis) {
+            if (typeof setTimeout !== "function") {
+                const err = new Error(
+                    "AbortSignal.timeout needs timers, see Runtime::set_scheduler");
+
+                err.name = "NotSupportedError";
+                throw err;
+            }
+
+            const signal = new AbortSignal(token);
+
+            setTimeout(() => {
+                const err = new Error("signal timed out");
+
+                err.name = "TimeoutError";
+                signal._abort(err, true);
+            }, millis);
+            return signal;
+        }
+
+        get aborted() {
+            this._sync();
+            return this._aborted;
+        }
+
+        get reason() {
+            this._sync();
+            return this._reason;
+        }
+
+        throwIfAborted() {
+            if (this.aborted) {
+                throw this._reason;
+            }
+        }
+
+        addEventListener(type, listener, options) {
+            if (type !== "abort" || listener == null
+                || this._listeners.includes(listener)) {
+                return;
+            }
+            this._listeners.push(listener);
+        }
+
+        removeEventListener(type, listener) {
+            if (type === "abort") {
+                this._listeners = this._listeners.filter((l) => l !== listener);
+            }
+        }
+
+        /* picks up aborts from the host */
+        _sync() {
+            if (!this._aborted) {
+                const reason = abortedReason(this._handle);
+
+                if (reason !== null) {
+                    this._abort(abortError(reason), false);
+                }
+            }
+        }
+
+        _abort(reason, notifyHost) {
+            if (this._aborted) {
+                return;
+            }
+            if (reason === undefined) {
+                reason = abortError("signal is aborted without reason");
+            }
+            this._aborted = true;
+            this._reason = reason;
+
+            if (notifyHost) {
+                setAborted(this._handle,
+                    String(reason instanceof Error ? reason.message : reason));
+            }
+
+            const event = { type: "abort", target: this };
+
+            if (typeof this.onabort === "function") {
+                this.onabort.call(this, event);
+            }
+            for (const listener of this._listeners.splice(0)) {
+                if (typeof listener === "function") {
+                    listener.call(this, event);
+                } else {
+                    listener.handleEvent(event);
+                }
+            }
+        }
+    }
+
+    class AbortController {
+        constructor() {
+            this._signal = new AbortSignal(token);
+        }
+
+        get signal() {
+            return this._signal;
+        }
+
+        abort(reason) {
+            this._signal._abort(reason, true);
+        }
+    }
+
+    globalThis.AbortController = AbortController;
+    globalThis.AbortSignal = AbortSignal;
+
+    return (handle) => new AbortSignal(token, handle);
+})"#;
+
+/// Host side of an `AbortSignal`. Aborting either aborts the other, the
+/// handle can be aborted from any thread. The signal's `abort` listeners run
+/// in the next `Context::run_until_idle`.
+#[derive(Clone, Debug, Default)]
+pub struct AbortHandle {
+    inner: Arc<AbortState>,
+}
+
+#[derive(Debug, Default)]
+struct AbortState {
+    aborted: AtomicBool,
+    reason: Mutex<String>,
+    /// Of the runtimes with a signal for the handle, woken on abort.
+    waiters: Mutex<Vec<Weak<JobWakers>>>,
+}
+
+impl AbortHandle {
+    pub fn new() -> Self {
+        AbortHandle::default()
+    }
+
+    /// The handle of `signal`. Returns `None` if `signal` isn't an
+    /// `AbortSignal`.
+    pub fn from_signal(signal: &Value) -> Option<AbortHandle> {
+        if !signal.is_object() {
+            return None;
+        }
+
+        let obj = Object { value: signal.clone() };
+        let handle = obj.get("_handle").ok()?.native_ref::<Self>()?.clone();
+        let ctx = Context { ptr: signal.context.clone() };
+
+        ctx.watch_abort(&handle, signal).ok()?;
+        Some(handle)
+    }
+
+    /// Aborts with the default reason.
+    pub fn abort(&self) {
+        self.abort_with("signal is aborted without reason");
+    }
+
+    /// Aborts, with scripts seeing an `AbortError` with `reason` as its
+    /// message. Does nothing if already aborted.
+    pub fn abort_with(&self, reason: &str) {
+        {
+            let mut guard = self.inner.reason.lock().unwrap();
+
+            if self.inner.aborted.load(Ordering::SeqCst) {
+                return;
+            }
+            *guard = reason.to_string();
+            self.inner.aborted.store(true, Ordering::SeqCst);
+        }
+
+        let waiters = self
+            .inner
+            .waiters
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect::<Vec<_>>();
+
+        for waiter in waiters {
+            waiter.wake_all();
+        }
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.inner.aborted.load(Ordering::SeqCst)
+    }
+
+    /// The message of the abort reason, if aborted.
+    pub fn reason(&self) -> Option<String> {
+        let guard = self.inner.reason.lock().unwrap();
+
+        if self.is_aborted() {
+            Some(guard.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Per context state of the abort classes.
+#[derive(Default)]
+pub(crate) struct Aborts {
+    /// Creates a signal for a handle. Doesn't keep the context alive.
+    factory: Option<Value>,
+    /// Signals the host holds the handle of, and may abort. Dropped once
+    /// collected.
+    watched: Vec<(AbortHandle, WeakTarget)>,
+}
+
+impl Context {
+    /// Installs `AbortController` and `AbortSignal`.
+    pub(crate) fn install_abort(&self) -> Result<(), Value> {
+        let new_handle = self.internal_closure("newHandle", |ctx, _, _| {
+            ctx.wrap_native(AbortHandle::new())
+                .unwrap_or_else(|ex| ctx.throw(ex))
+        })?;
+        let set_aborted =
+            self.internal_closure("setAborted", |ctx, _, args| {
+                let handle =
+                    args.get(0).and_then(|h| h.native_ref::<AbortHandle>());
+                let reason = args.get(1).and_then(|r| r.as_string());
+
+                if let (Some(handle), Some(reason)) = (handle, reason) {
+                    handle.abort_with(&reason);
+                }
+                ctx.undefined()
+            })?;
+        let aborted_reason =
+            self.internal_closure("abortedReason", |ctx, _, args| {
+                let handle =
+                    args.get(0).and_then(|h| h.native_ref::<AbortHandle>());
+
+                match handle.and_then(|h| h.reason()) {
+                    Some(reason) => ctx.string(&reason),
+                    None => ctx.null(),
+                }
+            })?;
+        let install = self.eval_script(PRELUDE, "<abort>")?;
+        let factory = install
+            .call(self.undefined(), &[new_handle, set_aborted, aborted_reason]);
+
+        if factory.is_exception() {
+            return Err(self.take_exception());
+        }
+
+        self.ptr.state().aborts.borrow_mut().factory =
+            Some(factory.into_unowned());
+        Ok(())
+    }
+
+    /// Creates an `AbortSignal` for scripts that is aborted through
+    /// `handle`.
+    pub fn abort_signal(&self, handle: &AbortHandle) -> Result<Value, Value> {
+        let factory = match self.ptr.state().aborts.borrow().factory {
+            Some(ref factory) => factory.owned(self),
+            None => panic!("abort classes not installed"),
+        };
+        let native = self.wrap_native(handle.clone())?;
+        let signal = factory.call(self.undefined(), &[native]);
+
+        if signal.is_exception() {
+            return Err(self.take_exception());
+        }
+
+        self.watch_abort(handle, &signal)?;
+        Ok(signal)
+    }
+
+    fn watch_abort(
+        &self,
+        handle: &AbortHandle,
+        signal: &Value,
+    ) -> Result<(), Value> {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+        let own = Arc::downgrade(&state.job_wakers);
+        let mut waiters = handle.inner.waiters.lock().unwrap();
+
+        waiters.retain(|w| w.strong_count() > 0);
+        if !waiters.iter().any(|w| w.ptr_eq(&own)) {
+            waiters.push(own);
+        }
+        drop(waiters);
+
+        let mut aborts = self.ptr.state().aborts.borrow_mut();
+
+        aborts.watched.retain(|(_, signal)| signal.is_alive());
+        if !aborts
+            .watched
+            .iter()
+            .any(|(h, _)| Arc::ptr_eq(&h.inner, &handle.inner))
+        {
+            drop(aborts);
+
+            let signal = self.downgrade(signal)?;
+
+            self.ptr
+                .state()
+                .aborts
+                .borrow_mut()
+                .watched
+                .push((handle.clone(), signal));
+        }
+        Ok(())
+    }
+
+    /// Runs the listeners of signals the host aborted. Returns whether
+    /// there were any.
+    pub(crate) fn dispatch_aborts(&self) -> Result<bool, Error> {
+        let aborted = {
+            let mut aborts = self.ptr.state().aborts.borrow_mut();
+            let (aborted, watched) = aborts
+                .watched
+                .drain(..)
+                .filter(|(_, signal)| signal.is_alive())
+                .partition::<Vec<_>, _>(|(handle, _)| handle.is_aborted());
+
+            aborts.watched = watched;
+            aborted
+        };
+
+        for (_, signal) in aborted.iter() {
+            let signal = match signal.upgrade(self) {
+                Some(signal) => signal,
+                None => continue,
+            };
+
+            // reading `aborted` makes the signal catch up
+            if (Object { value: signal }).get("aborted").is_err() {
+                return Err(Error::from(self.take_exception()));
+            }
+        }
+
+        Ok(!aborted.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::thread;
+
+    use super::*;
+    use crate::runtime::ContextPtr;
+    use crate::Runtime;
+
+    #[test]
+    fn script_abort() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let cancel = ctx
+            .closure("cancel", |ctx, _, args| {
+                let handle = AbortHandle::from_signal(&args[0]).unwrap();
+
+                ctx.boolean(handle.is_aborted())
+            })
+            .unwrap();
+
+        ctx.global().set("isCancelled", cancel);
+        ctx.eval_script(
+            "globalThis.c = new AbortController();
+            globalThis.events = [];
+            c.signal.addEventListener('abort', (e) => events.push(e.type));
+            c.abort('stop');",
+            "<test>",
+        )
+        .unwrap();
+        ctx.run_until_idle().unwrap();
+
+        let ret = ctx
+            .eval_script(
+                "[isCancelled(c.signal), c.signal.reason, events.join()].join()",
+                "<test>",
+            )
+            .unwrap();
+
+        assert_eq!(ret.as_string().unwrap(), "true,stop,abort");
+    }
+
+    #[test]
+    fn host_abort() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let handle = AbortHandle::new();
+        let signal = ctx.abort_signal(&handle).unwrap();
+
+        ctx.global().set("signal", signal);
+        ctx.eval_script(
+            "globalThis.seen = null;
+            signal.onabort = () => { seen = signal.reason.name; };",
+            "<test>",
+        )
+        .unwrap();
+
+        let h = handle.clone();
+        thread::spawn(move || h.abort_with("shutting down")).join().unwrap();
+        ctx.run_until_idle().unwrap();
+
+        let ret = ctx
+            .eval_script("seen + ': ' + signal.reason.message", "<test>")
+            .unwrap();
+
+        assert_eq!(ret.as_string().unwrap(), "AbortError: shutting down");
+        assert!(ctx.eval_script("new AbortSignal()", "<test>").is_err());
+    }
+
+    #[test]
+    fn timeout_without_timers() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let ret = ctx
+            .eval_script(
+                "try { AbortSignal.timeout(10); } catch (e) { e.name }",
+                "<test>",
+            )
+            .unwrap();
+
+        assert_eq!(ret.as_string().unwrap(), "NotSupportedError");
+    }
+
+    #[test]
+    fn collected_signals() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        for _ in 0..3 {
+            ctx.abort_signal(&AbortHandle::new()).unwrap();
+        }
+        rt.run_gc();
+
+        let _signal = ctx.abort_signal(&AbortHandle::new()).unwrap();
+
+        assert_eq!(ctx.ptr.state().aborts.borrow().watched.len(), 1);
+    }
+
+    #[test]
+    fn context_freed() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let state = match ctx.ptr {
+            ContextPtr::Owned(ref owned) => Rc::downgrade(owned),
+            ContextPtr::Borrowed(_) => unreachable!(),
+        };
+
+        ctx.abort_signal(&AbortHandle::new()).unwrap();
+        drop(ctx);
+
+        assert!(state.upgrade().is_none());
+    }
+}
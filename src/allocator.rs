@@ -0,0 +1,519 @@
+use std::alloc::{self, GlobalAlloc, Layout};
+use std::cmp;
+use std::collections::HashMap;
+use std::os::raw::c_void;
+use std::ptr;
+
+use quickjs_sys as sys;
+
+use crate::runtime::{Context, RuntimeState};
+
+/// Every block starts with a header holding its size and the id of the
+/// context it's charged to. Keeps the payload 16 byte aligned.
+///
+/// Only blocks the engine allocates through `MALLOC_FUNCTIONS` have one,
+/// the global allocator of the process isn't replaced.
+const HEADER: usize = 16;
+/// Same bookkeeping overhead QuickJS' default allocator assumes.
+const MALLOC_OVERHEAD: usize = 8;
+
+/// Source of the memory of a runtime, see `Runtime::with_allocator`.
+///
+/// Mirrors `GlobalAlloc`, so existing allocators are easy to adapt, see
+/// `GlobalAllocator`. Requests are at most 16 byte aligned.
+///
+/// # Safety
+///
+/// Implementations must uphold the contract of the `GlobalAlloc` methods of
+/// the same name. They're only ever called from the runtime's thread.
+pub unsafe trait MemoryAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8;
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout);
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        let new = self
+            .alloc(Layout::from_size_align_unchecked(new_size, layout.align()));
+
+        if !new.is_null() {
+            ptr::copy_nonoverlapping(
+                ptr,
+                new,
+                cmp::min(layout.size(), new_size),
+            );
+            self.dealloc(ptr, layout);
+        }
+        new
+    }
+}
+
+/// Adapts a `GlobalAlloc`, e.g. jemalloc's, to `MemoryAllocator`.
+pub struct GlobalAllocator<A>(pub A);
+
+unsafe impl<A: GlobalAlloc> MemoryAllocator for GlobalAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.0.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        self.0.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Installed with `JS_NewRuntime2`, so per context accounting is limited to
+/// the engine's own memory.
+pub(crate) static MALLOC_FUNCTIONS: sys::JSMallocFunctions =
+    sys::JSMallocFunctions {
+        js_malloc: Some(js_malloc),
+        js_free: Some(js_free),
+        js_realloc: Some(js_realloc),
+        js_malloc_usable_size: Some(js_malloc_usable_size),
+    };
+
+fn layout(size: usize) -> Layout {
+    Layout::from_size_align(size + HEADER, HEADER).unwrap()
+}
+
+unsafe fn header(ptr: *const c_void) -> *mut usize {
+    (ptr as *mut u8).sub(HEADER) as *mut usize
+}
+
+/// Bump allocator backing a context created with `ContextBuilder::arena`.
+/// Freeing a block only counts it, once all are freed the arena starts over
+/// in its first chunk. The chunks are released when the context is gone and
+/// its last block freed. All of them count against the memory limit.
+pub(crate) struct Arena {
+    chunk_size: usize,
+    chunks: Vec<(*mut u8, Layout)>,
+    next: *mut u8,
+    end: *mut u8,
+    /// Blocks not freed yet.
+    live: usize,
+    /// Set once the context is dropped.
+    closed: bool,
+}
+
+impl Arena {
+    pub(crate) fn new(chunk_size: usize) -> Arena {
+        Arena {
+            chunk_size,
+            chunks: Vec::new(),
+            next: ptr::null_mut(),
+            end: ptr::null_mut(),
+            live: 0,
+            closed: false,
+        }
+    }
+}
+
+/// Size of a block in an arena, including its header.
+fn arena_size(size: usize) -> usize {
+    (size + 2 * HEADER - 1) & !(HEADER - 1)
+}
+
+unsafe fn state<'a>(s: *mut sys::JSMallocState) -> &'a RuntimeState {
+    &*((*s).opaque as *const RuntimeState)
+}
+
+/// Bytes left below the memory limit, counting the memory arenas hold on
+/// to besides their blocks.
+unsafe fn room(s: *mut sys::JSMallocState, state: &RuntimeState) -> usize {
+    let used = (*s).malloc_size.saturating_add(state.arena_slack.get());
+
+    (*s).malloc_limit.saturating_sub(used)
+}
+
+impl RuntimeState {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.allocator {
+            Some(ref a) => a.alloc(layout),
+            None => alloc::alloc(layout),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match self.allocator {
+            Some(ref a) => a.dealloc(ptr, layout),
+            None => alloc::dealloc(ptr, layout),
+        }
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        match self.allocator {
+            Some(ref a) => a.realloc(ptr, layout, new_size),
+            None => alloc::realloc(ptr, layout, new_size),
+        }
+    }
+
+    /// Allocates from the arena of `owner`, `room` being what `room`
+    /// returned. Returns `None` if it has no arena.
+    unsafe fn arena_alloc(
+        &self,
+        owner: usize,
+        size: usize,
+        room: usize,
+    ) -> Option<*mut u8> {
+        let mut arenas = self.arenas.borrow_mut();
+        let arena = arenas.get_mut(&owner)?;
+        let block = arena_size(size);
+
+        if (arena.end as usize - arena.next as usize) < block {
+            let len = cmp::max(arena.chunk_size, block);
+
+            // the rest of the chunk counts against the limit too
+            if len - block > room - size {
+                return Some(ptr::null_mut());
+            }
+
+            let layout = Layout::from_size_align(len, HEADER).unwrap();
+            let chunk = self.alloc(layout);
+
+            if chunk.is_null() {
+                return Some(ptr::null_mut());
+            }
+
+            arena.chunks.push((chunk, layout));
+            arena.next = chunk;
+            arena.end = chunk.add(len);
+            self.arena_slack.set(self.arena_slack.get() + len);
+        }
+
+        let ret = arena.next;
+
+        arena.next = ret.add(block);
+        arena.live += 1;
+        self.arena_slack.set(self.arena_slack.get() - block);
+        Some(ret)
+    }
+
+    /// Counts a block of `owner` of `size` bytes as freed. Returns `false`
+    /// if it has no arena.
+    unsafe fn arena_free(&self, owner: usize, size: usize) -> bool {
+        let mut arenas = self.arenas.borrow_mut();
+        let arena = match arenas.get_mut(&owner) {
+            Some(arena) => arena,
+            None => return false,
+        };
+
+        arena.live -= 1;
+        self.arena_slack.set(self.arena_slack.get() + arena_size(size));
+
+        if arena.live > 0 {
+            return true;
+        }
+
+        if arena.closed {
+            let arena = arenas.remove(&owner).unwrap();
+
+            drop(arenas);
+            self.release_arena(arena);
+        } else {
+            self.reset_arena(arena);
+        }
+        true
+    }
+
+    /// Starts over in the first chunk of an arena without blocks, releasing
+    /// the others.
+    unsafe fn reset_arena(&self, arena: &mut Arena) {
+        for (chunk, layout) in arena.chunks.drain(1..) {
+            self.dealloc(chunk, layout);
+            self.arena_slack.set(self.arena_slack.get() - layout.size());
+        }
+
+        let (chunk, layout) = arena.chunks[0];
+
+        arena.next = chunk;
+        arena.end = chunk.add(layout.size());
+    }
+
+    unsafe fn release_arena(&self, arena: Arena) {
+        for (chunk, layout) in arena.chunks {
+            self.dealloc(chunk, layout);
+            self.arena_slack.set(self.arena_slack.get() - layout.size());
+        }
+    }
+
+    /// Called after the context `owner` has been freed.
+    pub(crate) fn close_arena(&self, owner: usize) {
+        let mut arenas = self.arenas.borrow_mut();
+
+        if let Some(arena) = arenas.get_mut(&owner) {
+            arena.closed = true;
+
+            if arena.live == 0 {
+                let arena = arenas.remove(&owner).unwrap();
+
+                drop(arenas);
+                unsafe { self.release_arena(arena) };
+            }
+        }
+    }
+
+    /// Called after the runtime has been freed.
+    pub(crate) fn release_arenas(&self) {
+        let arenas = self.arenas.replace(HashMap::new());
+
+        for (_, arena) in arenas {
+            unsafe { self.release_arena(arena) };
+        }
+        self.arena_slack.set(0);
+    }
+
+    fn charge(&self, owner: usize, size: usize) {
+        self.allocated.set(self.allocated.get() + size);
+        self.allocated_total.set(self.allocated_total.get() + size as u64);
+
+        if let Some(peak) = self.peak_memory.get() {
+            self.peak_memory.set(Some(peak.max(self.allocated.get() as u64)));
+        }
+
+        *self.context_memory.borrow_mut().entry(owner).or_insert(0) += size;
+    }
+
+    fn credit(&self, owner: usize, size: usize) {
+        self.allocated.set(self.allocated.get() - size);
+        if let Some(used) = self.context_memory.borrow_mut().get_mut(&owner) {
+            *used = used.saturating_sub(size);
+        }
+    }
+}
+
+unsafe extern "C" fn js_malloc(
+    s: *mut sys::JSMallocState,
+    size: usize,
+) -> *mut c_void {
+    let state = state(s);
+
+    state.gc_finished();
+
+    let room = room(s, state);
+
+    if size > room {
+        return ptr::null_mut();
+    }
+
+    let owner = state.active_context.get();
+    let base = match state.arena_alloc(owner, size, room) {
+        Some(base) => base,
+        None => state.alloc(layout(size)),
+    };
+
+    if base.is_null() {
+        return ptr::null_mut();
+    }
+
+    *(base as *mut usize) = size;
+    *(base as *mut usize).add(1) = owner;
+    state.charge(owner, size);
+
+    (*s).malloc_count += 1;
+    (*s).malloc_size += size + MALLOC_OVERHEAD;
+
+    base.add(HEADER) as *mut c_void
+}
+
+unsafe extern "C" fn js_free(s: *mut sys::JSMallocState, ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let hdr = header(ptr);
+    let size = *hdr;
+    let owner = *hdr.add(1);
+    let state = state(s);
+
+    state.credit(owner, size);
+
+    (*s).malloc_count -= 1;
+    (*s).malloc_size -= size + MALLOC_OVERHEAD;
+
+    if !state.arena_free(owner, size) {
+        state.dealloc(hdr as *mut u8, layout(size));
+    }
+}
+
+unsafe extern "C" fn js_realloc(
+    s: *mut sys::JSMallocState,
+    ptr: *mut c_void,
+    size: usize,
+) -> *mut c_void {
+    if ptr.is_null() {
+        return js_malloc(s, size);
+    }
+
+    if size == 0 {
+        js_free(s, ptr);
+        return ptr::null_mut();
+    }
+
+    let hdr = header(ptr);
+    let old_size = *hdr;
+    let owner = *hdr.add(1);
+
+    let state = state(s);
+
+    state.gc_finished();
+
+    let room = room(s, state).saturating_add(old_size);
+
+    if size > room {
+        return ptr::null_mut();
+    }
+
+    let base = if !state.arenas.borrow().contains_key(&owner) {
+        state.realloc(hdr as *mut u8, layout(old_size), size + HEADER)
+    } else if arena_size(size) <= arena_size(old_size) {
+        // the block keeps its place, the end of it is lost
+        let lost = arena_size(old_size) - arena_size(size);
+
+        state.arena_slack.set(state.arena_slack.get() + lost);
+        hdr as *mut u8
+    } else {
+        let base = state.arena_alloc(owner, size, room).unwrap();
+
+        if !base.is_null() {
+            ptr::copy_nonoverlapping(hdr as *const u8, base, old_size + HEADER);
+            state.arena_free(owner, old_size);
+        }
+        base
+    };
+
+    if base.is_null() {
+        return ptr::null_mut();
+    }
+
+    *(base as *mut usize) = size;
+    state.credit(owner, old_size);
+    state.charge(owner, size);
+
+    (*s).malloc_size = (*s).malloc_size - old_size + size;
+
+    base.add(HEADER) as *mut c_void
+}
+
+unsafe extern "C" fn js_malloc_usable_size(ptr: *const c_void) -> usize {
+    if ptr.is_null() {
+        0
+    } else {
+        *header(ptr)
+    }
+}
+
+impl Context {
+    /// Bytes currently allocated on behalf of this context.
+    ///
+    /// An allocation is charged to the context whose `eval` or function
+    /// call is running when it's made, and to the runtime itself if there
+    /// is none. Objects created in one context and kept alive by another
+    /// stay charged to the first, so the numbers are an approximation.
+    pub fn memory_used(&self) -> usize {
+        unsafe {
+            let state = RuntimeState::from_context(self.ptr.as_ptr());
+            let id = self.ptr.state().id;
+            let used = state.context_memory.borrow().get(&id).cloned();
+
+            used.unwrap_or(0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::GlobalAllocator;
+    use crate::Runtime;
+
+    struct Counting(Arc<AtomicUsize>);
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn custom_allocator() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let alloc = GlobalAllocator(Counting(count.clone()));
+        let mut rt = Runtime::with_allocator(alloc).unwrap();
+        let before = count.load(Ordering::Relaxed);
+
+        rt.context().unwrap();
+
+        assert!(before > 0);
+        assert!(count.load(Ordering::Relaxed) > before);
+    }
+
+    #[test]
+    fn arena() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context_builder().arena(64 * 1024).build().unwrap();
+        let val = ctx
+            .eval(
+                "new Array(10000).fill(0).map((_, i) => 'x' + i).join('').length",
+                "<test>",
+                false,
+                false,
+            )
+            .unwrap();
+
+        assert_eq!(val.as_integer(), Some(48890));
+        assert!(ctx.memory_used() > 0);
+
+        drop(val);
+        drop(ctx);
+
+        let ctx = rt.context().unwrap();
+
+        assert_eq!(
+            ctx.eval_script("'ok'", "<test>").unwrap().as_string().unwrap(),
+            "ok"
+        );
+    }
+
+    #[test]
+    fn per_context() {
+        let mut rt = Runtime::default();
+        let mut a = rt.context().unwrap();
+        let mut b = rt.context().unwrap();
+
+        a.eval(
+            "globalThis.keep = new Array(100000).fill(0).map((_, i) => ({ i }));",
+            "<test>",
+            false,
+            false,
+        )
+        .unwrap();
+        b.eval("globalThis.keep = 1;", "<test>", false, false).unwrap();
+
+        assert!(a.memory_used() > b.memory_used() + 1_000_000);
+    }
+}
@@ -89,7 +89,7 @@ mod tests {
     #[test]
     fn new() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
 
         let _a1 = ctx.array(&[]);
     }
@@ -0,0 +1,200 @@
+use std::pin::Pin;
+use std::task::{self, Poll};
+
+use futures_core::Stream;
+use quickjs_sys as sys;
+
+use crate::object::Object;
+use crate::promise::PromiseFuture;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Stream over an async iterator, see `Value::async_iterate`.
+struct AsyncIter {
+    context: Context,
+    iterator: Result<Value, Option<Value>>,
+    next: Option<PromiseFuture>,
+}
+
+impl AsyncIter {
+    /// Calls `next` on the iterator.
+    fn advance(&self, iterator: &Value) -> Result<PromiseFuture, Value> {
+        let ctx = &self.context;
+        let next = Object { value: iterator.clone() }.get("next");
+        let next = next.map_err(|_| ctx.take_exception())?;
+        let ret = next.call(iterator.clone(), &[]);
+
+        if ret.is_exception() {
+            Err(ctx.take_exception())
+        } else {
+            Ok(PromiseFuture::new(ctx, Ok(ret)))
+        }
+    }
+}
+
+impl Stream for AsyncIter {
+    type Item = Result<Value, Value>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let iterator = match this.iterator {
+            Ok(ref iterator) => iterator.clone(),
+            Err(ref mut err) => return Poll::Ready(err.take().map(Err)),
+        };
+
+        if this.next.is_none() {
+            match this.advance(&iterator) {
+                Ok(next) => this.next = Some(next),
+                Err(err) => {
+                    this.iterator = Err(None);
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+
+        let ret = match Pin::new(this.next.as_mut().unwrap()).poll(cx) {
+            Poll::Ready(ret) => ret,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        this.next = None;
+
+        let item = ret.and_then(|res| {
+            let res = Object { value: res };
+            let done =
+                res.get("done").map_err(|_| this.context.take_exception());
+            let done = done?.as_boolean().unwrap_or(false);
+
+            if done {
+                Ok(None)
+            } else {
+                let value = res.get("value");
+                value.map(Some).map_err(|_| this.context.take_exception())
+            }
+        });
+
+        match item {
+            Ok(Some(val)) => Poll::Ready(Some(Ok(val))),
+            Ok(None) => {
+                this.iterator = Err(None);
+                Poll::Ready(None)
+            }
+            Err(err) => {
+                this.iterator = Err(None);
+                Poll::Ready(Some(Err(err)))
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Iterates over this async iterable, like `for await` does. Polling
+    /// runs pending jobs until the next item is produced. The stream ends
+    /// after the first error.
+    pub fn async_iterate(&self) -> impl Stream<Item = Result<Value, Value>> {
+        let ctx = Context { ptr: self.context.clone() };
+        let iterator = async_iterator(&ctx, self).map_err(Some);
+
+        AsyncIter { context: ctx, iterator, next: None }
+    }
+}
+
+/// Calls the `Symbol.asyncIterator` method of `val`.
+fn async_iterator(ctx: &Context, val: &Value) -> Result<Value, Value> {
+    let symbol = ctx.global().get("Symbol");
+    let symbol = symbol.map_err(|_| ctx.take_exception())?;
+    let key = Object { value: symbol }.get("asyncIterator");
+    let key = key.map_err(|_| ctx.take_exception())?;
+    let method = unsafe {
+        let atom = sys::JS_ValueToAtom(ctx.ptr.as_ptr(), key.value);
+
+        if atom == sys::JS_ATOM_NULL {
+            return Err(ctx.take_exception());
+        }
+
+        let method = sys::JS_GetProperty(ctx.ptr.as_ptr(), val.value, atom);
+
+        sys::JS_FreeAtom(ctx.ptr.as_ptr(), atom);
+        Value { value: method, context: ctx.ptr.clone() }
+    };
+
+    if method.is_exception() {
+        return Err(ctx.take_exception());
+    }
+
+    if !method.is_function() {
+        unsafe {
+            sys::JS_ThrowTypeError(
+                ctx.ptr.as_ptr(),
+                b"value is not async iterable\0".as_ptr() as *const i8,
+            );
+        }
+        return Err(ctx.take_exception());
+    }
+
+    let iterator = method.call(val.clone(), &[]);
+
+    if iterator.is_exception() {
+        Err(ctx.take_exception())
+    } else {
+        Ok(iterator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::task::{self, Poll};
+
+    use futures_core::Stream;
+
+    use crate::promise::noop_waker;
+    use crate::Runtime;
+
+    #[test]
+    fn async_iterate() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let gen = ctx
+            .eval_script(
+                r#"
+                (async function* () {
+                    yield 1;
+                    await null;
+                    yield 2;
+                    throw new Error("done");
+                })()
+                "#,
+                "<test>",
+            )
+            .unwrap();
+        let waker = noop_waker();
+        let mut cx = task::Context::from_waker(&waker);
+        let mut stream = Box::pin(gen.async_iterate());
+        let mut items = Vec::new();
+
+        loop {
+            match Pin::as_mut(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(item)) => items.push(item),
+                Poll::Ready(None) => break,
+                Poll::Pending => panic!("stream stalled"),
+            }
+        }
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].as_ref().unwrap().as_integer(), Some(1));
+        assert_eq!(items[1].as_ref().unwrap().as_integer(), Some(2));
+        assert!(items[2].is_err());
+
+        let mut stream = Box::pin(ctx.integer(1).async_iterate());
+
+        match Pin::as_mut(&mut stream).poll_next(&mut cx) {
+            Poll::Ready(Some(Err(_))) => {}
+            _ => panic!("not rejected"),
+        }
+        assert!(Pin::as_mut(&mut stream).poll_next(&mut cx).is_ready());
+    }
+}
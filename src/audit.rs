@@ -0,0 +1,119 @@
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+
+/// Longest string argument kept in an `AuditEvent` summary.
+const MAX_SUMMARY: usize = 64;
+
+/// A host function call or `std`/`os` operation made by a script, see
+/// `Runtime::set_audit_hook`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEvent {
+    /// Name of the host function, or the operation like `std.open`.
+    pub operation: String,
+    /// Short descriptions of the arguments. Long strings are truncated and
+    /// objects aren't expanded.
+    pub args: Vec<String>,
+}
+
+fn summarize(val: &Value) -> String {
+    if let Some(s) = val.as_string() {
+        if s.chars().count() > MAX_SUMMARY {
+            let s = s.chars().take(MAX_SUMMARY).collect::<String>();
+            format!("{:?}...", s)
+        } else {
+            format!("{:?}", s)
+        }
+    } else if val.is_symbol() {
+        "[symbol]".to_string()
+    } else if val.is_function() {
+        "[function]".to_string()
+    } else if val.is_array() {
+        "[array]".to_string()
+    } else if val.is_object() {
+        "[object]".to_string()
+    } else {
+        format!("{:?}", val)
+    }
+}
+
+impl Context {
+    /// Reports `operation` to the runtime's audit hook, if any, and counts
+    /// it for `EvalMetrics::functions_called`.
+    pub(crate) fn audit(&self, operation: &str, args: &[Value]) {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+
+        state.host_calls.set(state.host_calls.get() + 1);
+
+        // taken out while running so the hook can call back into scripts
+        let hook = state.audit_hook.borrow_mut().take();
+
+        if let Some(mut hook) = hook {
+            hook(AuditEvent {
+                operation: operation.to_string(),
+                args: args.iter().map(summarize).collect(),
+            });
+
+            let mut slot = state.audit_hook.borrow_mut();
+
+            if slot.is_none() {
+                *slot = Some(hook);
+            }
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn __audit(ctx: &Context, operation: &str, args: &[Value]) {
+    ctx.audit(operation, args);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::AuditEvent;
+    use crate::Runtime;
+
+    #[test]
+    fn audit_hook() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut rt = Runtime::default();
+        let ev = events.clone();
+
+        rt.set_audit_hook(move |event| ev.borrow_mut().push(event));
+
+        let mut ctx = rt.context().unwrap();
+        let f = ctx.closure("lookup", |ctx, _, _| ctx.null()).unwrap();
+
+        ctx.global().set("lookup", f);
+        ctx.eval(
+            r#"
+            import * as std from "std";
+            lookup("user", 42, {});
+            std.getenv("HOME");
+            "#,
+            "main.js",
+            false,
+            false,
+        )
+        .unwrap();
+
+        let events = events.borrow();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0],
+            AuditEvent {
+                operation: "lookup".to_string(),
+                args: vec![
+                    "\"user\"".to_string(),
+                    "42".to_string(),
+                    "[object]".to_string()
+                ],
+            }
+        );
+        assert_eq!(events[1].operation, "std.getenv");
+        assert_eq!(events[1].args, vec!["\"HOME\"".to_string()]);
+    }
+}
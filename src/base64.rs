@@ -0,0 +1,202 @@
+use std::error;
+use std::fmt;
+
+use crate::object::Object;
+use crate::runtime::Context;
+use crate::value::Value;
+
+const ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `data` as padded standard base64.
+pub(crate) fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                out.push(
+                    ALPHABET[(n >> (18 - 6 * idx) & 0x3f) as usize] as char,
+                );
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+/// Decodes base64 the way `atob` does: ASCII whitespace is skipped and
+/// padding is optional. Returns `None` if `input` isn't valid base64.
+pub(crate) fn decode(input: &str) -> Option<Vec<u8>> {
+    let mut digits = input
+        .bytes()
+        .filter(|b| !matches!(b, b' ' | b'\t' | b'\n' | b'\x0c' | b'\r'))
+        .collect::<Vec<_>>();
+
+    if digits.len() % 4 == 0 {
+        for _ in 0..2 {
+            if digits.last() == Some(&b'=') {
+                digits.pop();
+            }
+        }
+    }
+    if digits.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(digits.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+
+    for digit in digits {
+        let value = ALPHABET.iter().position(|&a| a == digit)?;
+
+        acc = acc << 6 | value as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+
+    Some(out)
+}
+
+/// Thrown by `atob` and `btoa`. Named like the `DOMException` browsers
+/// throw.
+#[derive(Debug)]
+struct InvalidCharacter(&'static str);
+
+impl fmt::Display for InvalidCharacter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl error::Error for InvalidCharacter {}
+
+fn throw_invalid_character(ctx: &Context, msg: &'static str) -> Value {
+    match ctx.error_from(&InvalidCharacter(msg)) {
+        Ok(err) => {
+            let mut obj = Object { value: err };
+
+            obj.set("name", ctx.string("InvalidCharacterError"));
+            ctx.throw(obj.value)
+        }
+        Err(ex) => ctx.throw(ex),
+    }
+}
+
+/// Converts an argument to a string, throwing for Symbols.
+fn to_string(val: Option<&Value>) -> Result<String, Value> {
+    val.map_or_else(|| Ok(String::from("undefined")), Value::to_js_string)
+}
+
+impl Context {
+    /// Installs `atob` and `btoa`. Like in browsers, they work on "binary
+    /// strings", whose characters are all in the Latin-1 range.
+    pub(crate) fn install_base64(&self) -> Result<(), Value> {
+        let mut global = self.global();
+        let btoa = self.internal_closure("btoa", |ctx, _, args| {
+            let input = match to_string(args.get(0)) {
+                Ok(input) => input,
+                Err(ex) => return ctx.throw(ex),
+            };
+            let bytes = input
+                .chars()
+                .map(|c| if (c as u32) < 0x100 { Some(c as u8) } else { None })
+                .collect::<Option<Vec<_>>>();
+
+            match bytes {
+                Some(bytes) => ctx.string(&encode(&bytes)),
+                None => throw_invalid_character(
+                    ctx,
+                    "string contains characters outside of the Latin-1 range",
+                ),
+            }
+        })?;
+        let atob = self.internal_closure("atob", |ctx, _, args| {
+            let input = match to_string(args.get(0)) {
+                Ok(input) => input,
+                Err(ex) => return ctx.throw(ex),
+            };
+
+            match decode(&input) {
+                Some(bytes) => ctx.byte_string(&bytes),
+                None => throw_invalid_character(
+                    ctx,
+                    "string is not correctly encoded",
+                ),
+            }
+        })?;
+
+        if !global.set("btoa", btoa) || !global.set("atob", atob) {
+            return Err(self.take_exception());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn codec() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+
+        assert_eq!(decode("Zm8=").unwrap(), b"fo");
+        assert_eq!(decode(" Zm\n8 ").unwrap(), b"fo");
+        assert!(decode("Zm8==").is_none());
+        assert!(decode("Z").is_none());
+        assert!(decode("Zm-8").is_none());
+    }
+
+    #[test]
+    fn globals() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let eval = |src: &str| {
+            ctx.eval_script(src, "<test>").unwrap().as_string().unwrap()
+        };
+
+        assert_eq!(eval("btoa('hello')"), "aGVsbG8=");
+        assert_eq!(
+            eval("[...atob(btoa('\\xff\\x00é'))].map((c) => c.charCodeAt(0)).join()"),
+            "255,0,233"
+        );
+        assert_eq!(
+            eval("try { btoa('€') } catch (e) { e.name }"),
+            "InvalidCharacterError"
+        );
+        assert_eq!(
+            eval("try { atob('*') } catch (e) { e.name }"),
+            "InvalidCharacterError"
+        );
+        assert_eq!(
+            eval("try { btoa(Symbol()) } catch (e) { e.name }"),
+            "TypeError"
+        );
+        assert_eq!(
+            eval(
+                "try { btoa({ toString() { throw 'no'; } }) } catch (e) { e }"
+            ),
+            "no"
+        );
+    }
+}
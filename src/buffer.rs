@@ -0,0 +1,94 @@
+use std::slice;
+
+use quickjs_sys as sys;
+
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Class ids of `ArrayBuffer` and `SharedArrayBuffer`, from the class enum
+/// in quickjs.c, which isn't exported.
+const BUFFER_CLASS_IDS: [sys::JSClassID; 2] = [19, 20];
+
+impl Context {
+    /// Creates an `ArrayBuffer` holding a copy of `data`.
+    pub fn array_buffer(&self, data: &[u8]) -> Result<Value, Value> {
+        let val = unsafe {
+            Value {
+                value: sys::JS_NewArrayBufferCopy(
+                    self.ptr.as_ptr(),
+                    data.as_ptr(),
+                    data.len(),
+                ),
+                context: self.ptr.clone(),
+            }
+        };
+
+        if val.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(val)
+        }
+    }
+}
+
+impl Value {
+    /// Runs `f` on the contents of this `ArrayBuffer`. Returns `None` if
+    /// this isn't an `ArrayBuffer` or it has been detached.
+    pub(crate) fn with_array_buffer<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        unsafe {
+            let is_buffer = BUFFER_CLASS_IDS
+                .iter()
+                .any(|&id| !sys::JS_GetOpaque(self.value, id).is_null());
+
+            if !is_buffer {
+                return None;
+            }
+
+            // a detached buffer still throws, keep what's pending
+            let ctx = self.context.as_ptr();
+            let pending = sys::JS_GetException(ctx);
+            let mut len = 0;
+            let data = sys::JS_GetArrayBuffer(ctx, &mut len, self.value);
+
+            let ret = if data.is_null() {
+                sys::Helper_JS_FreeValue(ctx, sys::JS_GetException(ctx));
+                None
+            } else {
+                Some(f(slice::from_raw_parts(data, len)))
+            };
+
+            sys::JS_Throw(ctx, pending);
+            ret
+        }
+    }
+
+    pub fn is_array_buffer(&self) -> bool {
+        self.with_array_buffer(|_| ()).is_some()
+    }
+
+    /// Copies the contents of this `ArrayBuffer`.
+    pub fn array_buffer_data(&self) -> Option<Vec<u8>> {
+        self.with_array_buffer(|data| data.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    #[test]
+    fn array_buffer() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let buf = ctx.array_buffer(b"\x00\x01\x02").unwrap();
+
+        assert!(buf.is_array_buffer());
+        assert_eq!(buf.array_buffer_data().unwrap(), vec![0, 1, 2]);
+        assert!(!ctx.integer(1).is_array_buffer());
+        assert!(!ctx.object().unwrap().value.is_array_buffer());
+    }
+}
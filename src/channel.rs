@@ -0,0 +1,199 @@
+use std::sync::{mpsc, Arc};
+
+use crate::error::Error;
+use crate::plain::PlainValue;
+use crate::promise::JobWakers;
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+
+/// Sends values to the scripts of a context, see `Context::message_channel`.
+/// Can be cloned and sent to other threads.
+#[derive(Clone)]
+pub struct MessageSender {
+    tx: mpsc::Sender<PlainValue>,
+    waiters: Arc<JobWakers>,
+}
+
+impl MessageSender {
+    /// Queues `val` for the `onMessage` callbacks, and wakes the tasks
+    /// awaiting promises of the context's runtime, as the callbacks may
+    /// settle them. Fails if the context is gone.
+    pub fn send(
+        &self,
+        val: PlainValue,
+    ) -> Result<(), mpsc::SendError<PlainValue>> {
+        self.tx.send(val)?;
+        self.waiters.wake_all();
+        Ok(())
+    }
+}
+
+/// Host end of the channel set up by `Context::message_channel`.
+pub(crate) struct Channel {
+    incoming: mpsc::Receiver<PlainValue>,
+    handlers: Vec<Value>,
+}
+
+impl Context {
+    /// Installs a global `host` object with `postMessage(value)` and
+    /// `onMessage(callback)`. Values posted by scripts arrive on the
+    /// returned receiver, values sent on the returned sender are passed to
+    /// all callbacks by `run_until_idle`. Values are converted to
+    /// `PlainValue`, so they can be sent across threads.
+    pub fn message_channel(
+        &self,
+    ) -> Result<(MessageSender, mpsc::Receiver<PlainValue>), Value> {
+        let (to_host, from_script) = mpsc::channel();
+        let (to_script, incoming) = mpsc::channel();
+        let mut host = self.object()?;
+
+        let post =
+            self.internal_closure("postMessage", move |ctx, _, args| {
+                let val = match args.get(0) {
+                    Some(val) => PlainValue::from_value(val),
+                    None => Ok(PlainValue::Undefined),
+                };
+
+                match val {
+                    Ok(val) => {
+                        // the host may have dropped the receiver, that's fine
+                        let _ = to_host.send(val);
+                        ctx.undefined()
+                    }
+                    Err(err) => {
+                        let err =
+                            ctx.error_from(&err).unwrap_or_else(|err| err);
+                        ctx.throw(err)
+                    }
+                }
+            })?;
+        let on_message =
+            self.internal_closure("onMessage", |ctx, _, args| {
+                match args.get(0) {
+                    Some(cb) if cb.is_function() => {
+                        let mut channel = ctx.ptr.state().channel.borrow_mut();
+
+                        if let Some(channel) = channel.as_mut() {
+                            channel.handlers.push(cb.clone());
+                        }
+                        ctx.undefined()
+                    }
+                    _ => {
+                        let err = ctx.string("onMessage expects a function");
+                        ctx.throw(err)
+                    }
+                }
+            })?;
+
+        if !host.set("postMessage", post)
+            || !host.set("onMessage", on_message)
+            || !self.global().set("host", host.value)
+        {
+            return Err(self.take_exception());
+        }
+
+        *self.ptr.state().channel.borrow_mut() =
+            Some(Channel { incoming, handlers: Vec::new() });
+
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+        let to_script =
+            MessageSender { tx: to_script, waiters: state.job_wakers.clone() };
+
+        Ok((to_script, from_script))
+    }
+
+    /// Passes the messages sent by the host so far to the `onMessage`
+    /// callbacks, one at a time. Returns `false` if there were none. If a
+    /// callback throws, the messages after the one it was called with stay
+    /// queued.
+    pub(crate) fn dispatch_messages(&self) -> Result<bool, Error> {
+        let mut dispatched = false;
+
+        loop {
+            let (msg, handlers) = {
+                let channel = self.ptr.state().channel.borrow();
+                let channel = match channel.as_ref() {
+                    Some(channel) => channel,
+                    None => return Ok(false),
+                };
+
+                match channel.incoming.try_recv() {
+                    Ok(msg) => (msg, channel.handlers.clone()),
+                    Err(_) => return Ok(dispatched),
+                }
+            };
+            let val = msg.to_value(self)?;
+
+            dispatched = true;
+            for handler in handlers.iter() {
+                let ret = handler.call(self.undefined(), &[val.clone()]);
+
+                if ret.is_exception() {
+                    return Err(Error::from(self.take_exception()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plain::PlainValue;
+    use crate::Runtime;
+
+    #[test]
+    fn ping_pong() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let (tx, rx) = ctx.message_channel().unwrap();
+
+        ctx.eval_script(
+            r#"
+            host.onMessage((n) => host.postMessage(n + 1));
+            host.postMessage("ready");
+            "#,
+            "<test>",
+        )
+        .unwrap();
+
+        tx.send(PlainValue::Int(1)).unwrap();
+        tx.send(PlainValue::Int(41)).unwrap();
+        ctx.run_until_idle().unwrap();
+
+        let msgs = rx.try_iter().collect::<Vec<_>>();
+
+        assert_eq!(
+            msgs,
+            vec![
+                PlainValue::String("ready".to_string()),
+                PlainValue::Int(2),
+                PlainValue::Int(42)
+            ]
+        );
+    }
+
+    #[test]
+    fn throwing_handler() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let (tx, rx) = ctx.message_channel().unwrap();
+
+        ctx.eval_script(
+            r#"
+            host.onMessage((n) => {
+                if (n === 1) throw new Error("bad");
+                host.postMessage(n);
+            });
+            "#,
+            "<test>",
+        )
+        .unwrap();
+
+        tx.send(PlainValue::Int(1)).unwrap();
+        tx.send(PlainValue::Int(2)).unwrap();
+        assert!(ctx.run_until_idle().is_err());
+        ctx.run_until_idle().unwrap();
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![PlainValue::Int(2)]);
+    }
+}
@@ -0,0 +1,264 @@
+use std::ptr;
+
+use quickjs_sys as sys;
+
+use crate::array::Array;
+use crate::object::Object;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// A script `Map`. Unlike plain objects, keys can be any value.
+pub struct JsMap {
+    pub(crate) value: Value,
+}
+
+/// A script `Set`.
+pub struct JsSet {
+    pub(crate) value: Value,
+}
+
+/// Whether `val` is an instance of the global constructor `class`.
+fn is_instance(val: &Value, class: &str) -> bool {
+    let ctx = Context { ptr: val.context.clone() };
+
+    match ctx.global().get(class) {
+        Ok(ctor) => unsafe {
+            sys::JS_IsInstanceOf(ctx.ptr.as_ptr(), val.value, ctor.value) > 0
+        },
+        Err(_) => {
+            ctx.take_exception();
+            false
+        }
+    }
+}
+
+/// Calls the method `name` of `obj`.
+fn call_method(
+    obj: &Value,
+    name: &str,
+    args: &[Value],
+) -> Result<Value, Value> {
+    let ctx = Context { ptr: obj.context.clone() };
+    let func = Object { value: obj.clone() }.get(name);
+    let func = func.map_err(|_| ctx.take_exception())?;
+    let ret = func.call(obj.clone(), args);
+
+    if ret.is_exception() {
+        Err(ctx.take_exception())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Number of elements of a `Map` or `Set`.
+fn size(obj: &Value) -> Result<usize, Value> {
+    let ctx = Context { ptr: obj.context.clone() };
+    let size = Object { value: obj.clone() }.get("size");
+    let size = size.map_err(|_| ctx.take_exception())?;
+
+    Ok(size.as_integer().unwrap_or(0) as usize)
+}
+
+/// Copies the elements of a `Map` or `Set` into a vector with `Array.from`.
+fn elements(obj: &Value) -> Result<Vec<Value>, Value> {
+    let ctx = Context { ptr: obj.context.clone() };
+    let array = ctx.global().get("Array");
+    let array = array.map_err(|_| ctx.take_exception())?;
+    let array = Array { value: call_method(&array, "from", &[obj.clone()])? };
+    let len = array.len()?;
+
+    (0..len as u32)
+        .map(|idx| array.get(idx).map_err(|_| ctx.take_exception()))
+        .collect()
+}
+
+impl JsMap {
+    /// Wraps `val` if it's a `Map`.
+    pub fn from_value(val: Value) -> Option<JsMap> {
+        if is_instance(&val, "Map") {
+            Some(JsMap { value: val })
+        } else {
+            None
+        }
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Value stored under `key`, `undefined` if there is none.
+    pub fn get(&self, key: &Value) -> Result<Value, Value> {
+        call_method(&self.value, "get", &[key.clone()])
+    }
+
+    pub fn set(&self, key: Value, val: Value) -> Result<(), Value> {
+        call_method(&self.value, "set", &[key, val]).map(|_| ())
+    }
+
+    pub fn has(&self, key: &Value) -> Result<bool, Value> {
+        let ret = call_method(&self.value, "has", &[key.clone()])?;
+        Ok(ret.as_boolean().unwrap_or(false))
+    }
+
+    /// Removes `key`. Returns `false` if it wasn't there.
+    pub fn delete(&self, key: &Value) -> Result<bool, Value> {
+        let ret = call_method(&self.value, "delete", &[key.clone()])?;
+        Ok(ret.as_boolean().unwrap_or(false))
+    }
+
+    pub fn size(&self) -> Result<usize, Value> {
+        size(&self.value)
+    }
+
+    /// Key-value pairs in insertion order.
+    pub fn entries(&self) -> Result<Vec<(Value, Value)>, Value> {
+        let ctx = Context { ptr: self.value.context.clone() };
+
+        elements(&self.value)?
+            .into_iter()
+            .map(|entry| {
+                let entry = Array { value: entry };
+                let key = entry.get(0).map_err(|_| ctx.take_exception())?;
+                let val = entry.get(1).map_err(|_| ctx.take_exception())?;
+
+                Ok((key, val))
+            })
+            .collect()
+    }
+}
+
+impl JsSet {
+    /// Wraps `val` if it's a `Set`.
+    pub fn from_value(val: Value) -> Option<JsSet> {
+        if is_instance(&val, "Set") {
+            Some(JsSet { value: val })
+        } else {
+            None
+        }
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn add(&self, val: Value) -> Result<(), Value> {
+        call_method(&self.value, "add", &[val]).map(|_| ())
+    }
+
+    pub fn has(&self, val: &Value) -> Result<bool, Value> {
+        let ret = call_method(&self.value, "has", &[val.clone()])?;
+        Ok(ret.as_boolean().unwrap_or(false))
+    }
+
+    /// Removes `val`. Returns `false` if it wasn't there.
+    pub fn delete(&self, val: &Value) -> Result<bool, Value> {
+        let ret = call_method(&self.value, "delete", &[val.clone()])?;
+        Ok(ret.as_boolean().unwrap_or(false))
+    }
+
+    pub fn size(&self) -> Result<usize, Value> {
+        size(&self.value)
+    }
+
+    /// Elements in insertion order.
+    pub fn values(&self) -> Result<Vec<Value>, Value> {
+        elements(&self.value)
+    }
+}
+
+impl Context {
+    /// Creates a new, empty `Map`.
+    pub fn js_map(&self) -> Result<JsMap, Value> {
+        self.construct("Map").map(|value| JsMap { value })
+    }
+
+    /// Creates a new, empty `Set`.
+    pub fn js_set(&self) -> Result<JsSet, Value> {
+        self.construct("Set").map(|value| JsSet { value })
+    }
+
+    /// Calls the global constructor `class` without arguments.
+    fn construct(&self, class: &str) -> Result<Value, Value> {
+        let ctor = self.global().get(class);
+        let ctor = ctor.map_err(|_| self.take_exception())?;
+        let val = unsafe {
+            Value {
+                value: sys::JS_CallConstructor(
+                    self.ptr.as_ptr(),
+                    ctor.value,
+                    0,
+                    ptr::null_mut(),
+                ),
+                context: self.ptr.clone(),
+            }
+        };
+
+        if val.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(val)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsMap, JsSet};
+    use crate::Runtime;
+
+    #[test]
+    fn map() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let map = ctx
+            .eval_script("globalThis.key = {}; new Map([[key, 1]])", "<test>")
+            .unwrap();
+        let map = JsMap::from_value(map).unwrap();
+        let key = ctx.eval_script("key", "<test>").unwrap();
+
+        assert_eq!(map.get(&key).unwrap().as_integer(), Some(1));
+        assert!(map.get(&ctx.object().unwrap().value).unwrap().is_undefined());
+
+        map.set(ctx.integer(2), ctx.string("two")).unwrap();
+
+        assert!(map.has(&ctx.integer(2)).unwrap());
+        assert!(!map.has(&ctx.string("2")).unwrap());
+        assert_eq!(map.size().unwrap(), 2);
+
+        let entries = map.entries().unwrap();
+
+        assert_eq!(entries[0].0, key);
+        assert_eq!(entries[1].1.as_string().unwrap(), "two");
+
+        assert!(map.delete(&key).unwrap());
+        assert!(!map.delete(&key).unwrap());
+        assert_eq!(map.size().unwrap(), 1);
+
+        assert!(JsMap::from_value(ctx.object().unwrap().value).is_none());
+        assert!(JsMap::from_value(ctx.js_set().unwrap().value).is_none());
+    }
+
+    #[test]
+    fn set() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let set = ctx.js_set().unwrap();
+
+        set.add(ctx.integer(1)).unwrap();
+        set.add(ctx.integer(1)).unwrap();
+        set.add(ctx.string("1")).unwrap();
+
+        assert_eq!(set.size().unwrap(), 2);
+        assert!(set.has(&ctx.string("1")).unwrap());
+
+        let values = set.values().unwrap();
+
+        assert_eq!(values[0].as_integer(), Some(1));
+        assert_eq!(values[1].as_string().unwrap(), "1");
+
+        let set = JsSet::from_value(set.value).unwrap();
+
+        assert!(set.delete(&ctx.integer(1)).unwrap());
+        assert_eq!(set.size().unwrap(), 1);
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::runtime::Context;
+use crate::sandbox::{self, FsAccess};
+use crate::value::Value;
+
+/// Installs `require`, given a function finding a module's file and source
+/// and one compiling its wrapper.
+const PRELUDE: &str = r#"(function (find, compile) {
+    const cache = Object.create(null);
+
+    function dirname(filename) {
+        const idx = filename.lastIndexOf("/");
+
+        return idx < 0 ? "." : filename.slice(0, idx);
+    }
+
+    function load(base, specifier) {
+        const [filename, source] = find(base, String(specifier));
+        const cached = cache[filename];
+
+        if (cached !== undefined) {
+            return cached.exports;
+        }
+
+        const module = {
+            id: filename,
+            filename,
+            exports: {},
+            loaded: false,
+            require: makeRequire(filename),
+        };
+
+        cache[filename] = module;
+
+        try {
+            if (filename.endsWith(".json")) {
+                module.exports = JSON.parse(source);
+            } else {
+                compile(filename, source).call(module.exports, module.exports,
+                    module.require, module, filename, dirname(filename));
+            }
+        } catch (err) {
+            delete cache[filename];
+            throw err;
+        }
+        module.loaded = true;
+
+        return module.exports;
+    }
+
+    function makeRequire(base) {
+        const require = (specifier) => load(base, specifier);
+
+        require.resolve = (specifier) => find(base, String(specifier))[0];
+        require.cache = cache;
+        return require;
+    }
+
+    globalThis.require = makeRequire("");
+})"#;
+
+impl Context {
+    /// Installs a CommonJS `require` loading files through the context's
+    /// `FsSandbox`, like file module imports.
+    pub(crate) fn install_require(&self) -> Result<(), Value> {
+        let find = self.internal_closure("find", |ctx, _, args| {
+            let arg = |idx: usize| {
+                args.get(idx).and_then(|v| v.as_string()).unwrap_or_default()
+            };
+            let (base, specifier) = (arg(0), arg(1));
+
+            match ctx.find_commonjs(&base, &specifier) {
+                Ok((filename, source)) => {
+                    let pair = ctx
+                        .array(&[ctx.string(&filename), ctx.string(&source)]);
+
+                    pair.map(Value::from).unwrap_or_else(|ex| ctx.throw(ex))
+                }
+                Err(err) => {
+                    let err = ctx.error_from(&err).unwrap_or_else(|ex| ex);
+
+                    ctx.throw(err)
+                }
+            }
+        })?;
+        let compile = self.internal_closure("compile", |ctx, _, args| {
+            let arg = |idx: usize| {
+                args.get(idx).and_then(|v| v.as_string()).unwrap_or_default()
+            };
+            // on the first line, to keep line numbers
+            let wrapper = format!(
+                "(function (exports, require, module, __filename, \
+                 __dirname) {{{}\n}})",
+                arg(1)
+            );
+
+            ctx.eval_script(&wrapper, &arg(0))
+                .unwrap_or_else(|ex| ctx.throw(ex))
+        })?;
+        let install = self.eval_script(PRELUDE, "<require>")?;
+        let ret = install.call(self.undefined(), &[find, compile]);
+
+        if ret.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Resolves `specifier`, required by the module `base`, to a file name
+    /// and reads the file. Like Node, tries the name as is, then with `.js`
+    /// and `.json` appended, then as a directory with an `index.js`.
+    fn find_commonjs(
+        &self,
+        base: &str,
+        specifier: &str,
+    ) -> io::Result<(String, String)> {
+        let name = sandbox::resolve_module_name(base, specifier);
+        let name = name.trim_end_matches('/');
+
+        if self.ptr.state().fs_sandbox.is_some() && !name.is_empty() {
+            let candidates = [
+                name.to_string(),
+                format!("{}.js", name),
+                format!("{}.json", name),
+                format!("{}/index.js", name),
+            ];
+
+            for filename in candidates.iter() {
+                let source = self
+                    .resolve_path(Path::new(filename), FsAccess::Read)
+                    .and_then(|path| {
+                        if path.is_file() {
+                            fs::read_to_string(path)
+                        } else {
+                            Err(io::ErrorKind::NotFound.into())
+                        }
+                    });
+
+                if let Ok(source) = source {
+                    return Ok((filename.clone(), source));
+                }
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("cannot find module '{}'", specifier),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use crate::{Chroot, Runtime};
+
+    #[test]
+    fn require() {
+        let root = env::temp_dir()
+            .join(format!("quickjs-rs-require-{}", process::id()));
+        let lib = root.join("lib");
+
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(
+            lib.join("index.js"),
+            "const { twice } = require('./math');
+            exports.answer = twice(require('../config.json').half);",
+        )
+        .unwrap();
+        fs::write(
+            lib.join("math.js"),
+            "module.exports = { twice: (n) => n * 2, dir: __dirname };",
+        )
+        .unwrap();
+        fs::write(root.join("config.json"), r#"{ "half": 21 }"#).unwrap();
+
+        let mut rt = Runtime::default();
+        let ctx = rt
+            .context_builder()
+            .fs_sandbox(Chroot::new(&root, false))
+            .require(true)
+            .build()
+            .unwrap();
+        let eval = |src: &str| ctx.eval_script(src, "<test>").unwrap();
+
+        assert_eq!(eval("require('./lib').answer").as_integer(), Some(42));
+        assert_eq!(
+            eval("require('./lib/math').dir").as_string().unwrap(),
+            "lib"
+        );
+        assert_eq!(
+            eval("require('./lib/math') === require('./lib/math.js')")
+                .as_boolean(),
+            Some(true)
+        );
+        assert!(ctx.eval_script("require('./missing')", "<test>").is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+use std::os::raw::c_void;
+use std::slice;
+
+use quickjs_sys as sys;
+
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// A script compiled to bytecode once that can be run many times, see
+/// `Context::compile`.
+pub struct CompiledScript {
+    function: Value,
+    bytecode: Vec<u8>,
+}
+
+impl CompiledScript {
+    /// Runs the script in the context it was compiled for and returns the
+    /// value of its last statement.
+    pub fn run(&self) -> Result<Value, Value> {
+        let ctx = Context { ptr: self.function.context.clone() };
+
+        ctx.eval_function(self.function.clone())
+    }
+
+    /// Loads the script into `ctx`, without compiling it again.
+    pub fn instantiate(&self, ctx: &Context) -> Result<CompiledScript, Value> {
+        // written by `compile`, for this QuickJS
+        unsafe { ctx.load_script(&self.bytecode) }
+    }
+
+    /// The bytecode, for caching. Load it with `Context::load_script` into a
+    /// runtime of the same QuickJS version.
+    pub fn bytecode(&self) -> &[u8] {
+        &self.bytecode
+    }
+}
+
+impl Context {
+    /// Compiles `input` as a global script without running it.
+    pub fn compile(
+        &self,
+        input: &str,
+        filename: &str,
+    ) -> Result<CompiledScript, Value> {
+        let flags =
+            (sys::JS_EVAL_TYPE_GLOBAL | sys::JS_EVAL_FLAG_COMPILE_ONLY) as i32;
+        let function = self.eval_flags(input.as_bytes(), filename, flags)?;
+        let bytecode = self.write_bytecode(&function)?;
+
+        Ok(CompiledScript { function, bytecode })
+    }
+
+    /// Serializes a compiled script or module.
+    pub(crate) fn write_bytecode(
+        &self,
+        function: &Value,
+    ) -> Result<Vec<u8>, Value> {
+        unsafe {
+            let mut len = 0;
+            let buf = sys::JS_WriteObject(
+                self.ptr.as_ptr(),
+                &mut len,
+                function.value,
+                sys::JS_WRITE_OBJ_BYTECODE as i32,
+            );
+
+            if buf.is_null() {
+                return Err(self.take_exception());
+            }
+
+            let bytecode = slice::from_raw_parts(buf, len).to_vec();
+
+            sys::js_free(self.ptr.as_ptr(), buf as *mut c_void);
+            Ok(bytecode)
+        }
+    }
+
+    /// Deserializes bytecode written by `write_bytecode`. Fails unless the
+    /// result has the value tag `tag`.
+    ///
+    /// QuickJS doesn't validate bytecode, so `bytecode` must come from
+    /// `write_bytecode` of the same QuickJS version.
+    pub(crate) unsafe fn read_bytecode(
+        &self,
+        bytecode: &[u8],
+        tag: i32,
+    ) -> Result<Value, Value> {
+        let function = Value {
+            value: sys::JS_ReadObject(
+                self.ptr.as_ptr(),
+                bytecode.as_ptr(),
+                bytecode.len(),
+                sys::JS_READ_OBJ_BYTECODE as i32,
+            ),
+            context: self.ptr.clone(),
+        };
+
+        if function.is_exception() {
+            Err(self.take_exception())
+        } else if function.value.tag != tag as i64 {
+            sys::JS_ThrowTypeError(
+                self.ptr.as_ptr(),
+                b"bytecode is of the wrong kind\0".as_ptr() as *const i8,
+            );
+            Err(self.take_exception())
+        } else {
+            Ok(function)
+        }
+    }
+
+    /// Loads a script from bytecode returned by `CompiledScript::bytecode`.
+    ///
+    /// # Safety
+    ///
+    /// QuickJS trusts bytecode, malformed input is undefined behavior.
+    /// `bytecode` must come from `CompiledScript::bytecode` of the same
+    /// QuickJS version, never from an untrusted source.
+    pub unsafe fn load_script(
+        &self,
+        bytecode: &[u8],
+    ) -> Result<CompiledScript, Value> {
+        let tag = sys::JS_TAG_FUNCTION_BYTECODE as i32;
+        let function = self.read_bytecode(bytecode, tag)?;
+
+        Ok(CompiledScript { function, bytecode: bytecode.to_vec() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    #[test]
+    fn run_many() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let script = ctx
+            .compile("globalThis.n = (globalThis.n || 0) + 1; n * 2", "<test>")
+            .unwrap();
+
+        for i in 1..=3 {
+            assert_eq!(script.run().unwrap().as_integer(), Some(i * 2));
+        }
+
+        let other = rt.context().unwrap();
+        let copy = script.instantiate(&other).unwrap();
+
+        assert_eq!(copy.run().unwrap().as_integer(), Some(2));
+        assert_eq!(script.run().unwrap().as_integer(), Some(8));
+    }
+}
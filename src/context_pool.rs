@@ -0,0 +1,140 @@
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+
+use crate::error::Error;
+use crate::runtime::{Context, Runtime};
+
+/// Recycles contexts of one runtime between short-lived uses. Returned
+/// contexts are cleaned with `Context::reset`, the verification hooks can
+/// check host state it doesn't cover.
+pub struct ContextPool {
+    runtime: RefCell<Runtime>,
+    idle: RefCell<Vec<Context>>,
+    verify: Vec<Box<dyn Fn(&Context) -> bool>>,
+}
+
+impl ContextPool {
+    pub fn new(runtime: Runtime) -> Self {
+        ContextPool {
+            runtime: RefCell::new(runtime),
+            idle: RefCell::new(Vec::new()),
+            verify: Vec::new(),
+        }
+    }
+
+    /// Adds a check run on every returned context after it was cleaned.
+    /// Returning `false` discards the context.
+    pub fn add_verify<F>(&mut self, f: F)
+    where
+        F: Fn(&Context) -> bool + 'static,
+    {
+        self.verify.push(Box::new(f));
+    }
+
+    /// Takes an idle context or creates a new one.
+    pub fn checkout(&self) -> Result<PooledContext<'_>, Error> {
+        let idle = self.idle.borrow_mut().pop();
+        let context = match idle {
+            Some(context) => context,
+            None => self.runtime.borrow_mut().context()?,
+        };
+
+        Ok(PooledContext { pool: self, context: Some(context) })
+    }
+
+    /// Number of contexts waiting to be reused.
+    pub fn idle(&self) -> usize {
+        self.idle.borrow().len()
+    }
+
+    fn recycle(&self, mut context: Context) {
+        if context.reset().is_err() {
+            return;
+        }
+
+        if self.verify.iter().all(|f| f(&context)) {
+            self.idle.borrow_mut().push(context);
+        }
+    }
+}
+
+/// A context checked out of a `ContextPool`. Goes back to the pool when
+/// dropped.
+pub struct PooledContext<'a> {
+    pool: &'a ContextPool,
+    context: Option<Context>,
+}
+
+impl<'a> Deref for PooledContext<'a> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        self.context.as_ref().unwrap()
+    }
+}
+
+impl<'a> DerefMut for PooledContext<'a> {
+    fn deref_mut(&mut self) -> &mut Context {
+        self.context.as_mut().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledContext<'a> {
+    fn drop(&mut self) {
+        if let Some(context) = self.context.take() {
+            self.pool.recycle(context);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn reuse() {
+        let pool = ContextPool::new(Runtime::default());
+
+        {
+            let mut ctx = pool.checkout().unwrap();
+            ctx.eval("globalThis.leak = 1;", "<test>", false, false).unwrap();
+        }
+        assert_eq!(pool.idle(), 1);
+
+        let ctx = pool.checkout().unwrap();
+
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(
+            ctx.eval_script("typeof leak", "<test>").unwrap().as_string(),
+            Some("undefined".to_string())
+        );
+    }
+
+    #[test]
+    fn verify() {
+        let mut pool = ContextPool::new(Runtime::default());
+        let reject = Rc::new(Cell::new(false));
+        let r = reject.clone();
+
+        pool.add_verify(|ctx| {
+            let ret = ctx
+                .eval_script("Array.prototype.evil === undefined", "<verify>");
+            ret.ok().and_then(|v| v.as_boolean()).unwrap_or(false)
+        });
+        pool.add_verify(move |_| !r.get());
+
+        {
+            let mut ctx = pool.checkout().unwrap();
+            ctx.eval("Array.prototype.evil = 1;", "<test>", false, false)
+                .unwrap();
+        }
+        assert_eq!(pool.idle(), 1);
+
+        reject.set(true);
+        drop(pool.checkout().unwrap());
+        assert_eq!(pool.idle(), 0);
+    }
+}
@@ -0,0 +1,412 @@
+use crate::array::Array;
+use crate::error::Error;
+use crate::object::Object;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Rust types that can be turned into script values, see `js_obj!`.
+pub trait IntoJs {
+    fn into_js(self, ctx: &Context) -> Result<Value, Value>;
+}
+
+/// Rust types that can be read from script values, see `Object::get_as`.
+pub trait FromJs: Sized {
+    /// What's expected, like "number", for error messages.
+    fn expected() -> String;
+
+    /// Converts `val`, or returns `None` if it's not what's expected.
+    fn from_js(val: &Value) -> Option<Self>;
+}
+
+/// Describes the type of `val` for error messages.
+pub(crate) fn type_name(val: &Value) -> &'static str {
+    if val.is_undefined() {
+        "undefined"
+    } else if val.is_null() {
+        "null"
+    } else if val.is_boolean() {
+        "boolean"
+    } else if val.is_number() {
+        "number"
+    } else if val.is_string() {
+        "string"
+    } else if val.is_symbol() {
+        "symbol"
+    } else if val.is_function() {
+        "function"
+    } else if val.is_array() {
+        "array"
+    } else {
+        "object"
+    }
+}
+
+impl FromJs for Value {
+    fn expected() -> String {
+        "any value".to_string()
+    }
+
+    fn from_js(val: &Value) -> Option<Self> {
+        Some(val.clone())
+    }
+}
+
+impl FromJs for bool {
+    fn expected() -> String {
+        "boolean".to_string()
+    }
+
+    fn from_js(val: &Value) -> Option<Self> {
+        val.as_boolean()
+    }
+}
+
+impl FromJs for f64 {
+    fn expected() -> String {
+        "number".to_string()
+    }
+
+    fn from_js(val: &Value) -> Option<Self> {
+        val.as_float()
+    }
+}
+
+macro_rules! from_js_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl FromJs for $ty {
+                fn expected() -> String {
+                    format!("integer in the range of {}", stringify!($ty))
+                }
+
+                fn from_js(val: &Value) -> Option<Self> {
+                    let f = val.as_float()?;
+
+                    // MAX of 64 bit types rounds up to the next power of
+                    // two, which doesn't fit, so compare with that
+                    if f.fract() == 0.0
+                        && f >= <$ty>::MIN as f64
+                        && f < <$ty>::MAX as f64 + 1.0
+                    {
+                        Some(f as $ty)
+                    } else {
+                        None
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_js_integer!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+impl FromJs for String {
+    fn expected() -> String {
+        "string".to_string()
+    }
+
+    fn from_js(val: &Value) -> Option<Self> {
+        val.as_string()
+    }
+}
+
+/// Argument `idx` of a host function call, if there is one of type `T`.
+#[cfg(any(feature = "intl", feature = "url"))]
+pub(crate) fn arg<T: FromJs>(args: &[Value], idx: usize) -> Option<T> {
+    args.get(idx).and_then(T::from_js)
+}
+
+/// `undefined` and `null` are `None`.
+impl<T: FromJs> FromJs for Option<T> {
+    fn expected() -> String {
+        format!("{} or undefined", T::expected())
+    }
+
+    fn from_js(val: &Value) -> Option<Self> {
+        if val.is_undefined() || val.is_null() {
+            Some(None)
+        } else {
+            T::from_js(val).map(Some)
+        }
+    }
+}
+
+impl<T: FromJs> FromJs for Vec<T> {
+    fn expected() -> String {
+        format!("array of {}", T::expected())
+    }
+
+    fn from_js(val: &Value) -> Option<Self> {
+        if !val.is_array() {
+            return None;
+        }
+
+        let array = Array { value: val.clone() };
+        let len = array.len().ok()?;
+
+        (0..len as u32).map(|idx| T::from_js(&array.get(idx).ok()?)).collect()
+    }
+}
+
+impl FromJs for Object {
+    fn expected() -> String {
+        "object".to_string()
+    }
+
+    fn from_js(val: &Value) -> Option<Self> {
+        val.clone().into_object()
+    }
+}
+
+impl IntoJs for Value {
+    fn into_js(self, _: &Context) -> Result<Value, Value> {
+        Ok(self)
+    }
+}
+
+impl<'a> IntoJs for &'a Value {
+    fn into_js(self, _: &Context) -> Result<Value, Value> {
+        Ok(self.clone())
+    }
+}
+
+impl IntoJs for Object {
+    fn into_js(self, _: &Context) -> Result<Value, Value> {
+        Ok(self.value)
+    }
+}
+
+impl IntoJs for Array {
+    fn into_js(self, _: &Context) -> Result<Value, Value> {
+        Ok(self.value)
+    }
+}
+
+/// Passes on errors, so the output of `js_obj!` can be nested.
+impl<T: IntoJs> IntoJs for Result<T, Value> {
+    fn into_js(self, ctx: &Context) -> Result<Value, Value> {
+        self?.into_js(ctx)
+    }
+}
+
+impl IntoJs for bool {
+    fn into_js(self, ctx: &Context) -> Result<Value, Value> {
+        Ok(ctx.boolean(self))
+    }
+}
+
+macro_rules! into_js_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoJs for $ty {
+                fn into_js(self, ctx: &Context) -> Result<Value, Value> {
+                    Ok(ctx.integer(self as i64))
+                }
+            }
+        )*
+    };
+}
+
+into_js_integer!(i8, i16, i32, i64, u8, u16, u32);
+
+impl IntoJs for f32 {
+    fn into_js(self, ctx: &Context) -> Result<Value, Value> {
+        Ok(ctx.float(self as f64))
+    }
+}
+
+impl IntoJs for f64 {
+    fn into_js(self, ctx: &Context) -> Result<Value, Value> {
+        Ok(ctx.float(self))
+    }
+}
+
+impl<'a> IntoJs for &'a str {
+    fn into_js(self, ctx: &Context) -> Result<Value, Value> {
+        Ok(ctx.string(self))
+    }
+}
+
+impl IntoJs for String {
+    fn into_js(self, ctx: &Context) -> Result<Value, Value> {
+        Ok(ctx.string(&self))
+    }
+}
+
+/// `None` is `null`.
+impl<T: IntoJs> IntoJs for Option<T> {
+    fn into_js(self, ctx: &Context) -> Result<Value, Value> {
+        match self {
+            Some(val) => val.into_js(ctx),
+            None => Ok(ctx.null()),
+        }
+    }
+}
+
+impl<T: IntoJs> IntoJs for Vec<T> {
+    fn into_js(self, ctx: &Context) -> Result<Value, Value> {
+        let vals = self
+            .into_iter()
+            .map(|val| val.into_js(ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        ctx.array(&vals).map(|array| array.value)
+    }
+}
+
+#[doc(hidden)]
+pub fn __js_object(
+    ctx: &Context,
+    props: Vec<(&str, Result<Value, Value>)>,
+) -> Result<Value, Value> {
+    let mut obj = ctx.object()?;
+
+    for (key, val) in props {
+        if !obj.set(key, val?) {
+            return Err(ctx.take_exception());
+        }
+    }
+
+    Ok(obj.value)
+}
+
+#[doc(hidden)]
+pub fn __js_array(
+    ctx: &Context,
+    vals: Vec<Result<Value, Value>>,
+) -> Result<Value, Value> {
+    let vals = vals.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    ctx.array(&vals).map(|array| array.value)
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __js_key {
+    ($key:ident) => {
+        stringify!($key)
+    };
+    ($key:literal) => {
+        $key
+    };
+}
+
+/// Builds an object from `key: value` pairs. Keys are identifiers or string
+/// literals, values anything implementing `IntoJs`. Evaluates to a
+/// `Result<Value, Value>`.
+#[macro_export]
+macro_rules! js_obj {
+    ($ctx:expr, { $($key:tt : $val:expr),* $(,)? }) => {
+        match &$ctx {
+            ctx => $crate::__js_object(
+                ctx,
+                vec![$((
+                    $crate::__js_key!($key),
+                    $crate::IntoJs::into_js($val, ctx),
+                )),*],
+            ),
+        }
+    };
+}
+
+/// Builds an array from values implementing `IntoJs`, like `js_obj!`.
+#[macro_export]
+macro_rules! js_arr {
+    ($ctx:expr, [ $($val:expr),* $(,)? ]) => {
+        match &$ctx {
+            ctx => $crate::__js_array(
+                ctx,
+                vec![$($crate::IntoJs::into_js($val, ctx)),*],
+            ),
+        }
+    };
+}
+
+impl Object {
+    /// Reads the property `key` as a `T`. Fails with a message like
+    /// "expected number at key 'timeout', got string" if it's something
+    /// else.
+    pub fn get_as<T: FromJs>(&self, key: &str) -> Result<T, Error> {
+        let val = match self.get(key) {
+            Ok(val) => val,
+            Err(_) => {
+                let ctx = Context { ptr: self.value.context.clone() };
+                return Err(Error::from(ctx.take_exception()));
+            }
+        };
+
+        T::from_js(&val).ok_or_else(|| {
+            Error::Conversion(format!(
+                "expected {} at key '{}', got {}",
+                T::expected(),
+                key,
+                type_name(&val)
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object::Object;
+    use crate::{Error, Runtime};
+
+    #[test]
+    fn construction_macros() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let val = crate::js_obj!(ctx, {
+            name: "x",
+            port: 8080,
+            "max-age": 3.5,
+            proxy: None::<&str>,
+            tags: crate::js_arr!(ctx, [1, "two", vec![3]]),
+        })
+        .unwrap();
+
+        assert_eq!(
+            val.to_json_string(false).unwrap(),
+            r#"{"name":"x","port":8080,"max-age":3.5,"proxy":null,"tags":[1,"two",[3]]}"#
+        );
+        assert!(crate::js_arr!(ctx, []).unwrap().is_array());
+    }
+
+    #[test]
+    fn get_as() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let val = ctx
+            .eval_script(
+                r#"({ port: 8080, ratio: 0.5, name: "srv", tags: ["a", "b"],
+                      timeout: "5s", big: 70000, huge: 2 ** 64,
+                      tls: { cert: "a.pem" } })"#,
+                "<test>",
+            )
+            .unwrap();
+        let obj = val.into_object().unwrap();
+
+        assert_eq!(obj.get_as::<u16>("port").unwrap(), 8080);
+        assert_eq!(obj.get_as::<f64>("ratio").unwrap(), 0.5);
+        assert_eq!(obj.get_as::<String>("name").unwrap(), "srv");
+        assert_eq!(obj.get_as::<Vec<String>>("tags").unwrap(), ["a", "b"]);
+        assert_eq!(obj.get_as::<Option<bool>>("missing").unwrap(), None);
+
+        match obj.get_as::<f64>("timeout") {
+            Err(Error::Conversion(msg)) => {
+                assert_eq!(msg, "expected number at key 'timeout', got string")
+            }
+            ret => panic!("{:?}", ret),
+        }
+        assert!(obj.get_as::<u16>("big").is_err());
+        assert!(obj.get_as::<i32>("ratio").is_err());
+        assert!(obj.get_as::<u64>("huge").is_err());
+        assert_eq!(
+            obj.get_as::<Object>("tls")
+                .unwrap()
+                .get_as::<String>("cert")
+                .unwrap(),
+            "a.pem"
+        );
+        assert!(obj.get_as::<Object>("name").is_err());
+    }
+}
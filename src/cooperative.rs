@@ -0,0 +1,164 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{self, Poll};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+
+/// Future returned by `Context::eval_cooperative`.
+pub struct EvalCooperative<'a> {
+    context: &'a mut Context,
+    source: Option<(String, String)>,
+    strict: bool,
+    strip: bool,
+    result: Option<Value>,
+    slice: Duration,
+}
+
+impl Context {
+    /// Evaluates `input` and drives the job queue, giving control back to
+    /// the executor polling the returned future at least every `slice`.
+    /// The engine can't be suspended, so that happens between jobs, code
+    /// running past a slice only yields the thread to the OS scheduler.
+    /// Resolves to the value of `input` or the first error.
+    pub fn eval_cooperative(
+        &mut self,
+        input: &str,
+        filename: &str,
+        strict: bool,
+        strip: bool,
+        slice: Duration,
+    ) -> EvalCooperative<'_> {
+        EvalCooperative {
+            context: self,
+            source: Some((input.to_string(), filename.to_string())),
+            strict,
+            strip,
+            result: None,
+            slice,
+        }
+    }
+}
+
+impl<'a> Future for EvalCooperative<'a> {
+    type Output = Result<Value, Error>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let state =
+            unsafe { RuntimeState::from_context(this.context.ptr.as_ptr()) };
+        let slice_end = Instant::now() + this.slice;
+        let outer = state.slice.replace(Some((slice_end, this.slice)));
+        let ret = this.run_slice(cx, slice_end);
+
+        state.slice.set(outer);
+        ret
+    }
+}
+
+impl<'a> EvalCooperative<'a> {
+    fn run_slice(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        slice_end: Instant,
+    ) -> Poll<Result<Value, Error>> {
+        if let Some((input, filename)) = self.source.take() {
+            match self.context.eval(&input, &filename, self.strict, self.strip)
+            {
+                Ok(val) => self.result = Some(val),
+                Err(ex) => return Poll::Ready(Err(Error::from(ex))),
+            }
+
+            if Instant::now() >= slice_end {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+        }
+
+        loop {
+            match self.context.run_pending_job() {
+                Ok(true) if Instant::now() >= slice_end => {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => {
+                    if let Err(err) = self.context.report_uncaught(err) {
+                        return Poll::Ready(Err(err));
+                    }
+                }
+            }
+        }
+
+        if let Err(err) = self.context.take_unhandled_rejection() {
+            return Poll::Ready(Err(err));
+        }
+
+        let ret = match self.result.take() {
+            Some(val) => val,
+            None => self.context.undefined(),
+        };
+
+        Poll::Ready(Ok(ret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{self, Poll};
+    use std::time::Duration;
+
+    use crate::promise::noop_waker;
+    use crate::Runtime;
+
+    #[test]
+    fn yields_between_jobs() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let waker = noop_waker();
+        let mut cx = task::Context::from_waker(&waker);
+        let mut polls = 0;
+
+        {
+            let mut fut = ctx.eval_cooperative(
+                r#"
+                globalThis.done = false;
+                (async () => {
+                    for (let i = 0; i < 1000; i++) await null;
+                    globalThis.done = true;
+                })();
+                "#,
+                "<test>",
+                false,
+                false,
+                Duration::from_secs(0),
+            );
+
+            loop {
+                polls += 1;
+
+                match Pin::new(&mut fut).poll(&mut cx) {
+                    Poll::Ready(ret) => {
+                        assert!(ret.is_ok());
+                        break;
+                    }
+                    Poll::Pending => {}
+                }
+            }
+        }
+
+        assert!(polls > 1);
+        assert_eq!(
+            ctx.eval_script("done", "<test>").unwrap().as_boolean(),
+            Some(true)
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::runtime::{Context, Runtime};
+
+/// Replaces `Date` and `Math.random` with versions driven by the host.
+const PRELUDE: &str = r#"(function (now, random) {
+    const RealDate = Date;
+
+    function DeterministicDate(...args) {
+        if (new.target === undefined) {
+            return new RealDate(now()).toString();
+        }
+        return args.length === 0 ? new RealDate(now()) : new RealDate(...args);
+    }
+
+    DeterministicDate.prototype = RealDate.prototype;
+    DeterministicDate.prototype.constructor = DeterministicDate;
+    DeterministicDate.UTC = RealDate.UTC;
+    DeterministicDate.parse = RealDate.parse;
+    DeterministicDate.now = now;
+
+    globalThis.Date = DeterministicDate;
+    Math.random = random;
+})"#;
+
+/// A context without sources of nondeterminism.
+///
+/// `Math.random` is seeded, the clock seen by `Date` only moves when the
+/// host says so, the `std` and `os` modules are unavailable and the context
+/// is limited to a fixed amount of fuel. Running the same scripts on the
+/// same inputs gives the same results, which is what consensus and
+/// record/replay setups rely on.
+pub struct DeterministicContext {
+    context: Context,
+    clock: Rc<Cell<f64>>,
+}
+
+impl DeterministicContext {
+    /// Creates a new context in `rt` with `fuel` to run on, see
+    /// `Context::set_fuel`. The clock starts at the Unix epoch.
+    pub fn new(rt: &mut Runtime, seed: u64, fuel: u64) -> Result<Self, Error> {
+        let clock = Rc::new(Cell::new(0.0));
+        let state = Rc::new(Cell::new(if seed == 0 {
+            0x9e37_79b9_7f4a_7c15
+        } else {
+            seed
+        }));
+        let c = clock.clone();
+        let mut context = rt
+            .context_builder()
+            .std_module(false)
+            .os_module(false)
+            .setup(move |ctx| install(ctx, c.clone(), state.clone()))
+            .build()?;
+
+        context.set_fuel(Some(fuel));
+
+        Ok(DeterministicContext { context, clock })
+    }
+
+    /// Current time in milliseconds since the Unix epoch.
+    pub fn time(&self) -> f64 {
+        self.clock.get()
+    }
+
+    pub fn set_time(&self, millis: f64) {
+        self.clock.set(millis);
+    }
+
+    pub fn advance(&self, millis: f64) {
+        self.clock.set(self.clock.get() + millis);
+    }
+}
+
+impl Deref for DeterministicContext {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        &self.context
+    }
+}
+
+impl DerefMut for DeterministicContext {
+    fn deref_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+}
+
+/// Replaces `Date` and `Math.random` of `ctx`.
+fn install(
+    ctx: &Context,
+    clock: Rc<Cell<f64>>,
+    state: Rc<Cell<u64>>,
+) -> Result<(), Error> {
+    let now =
+        ctx.internal_closure("now", move |ctx, _, _| ctx.float(clock.get()))?;
+    let random = ctx.internal_closure("random", move |ctx, _, _| {
+        ctx.float(next_random(&state))
+    })?;
+    let install = ctx.eval_script(PRELUDE, "<deterministic>")?;
+    let ret = install.call(ctx.undefined(), &[now, random]);
+
+    if ret.is_exception() {
+        return Err(Error::from(ctx.take_exception()));
+    }
+
+    Ok(())
+}
+
+/// xorshift64*, returns a float in [0, 1).
+fn next_random(state: &Cell<u64>) -> f64 {
+    let mut x = state.get();
+
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    state.set(x);
+
+    (x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_random() {
+        let mut rt = Runtime::default();
+        let a = DeterministicContext::new(&mut rt, 42, 1_000).unwrap();
+        let b = DeterministicContext::new(&mut rt, 42, 1_000).unwrap();
+
+        let x = a.eval_script("Math.random()", "<test>").unwrap();
+        let y = b.eval_script("Math.random()", "<test>").unwrap();
+
+        assert_eq!(x.as_float(), y.as_float());
+    }
+
+    #[test]
+    fn clock() {
+        let mut rt = Runtime::default();
+        let ctx = DeterministicContext::new(&mut rt, 1, 1_000).unwrap();
+
+        ctx.set_time(1_000.0);
+        assert_eq!(
+            ctx.eval_script("Date.now()", "<test>").unwrap().as_float(),
+            Some(1_000.0)
+        );
+
+        ctx.advance(500.0);
+        assert_eq!(
+            ctx.eval_script("new Date().getTime()", "<test>")
+                .unwrap()
+                .as_float(),
+            Some(1_500.0)
+        );
+        assert_eq!(
+            ctx.eval_script("new Date() instanceof Date", "<test>")
+                .unwrap()
+                .as_boolean(),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn no_modules() {
+        let mut rt = Runtime::default();
+        let mut ctx = DeterministicContext::new(&mut rt, 1, 1_000).unwrap();
+
+        assert!(ctx
+            .eval(r#"import * as os from "os";"#, "<test>", false, false)
+            .is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt::Display;
+
+use miette::{
+    Diagnostic, LabeledSpan, MietteError, MietteSpanContents, SourceCode,
+    SourceSpan, SpanContents,
+};
+
+use crate::error::Error;
+use crate::sources::SourceExcerpt;
+
+impl SourceExcerpt {
+    /// Span of the offending code within `text`: from the column to the end
+    /// of the word there, or the whole line if the column isn't known.
+    fn span(&self) -> SourceSpan {
+        let start = match self.column {
+            Some(column) => self
+                .text
+                .char_indices()
+                .nth(column.saturating_sub(1) as usize)
+                .map_or(self.text.len(), |(idx, _)| idx),
+            None => self.text.len() - self.text.trim_start().len(),
+        };
+        let len = match self.column {
+            Some(_) => self.text[start..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(self.text.len() - start)
+                .max(1),
+            None => self.text.trim_end().len() - start,
+        };
+
+        SourceSpan::from((start, len))
+    }
+}
+
+/// Only the excerpt's line is known, so that's all there is to show. It's
+/// reported under its real line number.
+impl SourceCode for SourceExcerpt {
+    fn read_span<'a>(
+        &'a self,
+        _span: &SourceSpan,
+        _context_lines_before: usize,
+        _context_lines_after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        Ok(Box::new(MietteSpanContents::new_named(
+            self.filename.clone(),
+            self.text.as_bytes(),
+            SourceSpan::from((0, self.text.len())),
+            self.line.saturating_sub(1) as usize,
+            0,
+            1,
+        )))
+    }
+}
+
+impl Error {
+    fn excerpt(&self) -> Option<&SourceExcerpt> {
+        match self {
+            Error::Exception(ref ex) | Error::UnhandledRejection(ref ex) => {
+                ex.excerpt.as_ref()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Exceptions of code in the runtime's source registry point to the
+/// offending line.
+impl Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let code = match self {
+            Error::Exception(_) => "quickjs::exception",
+            Error::UnhandledRejection(_) => "quickjs::unhandled_rejection",
+            Error::Timeout => "quickjs::timeout",
+            Error::Conversion(_) => "quickjs::conversion",
+            Error::MissingProperty(_) => "quickjs::missing_property",
+            Error::PoolClosed => "quickjs::pool_closed",
+            Error::WorkerPanicked => "quickjs::worker_panicked",
+            Error::OutOfMemory => "quickjs::out_of_memory",
+        };
+
+        Some(Box::new(code))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        match self {
+            Error::Exception(ref ex) | Error::UnhandledRejection(ref ex) => {
+                let stack = ex.stack.as_ref()?.trim_end();
+
+                if stack.is_empty() {
+                    None
+                } else {
+                    Some(Box::new(format!("stack trace:\n{}", stack)))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.excerpt().map(|ex| ex as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let excerpt = self.excerpt()?;
+        let label = LabeledSpan::at(excerpt.span(), "thrown here");
+
+        Some(Box::new(std::iter::once(label)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::Diagnostic;
+
+    use crate::{Error, Runtime};
+
+    #[test]
+    fn labels() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        rt.enable_source_registry(true);
+
+        let ex =
+            ctx.eval_script("let a = 1;\n  missing();", "main.js").unwrap_err();
+        let err = Error::from(ex);
+        let label = err.labels().unwrap().next().unwrap();
+        let code = err.source_code().unwrap();
+        let contents = code.read_span(label.inner(), 0, 0).unwrap();
+
+        assert!(label.offset() >= 2);
+        assert_eq!(contents.line(), 1);
+        assert_eq!(contents.name(), Some("main.js"));
+        assert_eq!(contents.data(), b"  missing();");
+        assert_eq!(err.code().unwrap().to_string(), "quickjs::exception");
+
+        let plain = Error::Timeout;
+
+        assert!(plain.labels().is_none());
+        assert!(plain.source_code().is_none());
+    }
+}
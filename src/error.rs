@@ -0,0 +1,384 @@
+use std::error;
+#[cfg(any(feature = "intl", feature = "url"))]
+use std::ffi::CString;
+use std::fmt;
+use std::panic::Location;
+
+use quickjs_sys as sys;
+
+use crate::object::Object;
+use crate::runtime::{Context, RuntimeState};
+use crate::sources::{self, SourceExcerpt};
+use crate::value::Value;
+
+/// The parts of a JavaScript exception that are useful outside the engine.
+///
+/// Unlike the exception `Value` itself, this is plain data and can be sent
+/// to other threads or kept after the context is gone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExceptionDetails {
+    pub name: Option<String>,
+    pub message: String,
+    pub stack: Option<String>,
+    /// The line the exception was thrown at, if its code is in the source
+    /// registry.
+    pub excerpt: Option<SourceExcerpt>,
+}
+
+impl ExceptionDetails {
+    pub fn from_value(val: &Value) -> Self {
+        if val.is_object() {
+            let obj = Object { value: val.clone() };
+            let prop = |key| obj.get(key).ok().and_then(|v| v.as_string());
+
+            let stack = prop("stack");
+
+            ExceptionDetails {
+                name: prop("name"),
+                message: prop("message")
+                    .unwrap_or_else(|| format!("{:?}", val)),
+                excerpt: stack.as_ref().and_then(|s| sources::excerpt(val, s)),
+                stack,
+            }
+        } else {
+            ExceptionDetails {
+                name: None,
+                message: format!("{:?}", val),
+                stack: None,
+                excerpt: None,
+            }
+        }
+    }
+}
+
+impl ExceptionDetails {
+    /// Formats the exception for humans: the message, the offending line
+    /// if its source is registered, and the stack trace if there is one.
+    pub fn render(&self) -> String {
+        let mut out = self.to_string();
+
+        if let Some(ref excerpt) = self.excerpt {
+            excerpt.render(&mut out);
+        }
+
+        if let Some(ref stack) = self.stack {
+            for line in stack.lines().filter(|l| !l.trim().is_empty()) {
+                out.push_str("\n    ");
+                out.push_str(line.trim());
+            }
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for ExceptionDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "{}: {}", name, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Failure of an operation that can go wrong in more ways than a script
+/// throwing. Those that can only throw return the exception `Value`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A JavaScript exception was thrown.
+    Exception(ExceptionDetails),
+    /// A promise was rejected and no handler was attached by the time the
+    /// job queue ran empty.
+    UnhandledRejection(ExceptionDetails),
+    /// The script was aborted because it ran past its deadline.
+    Timeout,
+    /// A value couldn't be converted to the requested type.
+    Conversion(String),
+    /// A property on the way to a nested one doesn't exist. Holds the path
+    /// up to it.
+    MissingProperty(String),
+    /// The runtime pool has shut down.
+    PoolClosed,
+    /// A runtime pool worker panicked while running the script.
+    WorkerPanicked,
+    /// The engine couldn't allocate memory.
+    OutOfMemory,
+}
+
+impl From<Value> for Error {
+    fn from(val: Value) -> Self {
+        Error::Exception(ExceptionDetails::from_value(&val))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            &Error::Exception(ref ex) => write!(f, "{}", ex),
+            &Error::UnhandledRejection(ref ex) => {
+                write!(f, "unhandled promise rejection: {}", ex)
+            }
+            &Error::Timeout => write!(f, "script timed out"),
+            &Error::Conversion(ref msg) => write!(f, "{}", msg),
+            &Error::MissingProperty(ref path) => {
+                write!(f, "property '{}' is missing", path)
+            }
+            &Error::PoolClosed => write!(f, "runtime pool is shut down"),
+            &Error::WorkerPanicked => write!(f, "runtime pool worker panicked"),
+            &Error::OutOfMemory => write!(f, "out of memory"),
+        }
+    }
+}
+
+// `Error` is `Send + Sync + 'static`, so anyhow's blanket `From` impl covers
+// the conversion into `anyhow::Error`. The stack stays reachable through
+// `anyhow::Error::downcast_ref::<Error>()`.
+impl error::Error for Error {}
+
+/// `msg` for the `%s` of the engine's throw functions, without the nul
+/// bytes C strings can't hold.
+#[cfg(any(feature = "intl", feature = "url"))]
+fn c_message(msg: &str) -> CString {
+    CString::new(msg.replace('\0', "")).unwrap_or_default()
+}
+
+impl Context {
+    /// Throws `val` and returns the exception marker. Native callbacks return
+    /// it to make the call site in the script see the throw.
+    #[track_caller]
+    pub fn throw(&self, val: Value) -> Value {
+        unsafe {
+            let state = RuntimeState::from_context(self.ptr.as_ptr());
+
+            if state.record_throw_location.get() && val.is_object() {
+                let loc = Location::caller();
+                let mut obj = Object { value: val.clone() };
+
+                obj.define(
+                    "rustLocation",
+                    self.string(&format!(
+                        "{}:{}:{}",
+                        loc.file(),
+                        loc.line(),
+                        loc.column()
+                    )),
+                    sys::JS_PROP_CONFIGURABLE | sys::JS_PROP_WRITABLE,
+                );
+            }
+
+            let ex = sys::JS_Throw(
+                self.ptr.as_ptr(),
+                sys::Helper_JS_DupValue(self.ptr.as_ptr(), val.value),
+            );
+            Value { value: ex, context: self.ptr.clone() }
+        }
+    }
+
+    /// Throws `err` as a JavaScript `Error`, including its cause chain.
+    #[cfg(feature = "anyhow")]
+    #[track_caller]
+    pub fn throw_anyhow(&self, err: &anyhow::Error) -> Value {
+        match self.error_from(&**err) {
+            Ok(val) => self.throw(val),
+            Err(ex) => self.throw(ex),
+        }
+    }
+
+    /// Throws a `TypeError` with the message `msg`.
+    #[cfg(feature = "url")]
+    pub(crate) fn throw_type_error(&self, msg: &str) -> Value {
+        let msg = c_message(msg);
+
+        unsafe {
+            Value {
+                value: sys::JS_ThrowTypeError(
+                    self.ptr.as_ptr(),
+                    b"%s\0".as_ptr() as *const i8,
+                    msg.as_ptr(),
+                ),
+                context: self.ptr.clone(),
+            }
+        }
+    }
+
+    /// Throws a `RangeError` with the message `msg`.
+    #[cfg(feature = "intl")]
+    pub(crate) fn throw_range_error(&self, msg: &str) -> Value {
+        let msg = c_message(msg);
+
+        unsafe {
+            Value {
+                value: sys::JS_ThrowRangeError(
+                    self.ptr.as_ptr(),
+                    b"%s\0".as_ptr() as *const i8,
+                    msg.as_ptr(),
+                ),
+                context: self.ptr.clone(),
+            }
+        }
+    }
+
+    /// Builds a JavaScript `Error` object from a Rust error.
+    ///
+    /// `message` is set to the `Display` output of `err`. If `err` has a
+    /// `source()`, it is converted the same way and stored as `cause`, so
+    /// scripts catching the error can walk the whole chain.
+    pub fn error_from(&self, err: &dyn error::Error) -> Result<Value, Value> {
+        let val = unsafe {
+            Value {
+                value: sys::JS_NewError(self.ptr.as_ptr()),
+                context: self.ptr.clone(),
+            }
+        };
+
+        if val.is_exception() {
+            return Err(self.take_exception());
+        }
+
+        let flags = sys::JS_PROP_CONFIGURABLE | sys::JS_PROP_WRITABLE;
+        let mut obj = Object { value: val };
+
+        if !obj.define("message", self.string(&err.to_string()), flags) {
+            return Err(self.take_exception());
+        }
+
+        if let Some(source) = err.source() {
+            let cause = self.error_from(source)?;
+
+            if !obj.define("cause", cause, flags) {
+                return Err(self.take_exception());
+            }
+        }
+
+        Ok(obj.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error;
+    use std::fmt;
+
+    use super::*;
+    use crate::Runtime;
+
+    #[derive(Debug)]
+    struct DiskFull;
+
+    impl fmt::Display for DiskFull {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "disk full")
+        }
+    }
+
+    impl error::Error for DiskFull {}
+
+    #[derive(Debug)]
+    struct WriteFailed(DiskFull);
+
+    impl fmt::Display for WriteFailed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "write failed")
+        }
+    }
+
+    impl error::Error for WriteFailed {
+        fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn error_from_chain() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let err = ctx.error_from(&WriteFailed(DiskFull)).unwrap();
+        let err = Object { value: err };
+
+        assert_eq!(
+            &err.get("message").unwrap().as_string().unwrap(),
+            "write failed"
+        );
+
+        let cause = Object { value: err.get("cause").unwrap() };
+
+        assert_eq!(
+            &cause.get("message").unwrap().as_string().unwrap(),
+            "disk full"
+        );
+        assert!(cause.get("cause").unwrap().is_undefined());
+    }
+
+    #[test]
+    fn details() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+
+        let ex = ctx
+            .eval(r#"throw new TypeError("nope");"#, "<test>", false, false)
+            .unwrap_err();
+        let err = Error::from(ex);
+
+        match err {
+            Error::Exception(ref details) => {
+                assert_eq!(details.name.as_ref().unwrap(), "TypeError");
+                assert_eq!(&details.message, "nope");
+                assert!(details.stack.is_some());
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert_eq!(&err.to_string(), "TypeError: nope");
+    }
+
+    #[test]
+    fn render() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let ex = ctx
+            .eval_script(
+                "function check(v) {\n    v();\n}\ncheck(1);",
+                "config.js",
+            )
+            .unwrap_err();
+        let report = ExceptionDetails::from_value(&ex).render();
+        let lines = report.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "TypeError: not a function");
+        assert!(lines[1].starts_with("    at check (config.js:2"));
+        assert!(lines[2].starts_with("    at <eval> (config.js:4"));
+    }
+
+    #[test]
+    fn throw_location() {
+        let mut rt = Runtime::default();
+        rt.record_throw_location(true);
+
+        let ctx = rt.context().unwrap();
+        let err = ctx.error_from(&DiskFull).unwrap();
+
+        assert!(ctx.throw(err).is_exception());
+
+        let ex = Object { value: ctx.take_exception() };
+        let loc = ex.get("rustLocation").unwrap().as_string().unwrap();
+
+        assert!(loc.starts_with(file!()));
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn anyhow_roundtrip() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let err = anyhow::Error::new(DiskFull).context("write failed");
+        let ex = ctx.throw_anyhow(&err);
+
+        assert!(ex.is_exception());
+
+        let err: anyhow::Error = Error::from(ctx.take_exception()).into();
+
+        assert_eq!(&err.to_string(), "Error: write failed");
+    }
+}
@@ -0,0 +1,182 @@
+use quickjs_sys as sys;
+
+use crate::error::Error;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Settings for `Context::eval_with_options`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalOptions {
+    pub strict: bool,
+    pub strip: bool,
+    /// Lines before the source in the file it was taken from. Positions in
+    /// errors and stack traces are shifted by it.
+    pub line_offset: u32,
+    /// Columns before the source's first line in the file it was taken
+    /// from. Only positions on the first line are shifted.
+    pub column_offset: u32,
+}
+
+impl Context {
+    /// Like `eval`, for sources embedded in larger documents like the
+    /// `<script>` blocks of a page. With the offsets set, errors point to
+    /// the position in the original document.
+    pub fn eval_with_options(
+        &mut self,
+        input: &str,
+        filename: &str,
+        opts: &EvalOptions,
+    ) -> Result<Value, Value> {
+        let lines = opts.line_offset as usize;
+        let columns = opts.column_offset as usize;
+        let mut buf = Vec::with_capacity(lines + columns + input.len());
+
+        // QuickJS can't start counting elsewhere, so pad the source instead
+        buf.resize(lines, b'\n');
+        buf.resize(lines + columns, b' ');
+        buf.extend_from_slice(input.as_bytes());
+
+        self.eval_bytes(&buf, filename, opts.strict, opts.strip)
+    }
+}
+
+/// Whether `name` can be used as a parameter name.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+impl Context {
+    /// Evaluates `input` as the body of a function taking `bindings` as
+    /// parameters, so they don't end up on the global object. An expression
+    /// is returned as the result, statements have to `return` one.
+    pub fn eval_with(
+        &mut self,
+        input: &str,
+        filename: &str,
+        bindings: &[(&str, Value)],
+        opts: &EvalOptions,
+    ) -> Result<Value, Value> {
+        if let Some((name, _)) =
+            bindings.iter().find(|(name, _)| !is_identifier(name))
+        {
+            let err = Error::Conversion(format!(
+                "'{}' can't be used as a binding name",
+                name
+            ));
+            return Err(self.error_from(&err).unwrap_or_else(|ex| ex));
+        }
+
+        let names = bindings.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        let mut header = format!("(function ({}) {{ ", names.join(", "));
+        let mut lines = opts.line_offset as usize;
+        let mut flags = sys::JS_EVAL_TYPE_GLOBAL as i32;
+
+        if opts.strict {
+            header.push_str("'use strict'; ");
+            flags |= sys::JS_EVAL_FLAG_STRICT as i32;
+        }
+        if opts.strip {
+            flags |= sys::JS_EVAL_FLAG_STRIP as i32;
+        }
+
+        // the header takes up a line of the offset if there is one
+        if lines > 0 {
+            lines -= 1;
+            header.push('\n');
+        }
+
+        let source = |prefix: &str, suffix: &str| {
+            let mut buf = "\n".repeat(lines);
+
+            buf.push_str(&header);
+            buf.push_str(prefix);
+            buf.push_str(&" ".repeat(opts.column_offset as usize));
+            buf.push_str(input);
+            buf.push_str(suffix);
+            buf
+        };
+        let expr = source("return (", "\n); })");
+        let func = match self.eval_flags(expr.as_bytes(), filename, flags) {
+            Ok(func) => func,
+            Err(_) => {
+                let body = source("", "\n})");
+                self.eval_flags(body.as_bytes(), filename, flags)?
+            }
+        };
+        let args =
+            bindings.iter().map(|(_, val)| val.clone()).collect::<Vec<_>>();
+        let ret = func.call(self.undefined(), &args);
+
+        if ret.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(ret)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EvalOptions;
+    use crate::{ExceptionDetails, Runtime};
+
+    #[test]
+    fn bindings() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let input = ctx.eval_script("({ x: 2 })", "<test>").unwrap();
+        let env = ctx.string("prod");
+        let bindings = [("input", input), ("env", env)];
+        let opts = EvalOptions::default();
+
+        let ret =
+            ctx.eval_with("input.x * 2", "<test>", &bindings, &opts).unwrap();
+        assert_eq!(ret.as_integer(), Some(4));
+
+        let ret = ctx
+            .eval_with(
+                "var y = input.x; return env + y;",
+                "<test>",
+                &bindings,
+                &opts,
+            )
+            .unwrap();
+        assert_eq!(ret.as_string().unwrap(), "prod2");
+
+        let global = ctx.global();
+        assert!(global.get("input").unwrap().is_undefined());
+        assert!(global.get("y").unwrap().is_undefined());
+
+        let opts = EvalOptions { line_offset: 4, ..EvalOptions::default() };
+        let err = ctx
+            .eval_with("\nenv.nope()", "page.html", &bindings, &opts)
+            .unwrap_err();
+        let stack = ExceptionDetails::from_value(&err).stack.unwrap();
+        assert!(stack.contains("page.html:6"), "{}", stack);
+
+        assert!(ctx
+            .eval_with("1", "<test>", &[("a-b", ctx.null())], &opts)
+            .is_err());
+    }
+
+    #[test]
+    fn line_offset() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let opts = EvalOptions { line_offset: 10, ..EvalOptions::default() };
+
+        let err = ctx
+            .eval_with_options("\nthrow new Error('x');", "page.html", &opts)
+            .unwrap_err();
+        let details = ExceptionDetails::from_value(&err);
+
+        assert!(details.stack.unwrap().contains("page.html:12"));
+    }
+}
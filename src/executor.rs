@@ -0,0 +1,220 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{self, Poll};
+
+use crate::promise::Promise;
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+
+/// Runs the futures of async host functions, see `Context::async_closure`.
+pub trait Executor {
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()>>>);
+}
+
+/// Future of a running async host function and the promise it settles.
+/// Emptied when the context is dropped, so neither outlives it.
+pub(crate) type PendingPromise = RefCell<Option<Pending>>;
+
+pub(crate) struct Pending {
+    promise: Promise,
+    future: Pin<Box<dyn Future<Output = Result<Value, Value>>>>,
+}
+
+/// What is spawned on the executor. Completes without doing anything once
+/// the context is gone.
+struct Task {
+    slot: Rc<PendingPromise>,
+}
+
+impl Future for Task {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<()> {
+        let ret = {
+            let mut slot = self.slot.borrow_mut();
+            let ret = match slot.as_mut() {
+                Some(pending) => pending.future.as_mut().poll(cx),
+                None => return Poll::Ready(()),
+            };
+
+            match ret {
+                Poll::Ready(ret) => slot.take().map(|p| (p.promise, ret)),
+                Poll::Pending => return Poll::Pending,
+            }
+        };
+
+        match ret {
+            Some((promise, Ok(val))) => promise.resolve(val),
+            Some((promise, Err(err))) => promise.reject(err),
+            None => {}
+        }
+
+        Poll::Ready(())
+    }
+}
+
+impl Context {
+    /// Like `closure`, but `f` returns a future. Calling the function
+    /// spawns the future on the runtime's executor and returns a promise
+    /// settled with its result. Without an executor the call throws.
+    pub fn async_closure<F, Fut>(
+        &self,
+        name: &str,
+        f: F,
+    ) -> Result<Value, Value>
+    where
+        F: Fn(&Context, Value, &[Value]) -> Fut + 'static,
+        Fut: Future<Output = Result<Value, Value>> + 'static,
+    {
+        self.closure(name, move |ctx, this, args| {
+            match ctx.spawn_promise(f(ctx, this, args)) {
+                Ok(val) => val,
+                Err(err) => ctx.throw(err),
+            }
+        })
+    }
+
+    /// Spawns `fut` on the runtime's executor and returns a promise settled
+    /// with its result.
+    pub(crate) fn spawn_promise<Fut>(&self, fut: Fut) -> Result<Value, Value>
+    where
+        Fut: Future<Output = Result<Value, Value>> + 'static,
+    {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+        let executor = match state.executor.borrow().clone() {
+            Some(executor) => executor,
+            None => {
+                let err =
+                    io::Error::new(io::ErrorKind::Other, "no executor set");
+                return Err(self.error_from(&err)?);
+            }
+        };
+        let promise = self.promise()?;
+        let value = promise.value().clone();
+        let slot: Rc<PendingPromise> = Rc::new(RefCell::new(Some(Pending {
+            promise,
+            future: Box::pin(fut),
+        })));
+
+        {
+            let mut pending = self.ptr.state().pending_promises.borrow_mut();
+
+            pending.retain(|p| p.strong_count() > 0);
+            pending.push(Rc::downgrade(&slot));
+        }
+
+        executor.spawn(Box::pin(Task { slot }));
+
+        Ok(value)
+    }
+}
+
+/// Drops the futures and promises of async host functions still running,
+/// while the context is still there for the values they hold.
+pub(crate) fn cancel_pending(pending: &RefCell<Vec<Weak<PendingPromise>>>) {
+    let slots = pending.borrow_mut().drain(..).collect::<Vec<_>>();
+
+    for slot in slots.iter().filter_map(Weak::upgrade) {
+        // busy if the context is dropped by the future being polled
+        let pending = slot.try_borrow_mut().ok().and_then(|mut p| p.take());
+
+        drop(pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task;
+
+    use super::Executor;
+    use crate::promise::noop_waker;
+    use crate::Runtime;
+
+    type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+    #[derive(Clone, Default)]
+    struct Queue(Rc<RefCell<Vec<Task>>>);
+
+    impl Executor for Queue {
+        fn spawn(&self, future: Task) {
+            self.0.borrow_mut().push(future);
+        }
+    }
+
+    #[test]
+    fn async_closure() {
+        let queue = Queue::default();
+        let mut rt = Runtime::default();
+
+        rt.set_executor(queue.clone());
+
+        let mut ctx = rt.context().unwrap();
+        let f = ctx
+            .async_closure("double", |ctx, _, args| {
+                let ret = ctx.integer(args[0].as_integer().unwrap() * 2);
+
+                async move { Ok(ret) }
+            })
+            .unwrap();
+
+        ctx.global().set("double", f);
+        ctx.eval_script(
+            "double(21).then((v) => { globalThis.result = v; })",
+            "<test>",
+        )
+        .unwrap();
+
+        let waker = noop_waker();
+        let mut cx = task::Context::from_waker(&waker);
+        let tasks = queue.0.borrow_mut().drain(..).collect::<Vec<_>>();
+
+        assert_eq!(tasks.len(), 1);
+        for mut task in tasks {
+            assert!(task.as_mut().poll(&mut cx).is_ready());
+        }
+        ctx.run_until_idle().unwrap();
+
+        assert_eq!(
+            ctx.eval_script("result", "<test>").unwrap().as_integer(),
+            Some(42)
+        );
+    }
+
+    #[test]
+    fn dropped_with_context() {
+        let queue = Queue::default();
+        let mut rt = Runtime::default();
+
+        rt.set_executor(queue.clone());
+
+        let ctx = rt.context().unwrap();
+        let f = ctx
+            .async_closure("hold", |_, _, args| {
+                let held = args[0].clone();
+
+                async move {
+                    std::future::pending::<()>().await;
+                    Ok(held)
+                }
+            })
+            .unwrap();
+
+        f.call(ctx.undefined(), &[ctx.string("held")]);
+        drop(f);
+        drop(ctx);
+
+        let waker = noop_waker();
+        let mut cx = task::Context::from_waker(&waker);
+
+        for mut task in queue.0.borrow_mut().drain(..) {
+            assert!(task.as_mut().poll(&mut cx).is_ready());
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+
+use quickjs_sys as sys;
+
+use crate::runtime::RuntimeState;
+use crate::stats;
+use crate::trace;
+
+/// Reported to the hook set with `Runtime::set_gc_hook`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcEvent {
+    /// A collection started.
+    Start {
+        /// Bytes allocated by the engine when it started.
+        heap_size: usize,
+    },
+    /// A collection finished.
+    End {
+        /// How long the collection took.
+        pause: Duration,
+        /// Bytes freed by the collection.
+        reclaimed: usize,
+        /// Bytes still allocated afterwards.
+        heap_size: usize,
+    },
+}
+
+static MARKER_CLASS_ID: AtomicU32 = AtomicU32::new(0);
+static MARKER_CLASS_ID_INIT: Once = Once::new();
+
+fn marker_class_id() -> sys::JSClassID {
+    MARKER_CLASS_ID_INIT.call_once(|| {
+        let mut id = 0;
+
+        unsafe {
+            sys::JS_NewClassID(&mut id);
+        }
+        MARKER_CLASS_ID.store(id, Ordering::SeqCst);
+    });
+
+    MARKER_CLASS_ID.load(Ordering::SeqCst)
+}
+
+/// QuickJS has no callback for collections, but marks all objects when
+/// one starts. The marker is an object of a class whose mark function
+/// notes that.
+extern "C" fn mark(
+    rt: *mut sys::JSRuntime,
+    _val: sys::JSValue,
+    _mark_func: sys::JS_MarkFunc,
+) {
+    let state =
+        unsafe { &*(sys::JS_GetRuntimeOpaque(rt) as *const RuntimeState) };
+
+    state.gc_started();
+}
+
+/// Creates the marker object of `rt`, see `mark`. It must be freed before
+/// the runtime.
+pub(crate) unsafe fn new_marker(
+    rt: *mut sys::JSRuntime,
+) -> Option<sys::JSValue> {
+    let def = sys::JSClassDef {
+        class_name: b"GcMarker\0".as_ptr() as *const i8,
+        finalizer: None,
+        gc_mark: Some(mark),
+        call: None,
+        exotic: ptr::null_mut(),
+    };
+
+    if sys::JS_NewClass(rt, marker_class_id(), &def) < 0 {
+        return None;
+    }
+
+    // objects don't keep the context they were created in
+    let ctx = sys::JS_NewContextRaw(rt);
+
+    if ctx.is_null() {
+        return None;
+    }
+
+    let marker = sys::JS_NewObjectProtoClass(
+        ctx,
+        sys::Helper_JS_NewNull(),
+        marker_class_id(),
+    );
+
+    sys::JS_FreeContext(ctx);
+
+    if sys::Helper_JS_IsException(marker) != 0 {
+        None
+    } else {
+        Some(marker)
+    }
+}
+
+impl RuntimeState {
+    fn gc_started(&self) {
+        // objects are marked more than once per collection
+        if self.gc_start.get().is_none() {
+            self.gc_start.set(Some((Instant::now(), self.allocated.get())));
+        }
+    }
+
+    /// Records the end of the collection in progress, if there is one.
+    /// QuickJS doesn't allocate while collecting, so the allocator calls
+    /// this, as does everything running collections itself.
+    pub(crate) fn gc_finished(&self) {
+        let (start, before) = match self.gc_start.take() {
+            Some(start) => start,
+            None => return,
+        };
+        let pause = start.elapsed();
+        let after = self.allocated.get();
+
+        self.gc_time.set(self.gc_time.get() + pause);
+
+        // the hook can't run in the middle of an allocation
+        if let Ok(mut collections) = self.gc_events.try_borrow_mut() {
+            collections.push(GcEvent::Start { heap_size: before });
+            collections.push(GcEvent::End {
+                pause,
+                reclaimed: before.saturating_sub(after),
+                heap_size: after,
+            });
+        }
+    }
+
+    /// Runs a full collection.
+    pub(crate) fn collect_garbage(&self, rt: *mut sys::JSRuntime) {
+        unsafe {
+            sys::JS_RunGC(rt);
+        }
+        self.report_collections();
+    }
+
+    /// Reports the collections since the last call to the GC hook. Called
+    /// whenever it's safe to run host code.
+    pub(crate) fn report_collections(&self) {
+        self.gc_finished();
+
+        let collections = match self.gc_events.try_borrow_mut() {
+            Ok(mut collections) => mem::take(&mut *collections),
+            Err(_) => return,
+        };
+        let mut guard = self.gc_hook.try_borrow_mut().ok();
+
+        for event in collections {
+            if let GcEvent::End { pause, .. } = event {
+                stats::gc_pause(pause);
+                trace::gc(pause);
+            }
+            if let Some(hook) = guard.as_mut().and_then(|h| h.as_mut()) {
+                hook(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::GcEvent;
+    use crate::Runtime;
+
+    #[test]
+    fn gc_hook() {
+        let mut rt = Runtime::default();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let e = events.clone();
+
+        rt.set_gc_hook(move |ev| e.borrow_mut().push(ev));
+
+        let mut ctx = rt.context().unwrap();
+
+        ctx.eval(
+            r#"
+            for (let i = 0; i < 200000; i++) {
+                let a = {};
+                let b = { a };
+                a.b = b;
+            }
+            "#,
+            "<test>",
+            false,
+            false,
+        )
+        .unwrap();
+
+        let events = events.borrow();
+        let reclaimed = events
+            .iter()
+            .map(|ev| match ev {
+                GcEvent::End { reclaimed, .. } => *reclaimed,
+                GcEvent::Start { .. } => 0,
+            })
+            .sum::<usize>();
+
+        assert!(events.len() >= 2);
+        assert!(matches!(events[0], GcEvent::Start { .. }));
+        assert!(matches!(events[1], GcEvent::End { .. }));
+        assert!(reclaimed > 1_000_000);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::object::Object;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Outcome of resuming a `Generator`.
+#[derive(Debug, PartialEq)]
+pub enum GeneratorStep {
+    /// The generator yielded this value and can be resumed.
+    Yield(Value),
+    /// The generator returned this value and is finished.
+    Return(Value),
+}
+
+/// A script generator object driven from Rust.
+pub struct Generator {
+    pub(crate) value: Value,
+}
+
+impl Generator {
+    /// Wraps `val` if it has the `next`, `throw` and `return` methods of a
+    /// generator.
+    pub fn from_value(val: Value) -> Option<Generator> {
+        let obj = Object { value: val };
+        let has = |name| obj.get(name).map_or(false, |f| f.is_function());
+
+        if has("next") && has("throw") && has("return") {
+            Some(Generator { value: obj.value })
+        } else {
+            None
+        }
+    }
+
+    /// Resumes the generator with `val` as the result of the `yield` it's
+    /// suspended at.
+    pub fn next(&self, val: Value) -> Result<GeneratorStep, Value> {
+        self.resume("next", val)
+    }
+
+    /// Resumes the generator by throwing `err` at the `yield` it's
+    /// suspended at. Fails with `err` if the script doesn't catch it.
+    pub fn throw(&self, err: Value) -> Result<GeneratorStep, Value> {
+        self.resume("throw", err)
+    }
+
+    /// Finishes the generator as if it returned `val` at the `yield` it's
+    /// suspended at. `finally` blocks still run and may yield.
+    pub fn return_(&self, val: Value) -> Result<GeneratorStep, Value> {
+        self.resume("return", val)
+    }
+
+    fn resume(&self, method: &str, val: Value) -> Result<GeneratorStep, Value> {
+        let ctx = Context { ptr: self.value.context.clone() };
+        let obj = Object { value: self.value.clone() };
+        let func = obj.get(method).map_err(|_| ctx.take_exception())?;
+        let ret = func.call(self.value.clone(), &[val]);
+
+        if ret.is_exception() {
+            return Err(ctx.take_exception());
+        }
+
+        let ret = Object { value: ret };
+        let done = ret.get("done").map_err(|_| ctx.take_exception())?;
+        let value = ret.get("value").map_err(|_| ctx.take_exception())?;
+
+        if done.as_boolean().unwrap_or(false) {
+            Ok(GeneratorStep::Return(value))
+        } else {
+            Ok(GeneratorStep::Yield(value))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Generator, GeneratorStep};
+    use crate::Runtime;
+
+    #[test]
+    fn generator() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let gen = ctx
+            .eval_script(
+                r#"
+                (function* () {
+                    const x = yield 1;
+                    try {
+                        yield x * 2;
+                    } catch (e) {
+                        yield "caught " + e;
+                    }
+                    return "end";
+                })()
+                "#,
+                "<test>",
+            )
+            .unwrap();
+        let gen = Generator::from_value(gen).unwrap();
+        let step = |s| match s {
+            GeneratorStep::Yield(v) => (false, v),
+            GeneratorStep::Return(v) => (true, v),
+        };
+
+        let (done, v) = step(gen.next(ctx.undefined()).unwrap());
+        assert!(!done);
+        assert_eq!(v.as_integer(), Some(1));
+
+        let (done, v) = step(gen.next(ctx.integer(21)).unwrap());
+        assert!(!done);
+        assert_eq!(v.as_integer(), Some(42));
+
+        let (done, v) = step(gen.throw(ctx.string("up")).unwrap());
+        assert!(!done);
+        assert_eq!(v.as_string().unwrap(), "caught up");
+
+        let (done, v) = step(gen.next(ctx.undefined()).unwrap());
+        assert!(done);
+        assert_eq!(v.as_string().unwrap(), "end");
+
+        assert!(Generator::from_value(ctx.integer(1)).is_none());
+    }
+
+    #[test]
+    fn early_return() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let gen = ctx
+            .eval_script("(function* () { yield 1; yield 2; })()", "<test>")
+            .unwrap();
+        let gen = Generator::from_value(gen).unwrap();
+
+        gen.next(ctx.undefined()).unwrap();
+
+        match gen.return_(ctx.integer(7)).unwrap() {
+            GeneratorStep::Return(v) => assert_eq!(v.as_integer(), Some(7)),
+            step => panic!("{:?}", step),
+        }
+        assert!(gen.throw(ctx.string("late")).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::error::Error;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Replaces the `@{name}` placeholders of `template` with the parameter
+/// of the binding `name` in `names`.
+fn substitute(template: &str, names: &[&str]) -> Result<String, Error> {
+    let mut src = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("@{") {
+        let end = rest[start..].find('}').map(|end| start + end).ok_or_else(
+            || Error::Conversion("unterminated @{ in js! template".to_string()),
+        )?;
+        let name = rest[start + 2..end].trim();
+        let idx = names.iter().position(|n| *n == name).ok_or_else(|| {
+            Error::Conversion(format!("@{{{}}} is not bound in js!", name))
+        })?;
+
+        src.push_str(&rest[..start]);
+        src.push_str(&format!("__js_arg{}", idx));
+        rest = &rest[end + 1..];
+    }
+
+    src.push_str(rest);
+    Ok(src)
+}
+
+#[doc(hidden)]
+pub fn __js_eval(
+    ctx: &Context,
+    template: &str,
+    args: Vec<(&str, Result<Value, Value>)>,
+) -> Result<Value, Value> {
+    let names = args.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    let body = match substitute(template, &names) {
+        Ok(body) => body,
+        Err(err) => return Err(ctx.error_from(&err).unwrap_or_else(|ex| ex)),
+    };
+    let params = (0..names.len())
+        .map(|idx| format!("__js_arg{}", idx))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let src = format!("(function ({}) {{ return (\n{}\n); }})", params, body);
+    let func = ctx.eval_script(&src, "<js!>")?;
+    let args =
+        args.into_iter().map(|(_, val)| val).collect::<Result<Vec<_>, _>>()?;
+    let ret = func.call(ctx.undefined(), &args);
+
+    if ret.is_exception() {
+        Err(ctx.take_exception())
+    } else {
+        Ok(ret)
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __js_arg {
+    ($name:ident) => {
+        $name.clone()
+    };
+    ($name:ident = $val:expr) => {
+        $val
+    };
+}
+
+/// Evaluates a JavaScript expression with Rust values interpolated. Each
+/// `@{name}` refers to a binding after the template, `name = expr` or just
+/// `name`. Values implementing `IntoJs` are passed as arguments, not
+/// spliced into the source. Evaluates to a `Result<Value, Value>`.
+#[macro_export]
+macro_rules! js {
+    ($ctx:expr, $template:expr $(, $name:ident $(= $val:expr)?)* $(,)?) => {
+        match &$ctx {
+            ctx => $crate::__js_eval(
+                ctx,
+                $template,
+                vec![$((
+                    stringify!($name),
+                    $crate::IntoJs::into_js(
+                        $crate::__js_arg!($name $(= $val)?),
+                        ctx,
+                    ),
+                )),*],
+            ),
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    #[test]
+    fn interpolation() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let name = "x'); throw new Error('injected'); ('";
+
+        ctx.eval_script("function f(a, b) { return a + b.length; }", "<test>")
+            .unwrap();
+
+        let ret = crate::js!(ctx, "f(@{a}, @{name})", a = 1, name).unwrap();
+
+        assert_eq!(ret.as_integer(), Some(1 + name.len() as i64));
+        assert!(crate::js!(ctx, "@{missing}").is_err());
+        assert!(crate::js!(ctx, "@{a", a = 1).is_err());
+    }
+}
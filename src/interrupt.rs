@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Aborts scripts of a runtime from any thread, see
+/// `Runtime::interrupt_handle`.
+#[derive(Clone, Debug)]
+pub struct InterruptHandle {
+    pub(crate) flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    /// Aborts the script currently running in the runtime with an
+    /// uncatchable exception, as soon as the engine polls for interrupts.
+    /// Does nothing if no script is running.
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::Runtime;
+
+    #[test]
+    fn interrupt_from_thread() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let handle = rt.interrupt_handle();
+
+        let killer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            handle.interrupt();
+        });
+
+        assert!(ctx.eval("for (;;) {}", "<test>", false, false).is_err());
+        killer.join().unwrap();
+
+        // the interrupt is used up
+        assert!(ctx.eval("1 + 1", "<test>", false, false).is_ok());
+
+        // and one raised while idle is dropped
+        rt.interrupt_handle().interrupt();
+        assert!(ctx
+            .eval("for (let i = 0; i < 1e6; i++) {}", "<test>", false, false)
+            .is_ok());
+    }
+}
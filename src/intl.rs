@@ -0,0 +1,307 @@
+//! A minimal `Intl` for QuickJS, which has none, behind the `intl` feature.
+//!
+//! Only `Intl.NumberFormat` with fraction digits and grouping and
+//! `Intl.DateTimeFormat` with `dateStyle`/`timeStyle` are provided,
+//! formatted with the locale data compiled into the `icu` crates. Dates are
+//! shown in local time or UTC, other time zones are a `RangeError`.
+
+use fixed_decimal::FixedDecimal;
+use icu::calendar::{DateTime, Gregorian};
+use icu::datetime::options::length;
+use icu::datetime::TypedDateTimeFormatter;
+use icu::decimal::options::{FixedDecimalFormatterOptions, GroupingStrategy};
+use icu::decimal::FixedDecimalFormatter;
+use icu::locid::{locale, Locale};
+
+use crate::convert::arg;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Installs `Intl` unless the global already exists.
+const PRELUDE: &str = r#"(function (formatNumber, formatDate) {
+    if (typeof Intl !== "undefined") {
+        return;
+    }
+
+    const first = (locales) => Array.isArray(locales) ? locales[0] : locales;
+
+    class NumberFormat {
+        constructor(locales, options = {}) {
+            const min = options.minimumFractionDigits ?? 0;
+            const max = options.maximumFractionDigits ?? Math.max(min, 3);
+
+            if (min < 0 || max > 100 || min > max) {
+                throw new RangeError("fraction digits out of range");
+            }
+            this._options = {
+                locale: first(locales) ?? "en-US",
+                minimumFractionDigits: min,
+                maximumFractionDigits: max,
+                useGrouping: options.useGrouping ?? true,
+            };
+        }
+
+        format(n) {
+            const o = this._options;
+
+            return formatNumber(o.locale, Number(n), o.minimumFractionDigits,
+                o.maximumFractionDigits, !!o.useGrouping);
+        }
+
+        resolvedOptions() {
+            return { ...this._options };
+        }
+    }
+
+    class DateTimeFormat {
+        constructor(locales, options = {}) {
+            let { dateStyle, timeStyle, timeZone } = options;
+
+            if (dateStyle === undefined && timeStyle === undefined) {
+                dateStyle = "short";
+            }
+            if (timeZone !== undefined && timeZone !== "UTC") {
+                throw new RangeError(`unsupported time zone ${timeZone}`);
+            }
+            this._options = {
+                locale: first(locales) ?? "en-US",
+                dateStyle,
+                timeStyle,
+                timeZone,
+            };
+        }
+
+        format(date = Date.now()) {
+            const o = this._options;
+            const d = new Date(date);
+
+            if (isNaN(d.getTime())) {
+                throw new RangeError("invalid time value");
+            }
+
+            const fields = o.timeZone === "UTC"
+                ? [d.getUTCFullYear(), d.getUTCMonth() + 1, d.getUTCDate(),
+                   d.getUTCHours(), d.getUTCMinutes(), d.getUTCSeconds()]
+                : [d.getFullYear(), d.getMonth() + 1, d.getDate(),
+                   d.getHours(), d.getMinutes(), d.getSeconds()];
+
+            return formatDate(o.locale, o.dateStyle, o.timeStyle, ...fields);
+        }
+
+        resolvedOptions() {
+            return { ...this._options };
+        }
+    }
+
+    globalThis.Intl = { NumberFormat, DateTimeFormat };
+})"#;
+
+impl Context {
+    /// Installs `Intl.NumberFormat` and `Intl.DateTimeFormat`.
+    pub(crate) fn install_intl(&self) -> Result<(), Value> {
+        let format_number =
+            self.internal_closure("formatNumber", |ctx, _, args| {
+                let ret = format_number(
+                    &arg::<String>(args, 0).unwrap_or_default(),
+                    arg(args, 1).unwrap_or(f64::NAN),
+                    arg(args, 2).unwrap_or(0),
+                    arg(args, 3).unwrap_or(3),
+                    arg(args, 4).unwrap_or(true),
+                );
+
+                match ret {
+                    Ok(s) => ctx.string(&s),
+                    Err(msg) => ctx.throw_range_error(&msg),
+                }
+            })?;
+        let format_date =
+            self.internal_closure("formatDate", |ctx, _, args| {
+                let mut fields = [0i32; 6];
+
+                for (idx, field) in fields.iter_mut().enumerate() {
+                    *field = arg(args, idx + 3).unwrap_or(0);
+                }
+
+                let ret = format_date(
+                    &arg::<String>(args, 0).unwrap_or_default(),
+                    arg::<Option<String>>(args, 1).flatten().as_deref(),
+                    arg::<Option<String>>(args, 2).flatten().as_deref(),
+                    fields,
+                );
+
+                match ret {
+                    Ok(s) => ctx.string(&s),
+                    Err(msg) => ctx.throw_range_error(&msg),
+                }
+            })?;
+        let install = self.eval_script(PRELUDE, "<intl>")?;
+        let ret = install.call(self.undefined(), &[format_number, format_date]);
+
+        if ret.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Parses a BCP 47 tag, falling back to `en-US` like engines do for
+/// locales they don't know.
+fn parse_locale(tag: &str) -> Locale {
+    tag.parse().unwrap_or(locale!("en-US"))
+}
+
+fn format_number(
+    locale: &str,
+    n: f64,
+    min_fraction: usize,
+    max_fraction: usize,
+    grouping: bool,
+) -> Result<String, String> {
+    if n.is_nan() {
+        return Ok("NaN".into());
+    }
+    if n.is_infinite() {
+        return Ok(if n < 0.0 { "-∞" } else { "∞" }.into());
+    }
+
+    // round in Rust, then drop zeros not asked for
+    let mut digits = format!("{:.*}", max_fraction, n);
+
+    if let Some(dot) = digits.find('.') {
+        let keep = dot + 1 + min_fraction;
+
+        while digits.len() > keep && digits.ends_with('0') {
+            digits.pop();
+        }
+        if digits.ends_with('.') {
+            digits.pop();
+        }
+    }
+
+    let decimal = digits.parse::<FixedDecimal>().map_err(|e| e.to_string())?;
+    let mut options = FixedDecimalFormatterOptions::default();
+
+    options.grouping_strategy =
+        if grouping { GroupingStrategy::Auto } else { GroupingStrategy::Never };
+
+    let locale = parse_locale(locale);
+    let formatter = FixedDecimalFormatter::try_new(&(&locale).into(), options)
+        .map_err(|e| e.to_string())?;
+
+    Ok(formatter.format_to_string(&decimal))
+}
+
+fn date_length(name: &str) -> Result<length::Date, String> {
+    match name {
+        "full" => Ok(length::Date::Full),
+        "long" => Ok(length::Date::Long),
+        "medium" => Ok(length::Date::Medium),
+        "short" => Ok(length::Date::Short),
+        _ => Err(format!("invalid style {}", name)),
+    }
+}
+
+/// Without time zone support, `long` and `full` times are `medium`.
+fn time_length(name: &str) -> Result<length::Time, String> {
+    match name {
+        "full" | "long" | "medium" => Ok(length::Time::Medium),
+        "short" => Ok(length::Time::Short),
+        _ => Err(format!("invalid style {}", name)),
+    }
+}
+
+fn format_date(
+    locale: &str,
+    date_style: Option<&str>,
+    time_style: Option<&str>,
+    fields: [i32; 6],
+) -> Result<String, String> {
+    let bag = match (date_style, time_style) {
+        (Some(date), Some(time)) => length::Bag::from_date_time_style(
+            date_length(date)?,
+            time_length(time)?,
+        ),
+        (None, Some(time)) => length::Bag::from_time_style(time_length(time)?),
+        (Some(date), None) => length::Bag::from_date_style(date_length(date)?),
+        (None, None) => length::Bag::from_date_style(length::Date::Short),
+    };
+    let [year, month, day, hour, minute, second] = fields;
+    let datetime = DateTime::try_new_gregorian_datetime(
+        year,
+        month as u8,
+        day as u8,
+        hour as u8,
+        minute as u8,
+        second as u8,
+    )
+    .map_err(|e| e.to_string())?;
+    let locale = parse_locale(locale);
+    let formatter = TypedDateTimeFormatter::<Gregorian>::try_new(
+        &(&locale).into(),
+        bag.into(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(formatter.format_to_string(&datetime))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    #[test]
+    fn number_format() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let format = |src: &str| {
+            ctx.eval_script(src, "<test>").unwrap().as_string().unwrap()
+        };
+
+        assert_eq!(
+            format("new Intl.NumberFormat('en-US').format(1234.5)"),
+            "1,234.5"
+        );
+        assert_eq!(
+            format("new Intl.NumberFormat('de-DE').format(1234.5)"),
+            "1.234,5"
+        );
+        assert_eq!(
+            format(
+                "new Intl.NumberFormat('en-US', { minimumFractionDigits: 2 })
+                    .format(3)"
+            ),
+            "3.00"
+        );
+        assert_eq!(
+            format(
+                "new Intl.NumberFormat('en', { useGrouping: false })
+                    .format(1234567)"
+            ),
+            "1234567"
+        );
+    }
+
+    #[test]
+    fn date_time_format() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let ret = ctx
+            .eval_script(
+                "new Intl.DateTimeFormat('en-US', {
+                    dateStyle: 'medium',
+                    timeZone: 'UTC',
+                }).format(Date.UTC(2024, 0, 15))",
+                "<test>",
+            )
+            .unwrap();
+
+        assert_eq!(ret.as_string().unwrap(), "Jan 15, 2024");
+        assert!(ctx
+            .eval_script(
+                "new Intl.DateTimeFormat('en', { timeZone: 'Europe/Berlin' })",
+                "<test>",
+            )
+            .is_err());
+    }
+}
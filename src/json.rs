@@ -0,0 +1,132 @@
+use quickjs_sys as sys;
+
+use crate::error::Error;
+use crate::runtime::Context;
+use crate::value::Value;
+
+impl Value {
+    /// Serializes this value with `JSON.stringify`, indented by two spaces
+    /// if `pretty` is set. Values without a JSON representation, like
+    /// functions, are a `Error::Conversion`.
+    pub fn to_json_string(&self, pretty: bool) -> Result<String, Error> {
+        let ctx = Context { ptr: self.context.clone() };
+        let space = if pretty { ctx.integer(2) } else { ctx.undefined() };
+        let json = unsafe {
+            Value {
+                value: sys::JS_JSONStringify(
+                    ctx.ptr.as_ptr(),
+                    self.value,
+                    sys::Helper_JS_NewUndefined(),
+                    space.value,
+                ),
+                context: ctx.ptr.clone(),
+            }
+        };
+
+        if json.is_exception() {
+            return Err(Error::from(ctx.take_exception()));
+        }
+
+        json.as_string().ok_or_else(|| {
+            Error::Conversion(format!(
+                "{:?} can't be represented as JSON",
+                self
+            ))
+        })
+    }
+}
+
+impl Context {
+    /// Parses standard JSON. Syntax errors are `SyntaxError` exceptions.
+    pub fn value_from_json(&self, input: &str) -> Result<Value, Value> {
+        self.parse_json(input, 0)
+    }
+
+    /// Parses relaxed JSON as used in hand-written config files: standard
+    /// JSON with comments, trailing commas, unquoted property names, single
+    /// quoted strings and hexadecimal numbers.
+    pub fn parse_json5(&self, input: &str) -> Result<Value, Value> {
+        self.parse_json(input, sys::JS_PARSE_JSON_EXT as i32)
+    }
+
+    fn parse_json(&self, input: &str, flags: i32) -> Result<Value, Value> {
+        let mut buf = input.as_bytes().to_vec();
+
+        // the parser expects a terminated buffer
+        buf.push(0);
+
+        let val = unsafe {
+            Value {
+                value: sys::JS_ParseJSON2(
+                    self.ptr.as_ptr(),
+                    buf.as_ptr() as *const i8,
+                    input.len(),
+                    b"<json>\0".as_ptr() as *const i8,
+                    flags,
+                ),
+                context: self.ptr.clone(),
+            }
+        };
+
+        if val.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(val)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object::Object;
+    use crate::{ExceptionDetails, Runtime};
+
+    #[test]
+    fn json5() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let val = ctx
+            .parse_json5(
+                r#"{
+                    // listen address
+                    host: 'localhost',
+                    port: 0x1f90,
+                    tags: [1, 2,],
+                }"#,
+            )
+            .unwrap();
+        let obj = Object { value: val };
+
+        assert_eq!(obj.get("host").unwrap().as_string().unwrap(), "localhost");
+        assert_eq!(obj.get("port").unwrap().as_integer(), Some(8080));
+        assert_eq!(obj.keys().unwrap(), vec!["host", "port", "tags"]);
+
+        assert!(ctx.parse_json5("{ a: }").is_err());
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let val = ctx.value_from_json(r#"{"a": [1, "x"], "b": null}"#).unwrap();
+
+        assert_eq!(
+            val.to_json_string(false).unwrap(),
+            r#"{"a":[1,"x"],"b":null}"#
+        );
+        assert_eq!(
+            val.to_json_string(true).unwrap(),
+            "{\n  \"a\": [\n    1,\n    \"x\"\n  ],\n  \"b\": null\n}"
+        );
+
+        let ex = ctx.value_from_json("{ a: 1 }").unwrap_err();
+
+        assert_eq!(
+            ExceptionDetails::from_value(&ex).name.unwrap(),
+            "SyntaxError"
+        );
+        assert!(ctx.undefined().to_json_string(false).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 mod runtime;
-pub use crate::runtime::{Context, Runtime};
+pub use crate::runtime::{Context, ContextBuilder, Runtime};
 
 mod value;
 pub use crate::value::Value;
@@ -8,4 +8,156 @@ mod array;
 pub use crate::array::Array;
 
 mod object;
-pub use crate::object::Object;
+pub use crate::object::{Object, PropertyFlags, PropertyKey};
+
+mod error;
+pub use crate::error::{Error, ExceptionDetails};
+
+mod sources;
+pub use crate::sources::SourceExcerpt;
+
+#[cfg(feature = "miette")]
+mod diagnostic;
+
+mod convert;
+#[doc(hidden)]
+pub use crate::convert::{__js_array, __js_object};
+pub use crate::convert::{FromJs, IntoJs};
+
+mod interpolate;
+#[doc(hidden)]
+pub use crate::interpolate::__js_eval;
+
+mod native;
+#[doc(hidden)]
+pub use crate::native::__throw_panic;
+
+mod ops;
+
+mod eval_options;
+pub use crate::eval_options::EvalOptions;
+
+mod deterministic;
+pub use crate::deterministic::DeterministicContext;
+
+mod metering;
+pub use crate::metering::EvalMetrics;
+
+mod allocator;
+pub use crate::allocator::{GlobalAllocator, MemoryAllocator};
+
+mod watchdog;
+
+mod cooperative;
+pub use crate::cooperative::EvalCooperative;
+
+mod plain;
+pub use crate::plain::PlainValue;
+
+mod pool;
+pub use crate::pool::{PoolEval, RuntimePool};
+
+mod context_pool;
+pub use crate::context_pool::{ContextPool, PooledContext};
+
+mod reset;
+
+mod json;
+
+mod buffer;
+
+#[cfg(feature = "msgpack")]
+mod msgpack;
+
+mod string;
+
+mod base64;
+
+mod abort;
+pub use crate::abort::AbortHandle;
+
+mod weakref;
+
+mod microtask;
+pub use crate::microtask::MicrotaskPolicy;
+
+mod services;
+
+mod promise;
+pub use crate::promise::{Promise, PromiseFuture};
+
+#[cfg(feature = "async")]
+mod executor;
+#[cfg(feature = "async")]
+pub use crate::executor::Executor;
+
+#[cfg(feature = "async")]
+mod async_iter;
+
+mod collections;
+pub use crate::collections::{JsMap, JsSet};
+
+mod generator;
+pub use crate::generator::{Generator, GeneratorStep};
+
+mod channel;
+pub use crate::channel::MessageSender;
+
+mod stream;
+
+mod timers;
+pub use crate::timers::{Scheduler, Timer};
+
+#[cfg(feature = "intl")]
+mod intl;
+#[cfg(feature = "url")]
+mod urls;
+
+#[cfg(feature = "operators")]
+mod operators;
+#[cfg(feature = "operators")]
+pub use crate::operators::OperatorSet;
+
+mod sandbox;
+pub use crate::sandbox::{Chroot, FsAccess, FsSandbox};
+
+mod permissions;
+pub use crate::permissions::{Capability, Permissions};
+
+mod audit;
+#[doc(hidden)]
+pub use crate::audit::__audit;
+pub use crate::audit::AuditEvent;
+
+mod timing;
+pub use crate::timing::Timing;
+
+mod gc;
+pub use crate::gc::GcEvent;
+
+mod stats;
+mod trace;
+
+mod compiled;
+pub use crate::compiled::CompiledScript;
+
+mod module;
+pub use crate::module::Module;
+
+mod reload;
+
+mod commonjs;
+
+mod uncaught;
+
+mod interrupt;
+pub use crate::interrupt::InterruptHandle;
+
+#[cfg(feature = "bytes")]
+mod shared_bytes;
+
+#[cfg(feature = "ndarray")]
+mod ndarray_interop;
+
+mod scope;
+pub use crate::scope::{Local, Scope};
@@ -0,0 +1,96 @@
+use quickjs_sys as sys;
+
+use crate::runtime::{memory_usage, Context, RuntimeState};
+use crate::value::Value;
+
+/// Resources used by a single evaluation, see `Context::eval_metered`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvalMetrics {
+    /// Bytes allocated by the engine, not counting what was freed.
+    pub memory_allocated: u64,
+    /// Most bytes allocated by the engine at any point of the evaluation.
+    pub peak_memory: u64,
+    /// Change in the number of live objects.
+    pub objects_created: i64,
+    /// Host functions called by scripts, including `std` and `os`
+    /// operations. Calls between script functions aren't counted.
+    pub functions_called: u64,
+    /// Number of times the engine polled for interrupts. The engine polls
+    /// about every ten thousand calls or loop iterations, so this is a
+    /// measure of the work done.
+    pub work_units: u64,
+}
+
+impl Context {
+    /// Like `eval`, but also measures the resources used. Memory figures are
+    /// for the whole runtime, so they include allocations by other contexts
+    /// running in between, e.g. from callbacks.
+    pub fn eval_metered(
+        &mut self,
+        input: &str,
+        filename: &str,
+        strict: bool,
+        strip: bool,
+    ) -> (Result<Value, Value>, EvalMetrics) {
+        let rt = unsafe { sys::JS_GetRuntime(self.ptr.as_ptr()) };
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+        let before = memory_usage(rt);
+        let allocated = state.allocated_total.get();
+        let host_calls = state.host_calls.get();
+        let interrupts = state.interrupts.get();
+        let outer =
+            state.peak_memory.replace(Some(state.allocated.get() as u64));
+
+        let ret = self.eval(input, filename, strict, strip);
+
+        let after = memory_usage(rt);
+        let peak = state.peak_memory.replace(outer).unwrap_or(0);
+
+        // keep an enclosing measurement up to date
+        if let Some(outer) = outer {
+            state.peak_memory.set(Some(outer.max(peak)));
+        }
+
+        let metrics = EvalMetrics {
+            memory_allocated: state.allocated_total.get() - allocated,
+            peak_memory: peak,
+            objects_created: after.obj_count - before.obj_count,
+            functions_called: state.host_calls.get() - host_calls,
+            work_units: state.interrupts.get() - interrupts,
+        };
+
+        (ret, metrics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    #[test]
+    fn metered() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let f = ctx.closure("f", |ctx, _, _| ctx.undefined()).unwrap();
+
+        ctx.global().set("f", f);
+
+        let (ret, metrics) = ctx.eval_metered(
+            r#"
+            globalThis.keep = [];
+            for (let i = 0; i < 100000; i++) keep.push({ i });
+            for (let i = 0; i < 3; i++) f();
+            keep = null;
+            "#,
+            "<test>",
+            false,
+            false,
+        );
+
+        assert!(ret.is_ok());
+        assert!(metrics.memory_allocated > 1_000_000);
+        assert!(metrics.peak_memory > 1_000_000);
+        assert_eq!(metrics.functions_called, 3);
+        assert!(metrics.work_units > 0);
+    }
+}
@@ -0,0 +1,252 @@
+use quickjs_sys as sys;
+
+use crate::error::Error;
+use crate::runtime::{Context, Enter, RuntimeState};
+use crate::value::Value;
+
+/// When the jobs of a runtime, promise reactions and `queueMicrotask`
+/// callbacks, run. See `ContextBuilder::microtask_policy`.
+///
+/// The engine keeps one job queue per runtime, so running jobs runs those
+/// of all its contexts. Errors of jobs run by `AfterCall` are kept by the
+/// context the job belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MicrotaskPolicy {
+    /// Only when the host asks for it with `Context::run_until_idle`.
+    /// Host state touched by callbacks only changes at known points.
+    Manual,
+    /// Right after each evaluation or call from the host returns, like
+    /// browsers do at the end of a task. Calls made by scripts into the
+    /// host and back don't count. Jobs queued by other contexts of the
+    /// runtime run as well.
+    AfterCall,
+}
+
+impl Default for MicrotaskPolicy {
+    fn default() -> Self {
+        MicrotaskPolicy::Manual
+    }
+}
+
+extern "C" fn run_microtask(
+    ctx: *mut sys::JSContext,
+    _argc: i32,
+    argv: *mut sys::JSValue,
+) -> sys::JSValue {
+    unsafe {
+        sys::JS_Call(
+            ctx,
+            *argv,
+            sys::Helper_JS_NewUndefined(),
+            0,
+            std::ptr::null_mut(),
+        )
+    }
+}
+
+impl Context {
+    /// Installs `queueMicrotask`, unless the engine has it already.
+    pub(crate) fn install_microtasks(&self) -> Result<(), Value> {
+        let mut global = self.global();
+
+        if !global
+            .get("queueMicrotask")
+            .map_err(|_| self.take_exception())?
+            .is_undefined()
+        {
+            return Ok(());
+        }
+
+        let queue =
+            self.internal_closure("queueMicrotask", |ctx, _, args| {
+                let cb = match args.get(0) {
+                    Some(cb) if cb.is_function() => cb,
+                    _ => unsafe {
+                        return Value {
+                            value: sys::JS_ThrowTypeError(
+                                ctx.ptr.as_ptr(),
+                                b"queueMicrotask expects a function\0".as_ptr()
+                                    as *const i8,
+                            ),
+                            context: ctx.ptr.clone(),
+                        };
+                    },
+                };
+                let mut argv = [cb.value];
+                let rc = unsafe {
+                    sys::JS_EnqueueJob(
+                        ctx.ptr.as_ptr(),
+                        Some(run_microtask),
+                        1,
+                        argv.as_mut_ptr(),
+                    )
+                };
+
+                if rc < 0 {
+                    ctx.exception()
+                } else {
+                    ctx.undefined()
+                }
+            })?;
+
+        if !global.set("queueMicrotask", queue) {
+            return Err(self.take_exception());
+        }
+
+        Ok(())
+    }
+
+    /// Ends a call from the host, running pending jobs afterwards if the
+    /// policy is `AfterCall`. An exception thrown by the call stays
+    /// pending, exceptions thrown by jobs are uncaught errors. Returns
+    /// `ret`.
+    pub(crate) fn finish_call(&self, enter: Enter<'_>, ret: Value) -> Value {
+        let outermost = enter.is_outermost();
+
+        drop(enter);
+
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+
+        // jobs calling into the host and back mustn't start draining again
+        if !outermost
+            || self.ptr.state().microtask_policy.get()
+                != MicrotaskPolicy::AfterCall
+            || state.draining_microtasks.replace(true)
+        {
+            return ret;
+        }
+
+        let ex =
+            if ret.is_exception() { Some(self.take_exception()) } else { None };
+
+        loop {
+            match self.run_job() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err((job_ctx, err)) => {
+                    if let Err(err) = self.report_uncaught(err) {
+                        job_ctx
+                            .ptr
+                            .state()
+                            .microtask_errors
+                            .borrow_mut()
+                            .push(err);
+                    }
+                }
+            }
+        }
+        state.draining_microtasks.set(false);
+
+        if let Some(ex) = ex {
+            unsafe {
+                sys::JS_Throw(self.ptr.as_ptr(), ex.into_raw());
+            }
+        }
+
+        ret
+    }
+
+    /// Runs `f` without running jobs afterwards, for calls the crate makes
+    /// on its own.
+    pub(crate) fn without_microtasks<R>(&self, f: impl FnOnce() -> R) -> R {
+        let state = self.ptr.state();
+        let policy = state.microtask_policy.replace(MicrotaskPolicy::Manual);
+        let ret = f();
+
+        state.microtask_policy.set(policy);
+        ret
+    }
+
+    /// Returns the first error thrown by a job run because of
+    /// `MicrotaskPolicy::AfterCall` since the last call, and forgets
+    /// about the rest.
+    pub(crate) fn take_microtask_error(&self) -> Result<(), Error> {
+        let mut errors = self.ptr.state().microtask_errors.borrow_mut();
+
+        match errors.drain(..).next() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn manual() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+
+        ctx.eval_script(
+            "globalThis.order = [];
+            queueMicrotask(() => order.push('task'));
+            Promise.resolve().then(() => order.push('then'));
+            order.push('sync');",
+            "<test>",
+        )
+        .unwrap();
+
+        let order = |ctx: &Context| {
+            ctx.eval_script("order.join()", "<test>")
+                .unwrap()
+                .as_string()
+                .unwrap()
+        };
+
+        assert_eq!(order(&ctx), "sync");
+        ctx.run_until_idle().unwrap();
+        assert_eq!(order(&ctx), "sync,task,then");
+        assert!(ctx.eval_script("queueMicrotask(1)", "<test>").is_err());
+    }
+
+    #[test]
+    fn after_call() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt
+            .context_builder()
+            .microtask_policy(MicrotaskPolicy::AfterCall)
+            .build()
+            .unwrap();
+        let f = ctx
+            .eval_script(
+                "globalThis.n = 0;
+                (function () {
+                    queueMicrotask(() => n++);
+                    throw new Error('sync');
+                })",
+                "<test>",
+            )
+            .unwrap();
+
+        let ret = f.call(ctx.undefined(), &[]);
+
+        assert!(ret.is_exception());
+        assert_eq!(
+            Error::from(ctx.take_exception()).to_string(),
+            "Error: sync"
+        );
+        assert_eq!(
+            ctx.eval_script("n", "<test>").unwrap().as_integer(),
+            Some(1)
+        );
+
+        ctx.eval_script("queueMicrotask(() => { throw 1; })", "<test>")
+            .unwrap();
+        assert!(ctx.run_until_idle().is_err());
+        assert!(ctx.run_until_idle().is_ok());
+
+        // jobs are shared, their errors stay with the context queuing them
+        let mut manual = rt.context().unwrap();
+
+        manual
+            .eval_script("queueMicrotask(() => { throw 2; })", "<test>")
+            .unwrap();
+        (&ctx.integer(1) + &ctx.integer(2)).unwrap();
+        assert!(ctx.eval_script("1", "<test>").is_ok());
+        assert!(ctx.run_until_idle().is_ok());
+        assert!(manual.run_until_idle().is_err());
+    }
+}
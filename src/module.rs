@@ -0,0 +1,178 @@
+use quickjs_sys as sys;
+
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// A module compiled and linked, but not evaluated yet, see
+/// `Context::compile_module`.
+pub struct Module {
+    function: Value,
+    name: String,
+    imports: Vec<String>,
+    bytecode: Vec<u8>,
+}
+
+impl Module {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Names of the modules this one imports, as resolved by the module
+    /// loader. They are loaded already, but not evaluated.
+    pub fn imports(&self) -> &[String] {
+        &self.imports
+    }
+
+    /// Evaluates the module, and the modules it imports that weren't
+    /// evaluated before, in the context it was compiled for. A module only
+    /// runs once, evaluating it again does nothing. Use `instantiate` to
+    /// run it anew.
+    ///
+    /// Returns the value QuickJS gives for the evaluation, `undefined` or a
+    /// promise with top level `await`.
+    pub fn evaluate(&self) -> Result<Value, Value> {
+        let ctx = Context { ptr: self.function.context.clone() };
+
+        ctx.eval_function(self.function.clone())
+    }
+
+    /// Loads the module into `ctx` from its bytecode, without compiling it
+    /// again. The copy has not been evaluated yet, even in the same
+    /// context.
+    pub fn instantiate(&self, ctx: &Context) -> Result<Module, Value> {
+        // written by `compile_module`, for this QuickJS
+        unsafe { ctx.load_module(&self.bytecode) }
+    }
+
+    /// The bytecode, for caching. Load it with `Context::load_module` into
+    /// a runtime of the same QuickJS version.
+    pub fn bytecode(&self) -> &[u8] {
+        &self.bytecode
+    }
+
+    /// Makes later imports of this module, and of the modules importing
+    /// it, load and evaluate them anew through the module loader. Modules
+    /// that imported it already keep the old instance. Fails for modules
+    /// that didn't come through the loader, like those compiled by the
+    /// host. See `Context::reload_module`.
+    pub fn invalidate(&self) -> Result<(), Value> {
+        let ctx = Context { ptr: self.function.context.clone() };
+
+        ctx.invalidate_module(&self.name).map(|_| ())
+    }
+}
+
+impl Context {
+    /// Compiles `input` as a module named `name` and resolves its imports
+    /// through the module loader, without evaluating anything.
+    pub fn compile_module(
+        &self,
+        name: &str,
+        input: &str,
+    ) -> Result<Module, Value> {
+        let flags =
+            (sys::JS_EVAL_TYPE_MODULE | sys::JS_EVAL_FLAG_COMPILE_ONLY) as i32;
+        let function = self.eval_flags(input.as_bytes(), name, flags)?;
+        let bytecode = self.write_bytecode(&function)?;
+
+        self.link_module(function, bytecode)
+    }
+
+    /// Loads a module from bytecode returned by `Module::bytecode` and
+    /// resolves its imports.
+    ///
+    /// # Safety
+    ///
+    /// QuickJS trusts bytecode, malformed input is undefined behavior.
+    /// `bytecode` must come from `Module::bytecode` of the same QuickJS
+    /// version, never from an untrusted source.
+    pub unsafe fn load_module(&self, bytecode: &[u8]) -> Result<Module, Value> {
+        let function =
+            self.read_bytecode(bytecode, sys::JS_TAG_MODULE as i32)?;
+
+        self.link_module(function, bytecode.to_vec())
+    }
+
+    /// Resolves the imports of `function`, which must be a module.
+    fn link_module(
+        &self,
+        function: Value,
+        bytecode: Vec<u8>,
+    ) -> Result<Module, Value> {
+        debug_assert_eq!(function.value.tag, sys::JS_TAG_MODULE as i64);
+
+        let name = unsafe {
+            let def = function.value.u.ptr as *mut sys::JSModuleDef;
+            let atom = sys::JS_GetModuleName(self.ptr.as_ptr(), def);
+
+            Value {
+                value: sys::JS_AtomToString(self.ptr.as_ptr(), atom),
+                context: self.ptr.clone(),
+            }
+        };
+        let name = name.as_string().unwrap_or_default();
+
+        // the normalizer logs what each module being resolved asks for
+        let state = self.ptr.state();
+        let outer = state.import_log.replace(Some(Vec::new()));
+        let rc = {
+            let _enter = self.ptr.enter();
+
+            unsafe { sys::JS_ResolveModule(self.ptr.as_ptr(), function.value) }
+        };
+        let log = state.import_log.replace(outer).unwrap_or_default();
+
+        if rc < 0 {
+            return Err(self.take_exception());
+        }
+
+        let mut imports = Vec::new();
+
+        for (base, import) in log {
+            if base == name && !imports.contains(&import) {
+                imports.push(import);
+            }
+        }
+
+        Ok(Module { function, name, imports, bytecode })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    #[test]
+    fn deferred() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let module = ctx
+            .compile_module(
+                "main.js",
+                "import * as std from 'std';
+                globalThis.runs = (globalThis.runs || 0) + 1;",
+            )
+            .unwrap();
+        let runs = || {
+            ctx.eval_script("globalThis.runs", "<test>").unwrap().as_integer()
+        };
+
+        assert_eq!(module.name(), "main.js");
+        assert_eq!(module.imports(), ["std"]);
+        assert_eq!(runs(), None);
+
+        module.evaluate().unwrap();
+        assert_eq!(runs(), Some(1));
+
+        let copy = module.instantiate(&ctx).unwrap();
+
+        copy.evaluate().unwrap();
+        assert_eq!(runs(), Some(2));
+        assert!(module.invalidate().is_err());
+        assert!(ctx.compile_module("bad.js", "import 'missing.js';").is_err());
+
+        let script = ctx.compile("1", "<test>").unwrap();
+
+        assert!(unsafe { ctx.load_module(script.bytecode()) }.is_err());
+    }
+}
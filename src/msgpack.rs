@@ -0,0 +1,171 @@
+use rmpv::Value as MsgValue;
+
+use crate::error::Error;
+use crate::object::Object;
+use crate::plain::MAX_DEPTH;
+use crate::runtime::Context;
+use crate::value::Value;
+
+fn too_deep() -> Error {
+    Error::Conversion("value is nested too deeply or cyclic".to_string())
+}
+
+fn encode(val: &Value, depth: usize) -> Result<MsgValue, Error> {
+    if depth > MAX_DEPTH {
+        return Err(too_deep());
+    }
+
+    if val.is_undefined() || val.is_null() {
+        Ok(MsgValue::Nil)
+    } else if let Some(b) = val.as_boolean() {
+        Ok(MsgValue::from(b))
+    } else if let Some(i) = val.as_integer() {
+        Ok(MsgValue::from(i))
+    } else if let Some(f) = val.as_float() {
+        Ok(MsgValue::from(f))
+    } else if let Some(s) = val.as_string() {
+        Ok(MsgValue::from(s))
+    } else if let Some(data) = val.array_buffer_data() {
+        Ok(MsgValue::from(data))
+    } else if val.is_array() {
+        let obj = Object { value: val.clone() };
+        let len = obj.get("length")?.as_integer().unwrap_or(0);
+
+        (0..len)
+            .map(|idx| encode(&obj.get(&idx.to_string())?, depth + 1))
+            .collect::<Result<Vec<_>, _>>()
+            .map(MsgValue::Array)
+    } else if val.is_object() && !val.is_function() {
+        let obj = Object { value: val.clone() };
+
+        obj.entries()?
+            .into_iter()
+            .map(|(key, val)| -> Result<_, Error> {
+                Ok((MsgValue::from(key), encode(&val, depth + 1)?))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(MsgValue::Map)
+    } else {
+        Err(Error::Conversion(format!("can't encode {:?} as MessagePack", val)))
+    }
+}
+
+fn decode(ctx: &Context, val: &MsgValue, depth: usize) -> Result<Value, Error> {
+    if depth > MAX_DEPTH {
+        return Err(too_deep());
+    }
+
+    match val {
+        &MsgValue::Nil => Ok(ctx.null()),
+        &MsgValue::Boolean(b) => Ok(ctx.boolean(b)),
+        &MsgValue::Integer(i) => match i.as_i64() {
+            Some(i) => Ok(ctx.integer(i)),
+            None => Ok(ctx.float(i.as_f64().unwrap_or(0.0))),
+        },
+        &MsgValue::F32(f) => Ok(ctx.float(f as f64)),
+        &MsgValue::F64(f) => Ok(ctx.float(f)),
+        &MsgValue::String(ref s) => match s.as_str() {
+            Some(s) => Ok(ctx.string(s)),
+            None => Err(Error::Conversion("string is not UTF-8".to_string())),
+        },
+        &MsgValue::Binary(ref data) => Ok(ctx.array_buffer(data)?),
+        &MsgValue::Array(ref items) => {
+            let items = items
+                .iter()
+                .map(|item| decode(ctx, item, depth + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(Value::from(ctx.array(&items)?))
+        }
+        &MsgValue::Map(ref entries) => {
+            let mut obj = ctx.object()?;
+
+            for &(ref key, ref val) in entries {
+                let key = match key {
+                    &MsgValue::String(ref s) if s.as_str().is_some() => {
+                        s.as_str().unwrap().to_string()
+                    }
+                    &MsgValue::Integer(i) => i.to_string(),
+                    _ => {
+                        return Err(Error::Conversion(format!(
+                            "unsupported map key {}",
+                            key
+                        )))
+                    }
+                };
+
+                if !obj.set(&key, decode(ctx, val, depth + 1)?) {
+                    return Err(Error::from(ctx.take_exception()));
+                }
+            }
+
+            Ok(obj.value)
+        }
+        &MsgValue::Ext(ty, _) => {
+            Err(Error::Conversion(format!("unsupported extension type {}", ty)))
+        }
+    }
+}
+
+impl Value {
+    /// Serializes this value as MessagePack.
+    ///
+    /// Objects become maps with string keys, `ArrayBuffer`s binary data and
+    /// both `null` and `undefined` become nil. Functions and symbols can't
+    /// be encoded.
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, Error> {
+        let val = encode(self, 0)?;
+        let mut buf = Vec::new();
+
+        rmpv::encode::write_value(&mut buf, &val)
+            .map_err(|err| Error::Conversion(err.to_string()))?;
+        Ok(buf)
+    }
+}
+
+impl Context {
+    /// Deserializes a MessagePack encoded value, see `Value::to_msgpack`.
+    pub fn from_msgpack(&self, mut data: &[u8]) -> Result<Value, Error> {
+        let val = rmpv::decode::read_value(&mut data)
+            .map_err(|err| Error::Conversion(err.to_string()))?;
+
+        decode(self, &val, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plain::PlainValue;
+    use crate::Runtime;
+
+    #[test]
+    fn roundtrip() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let val = ctx
+            .eval_script(
+                r#"({ a: 1, b: [true, null, "x"], c: { d: 1.5 } })"#,
+                "<test>",
+            )
+            .unwrap();
+        let buf = val.to_msgpack().unwrap();
+        let back = ctx.from_msgpack(&buf).unwrap();
+
+        assert_eq!(
+            PlainValue::from_value(&back).unwrap(),
+            PlainValue::from_value(&val).unwrap()
+        );
+    }
+
+    #[test]
+    fn binary() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let buf = ctx.array_buffer(&[0xde, 0xad]).unwrap();
+        let back = ctx.from_msgpack(&buf.to_msgpack().unwrap()).unwrap();
+
+        assert_eq!(back.array_buffer_data().unwrap(), vec![0xde, 0xad]);
+    }
+}
@@ -0,0 +1,349 @@
+use std::any::Any;
+use std::cell::{Ref, RefCell, RefMut};
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Once;
+
+use quickjs_sys as sys;
+
+use crate::object::Object;
+use crate::runtime::{Context, ContextPtr};
+use crate::trace;
+use crate::value::Value;
+
+pub(crate) type NativeCell = RefCell<Box<dyn Any>>;
+
+type Callback = Box<dyn Fn(&Context, Value, &[Value]) -> Value>;
+
+/// Data of functions created by `Context::closure`.
+struct Closure {
+    name: String,
+    f: Callback,
+    /// Whether calls are reported to the audit hook.
+    audited: bool,
+}
+
+static CLASS_ID: AtomicU32 = AtomicU32::new(0);
+static CLASS_ID_INIT: Once = Once::new();
+
+pub(crate) fn class_id() -> sys::JSClassID {
+    CLASS_ID_INIT.call_once(|| {
+        let mut id = 0;
+
+        unsafe {
+            sys::JS_NewClassID(&mut id);
+        }
+        CLASS_ID.store(id, Ordering::SeqCst);
+    });
+
+    CLASS_ID.load(Ordering::SeqCst)
+}
+
+extern "C" fn finalize(_rt: *mut sys::JSRuntime, val: sys::JSValue) {
+    unsafe {
+        let cell = sys::JS_GetOpaque(val, class_id()) as *mut NativeCell;
+
+        if !cell.is_null() {
+            drop(Box::from_raw(cell));
+        }
+    }
+}
+
+/// Registers the class of objects wrapping Rust values with `rt`. Returns
+/// `false` on failure.
+pub(crate) unsafe fn register_class(rt: *mut sys::JSRuntime) -> bool {
+    let def = sys::JSClassDef {
+        class_name: b"NativeValue\0".as_ptr() as *const i8,
+        finalizer: Some(finalize),
+        gc_mark: None,
+        call: None,
+        exotic: ptr::null_mut(),
+    };
+
+    sys::JS_NewClass(rt, class_id(), &def) >= 0
+}
+
+/// Wraps `val` in a new object. It's dropped when the object is collected.
+pub(crate) fn new_native(
+    ctx: &Context,
+    val: Box<dyn Any>,
+) -> Result<Value, Value> {
+    let obj = unsafe {
+        Value {
+            value: sys::JS_NewObjectClass(ctx.ptr.as_ptr(), class_id() as i32),
+            context: ctx.ptr.clone(),
+        }
+    };
+
+    if obj.is_exception() {
+        return Err(ctx.take_exception());
+    }
+
+    let cell: Box<NativeCell> = Box::new(RefCell::new(val));
+
+    unsafe {
+        sys::JS_SetOpaque(obj.value, Box::into_raw(cell) as *mut c_void);
+    }
+
+    Ok(obj)
+}
+
+/// Returns the Rust value wrapped by `val`, if it was created by
+/// `new_native`.
+pub(crate) fn native_cell(val: &Value) -> Option<&NativeCell> {
+    unsafe {
+        let cell =
+            sys::JS_GetOpaque(val.value, class_id()) as *const NativeCell;
+
+        if cell.is_null() {
+            None
+        } else {
+            Some(&*cell)
+        }
+    }
+}
+
+impl Value {
+    /// Borrows the Rust value boxed by `Context::wrap_native`. Returns `None`
+    /// if this isn't a wrapped `T` or it's mutably borrowed.
+    pub fn native_ref<T: 'static>(&self) -> Option<Ref<'_, T>> {
+        let cell = native_cell(self)?.try_borrow().ok()?;
+
+        if cell.is::<T>() {
+            Some(Ref::map(cell, |val| val.downcast_ref::<T>().unwrap()))
+        } else {
+            None
+        }
+    }
+
+    /// Mutably borrows the Rust value boxed by `Context::wrap_native`.
+    /// Returns `None` if this isn't a wrapped `T` or it's already borrowed.
+    pub fn native_mut<T: 'static>(&self) -> Option<RefMut<'_, T>> {
+        let cell = native_cell(self)?.try_borrow_mut().ok()?;
+
+        if cell.is::<T>() {
+            Some(RefMut::map(cell, |val| val.downcast_mut::<T>().unwrap()))
+        } else {
+            None
+        }
+    }
+}
+
+extern "C" fn call_closure(
+    ctx: *mut sys::JSContext,
+    this: sys::JSValue,
+    argc: i32,
+    argv: *mut sys::JSValue,
+    _magic: i32,
+    data: *mut sys::JSValue,
+) -> sys::JSValue {
+    assert!(!ctx.is_null());
+
+    let ctx = Context { ptr: ContextPtr::Borrowed(ctx) };
+    let dup = |v| unsafe {
+        Value {
+            value: sys::Helper_JS_DupValue(ctx.ptr.as_ptr(), v),
+            context: ctx.ptr.clone(),
+        }
+    };
+    let this = dup(this);
+    let args = (0..argc as isize)
+        .map(|idx| unsafe { dup(*argv.offset(idx)) })
+        .collect::<Vec<_>>();
+    let data = unsafe { dup(*data) };
+    let cell = native_cell(&data).map(|cell| cell.borrow());
+    let closure = cell.as_ref().and_then(|c| c.downcast_ref::<Closure>());
+    let closure = match closure {
+        Some(closure) => closure,
+        None => unsafe {
+            return sys::JS_ThrowInternalError(
+                ctx.ptr.as_ptr(),
+                b"closure data mismatch\0".as_ptr() as *const i8,
+            );
+        },
+    };
+
+    let _span = trace::native_call(&closure.name);
+
+    if closure.audited {
+        ctx.audit(&closure.name, &args);
+    }
+
+    let ret = panic::catch_unwind(AssertUnwindSafe(|| {
+        (closure.f)(&ctx, this, &args).into_raw()
+    }));
+
+    ret.unwrap_or_else(|_| __throw_panic(&ctx))
+}
+
+/// Throws an `InternalError` for a host function that panicked, as the
+/// panic mustn't unwind into the engine.
+#[doc(hidden)]
+pub fn __throw_panic(ctx: &Context) -> sys::JSValue {
+    unsafe {
+        sys::JS_ThrowInternalError(
+            ctx.ptr.as_ptr(),
+            b"host function panicked\0".as_ptr() as *const i8,
+        )
+    }
+}
+
+impl Context {
+    /// Boxes `value` into an opaque JavaScript object. Scripts can pass it
+    /// around but not look inside. `value` is dropped when the object is
+    /// collected.
+    pub fn wrap_native<T: 'static>(&self, value: T) -> Result<Value, Value> {
+        new_native(self, Box::new(value))
+    }
+
+    /// Creates a JavaScript function calling `f`. Unlike `function`, `f` can
+    /// be a closure capturing host state. It's dropped together with the
+    /// function object.
+    pub fn closure<F>(&self, name: &str, f: F) -> Result<Value, Value>
+    where
+        F: Fn(&Context, Value, &[Value]) -> Value + 'static,
+    {
+        self.new_closure(name, Box::new(f), true)
+    }
+
+    /// Like `closure`, for the crate's own helpers. Calls aren't reported
+    /// to the audit hook or counted as host calls.
+    pub(crate) fn internal_closure<F>(
+        &self,
+        name: &str,
+        f: F,
+    ) -> Result<Value, Value>
+    where
+        F: Fn(&Context, Value, &[Value]) -> Value + 'static,
+    {
+        self.new_closure(name, Box::new(f), false)
+    }
+
+    fn new_closure(
+        &self,
+        name: &str,
+        f: Callback,
+        audited: bool,
+    ) -> Result<Value, Value> {
+        let closure = Closure { name: name.to_string(), f, audited };
+        let data = new_native(self, Box::new(closure))?;
+        let mut raw = data.value;
+        let val = unsafe {
+            Value {
+                value: sys::JS_NewCFunctionData(
+                    self.ptr.as_ptr(),
+                    Some(call_closure),
+                    0,
+                    0,
+                    1,
+                    &mut raw,
+                ),
+                context: self.ptr.clone(),
+            }
+        };
+
+        if val.is_exception() {
+            return Err(self.take_exception());
+        }
+
+        let mut func = Object { value: val };
+
+        if !func.define("name", self.string(name), sys::JS_PROP_CONFIGURABLE) {
+            return Err(self.take_exception());
+        }
+
+        Ok(func.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::native_cell;
+    use crate::Runtime;
+
+    #[test]
+    fn closure() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let calls = Rc::new(Cell::new(0));
+        let c = calls.clone();
+        let f = ctx
+            .closure("count", move |ctx, _, args| {
+                c.set(c.get() + 1);
+                ctx.integer(args.len() as i64)
+            })
+            .unwrap();
+
+        let ret = f.call(ctx.undefined(), &[ctx.null(), ctx.null()]);
+
+        assert_eq!(ret.as_integer(), Some(2));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn panicking_closure() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let f = ctx.closure("boom", |_, _, _| panic!("boom")).unwrap();
+
+        ctx.global().set("boom", f);
+
+        let ret = ctx
+            .eval_script(
+                "try { boom(); } catch (e) { e.name + ': ' + e.message }",
+                "<test>",
+            )
+            .unwrap();
+
+        assert_eq!(
+            ret.as_string().unwrap(),
+            "InternalError: host function panicked"
+        );
+    }
+
+    #[test]
+    fn wrap_native() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let dropped = Rc::new(Cell::new(false));
+
+        struct Handle(Rc<Cell<bool>>);
+
+        impl Drop for Handle {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let val = ctx.wrap_native(Handle(dropped.clone())).unwrap();
+
+        assert!(val.is_object());
+        assert!(native_cell(&val).unwrap().borrow().is::<Handle>());
+
+        drop(val);
+        drop(ctx);
+        drop(rt);
+        assert!(dropped.get());
+    }
+
+    #[test]
+    fn native_ref() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let val = ctx.wrap_native(vec![1, 2]).unwrap();
+
+        val.native_mut::<Vec<i32>>().unwrap().push(3);
+
+        let r = val.native_ref::<Vec<i32>>().unwrap();
+
+        assert_eq!(*r, vec![1, 2, 3]);
+        assert!(val.native_mut::<Vec<i32>>().is_none());
+        assert!(val.native_ref::<String>().is_none());
+        assert!(ctx.integer(1).native_ref::<Vec<i32>>().is_none());
+    }
+}
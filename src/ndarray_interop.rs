@@ -0,0 +1,166 @@
+use ndarray::{Array1, Array2, ArrayView1, ArrayView2};
+use quickjs_sys as sys;
+
+use crate::error::Error;
+use crate::object::Object;
+use crate::runtime::Context;
+use crate::value::Value;
+
+impl Context {
+    fn new_float64_array(&self, data: &[f64]) -> Result<Value, Value> {
+        let bytes = data
+            .iter()
+            .flat_map(|x| x.to_ne_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let buf = self.array_buffer(&bytes)?;
+        let ctor = self.global().get("Float64Array")?;
+        let mut args = [buf.value];
+        let val = unsafe {
+            Value {
+                value: sys::JS_CallConstructor(
+                    self.ptr.as_ptr(),
+                    ctor.value,
+                    1,
+                    args.as_mut_ptr(),
+                ),
+                context: self.ptr.clone(),
+            }
+        };
+
+        if val.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(val)
+        }
+    }
+
+    /// Copies `view` into a new `Float64Array`.
+    pub fn float64_array(&self, view: ArrayView1<f64>) -> Result<Value, Value> {
+        self.new_float64_array(&view.iter().cloned().collect::<Vec<_>>())
+    }
+
+    /// Copies `view` into a new `Float64Array` in row-major order. The
+    /// array gets a `shape` property `[rows, columns]`.
+    pub fn float64_matrix(
+        &self,
+        view: ArrayView2<f64>,
+    ) -> Result<Value, Value> {
+        let (rows, cols) = view.dim();
+        let val =
+            self.new_float64_array(&view.iter().cloned().collect::<Vec<_>>())?;
+        let shape = self
+            .array(&[self.integer(rows as i64), self.integer(cols as i64)])?;
+        let mut obj = Object { value: val };
+
+        if !obj.set("shape", Value::from(shape)) {
+            return Err(self.take_exception());
+        }
+
+        Ok(obj.value)
+    }
+}
+
+impl Value {
+    /// Copies the elements of a `Float64Array`.
+    fn float64_data(&self) -> Result<Vec<f64>, Error> {
+        let ctx = Context { ptr: self.context.clone() };
+        let ctor = ctx.global().get("Float64Array")?;
+        let is_f64 = unsafe {
+            sys::JS_IsInstanceOf(ctx.ptr.as_ptr(), self.value, ctor.value)
+        };
+
+        if is_f64 <= 0 {
+            return Err(Error::Conversion(format!(
+                "{:?} is not a Float64Array",
+                self
+            )));
+        }
+
+        let mut offset = 0;
+        let mut len = 0;
+        let mut bpe = 0;
+        let buf = unsafe {
+            Value {
+                value: sys::JS_GetTypedArrayBuffer(
+                    ctx.ptr.as_ptr(),
+                    self.value,
+                    &mut offset,
+                    &mut len,
+                    &mut bpe,
+                ),
+                context: ctx.ptr.clone(),
+            }
+        };
+
+        if buf.is_exception() {
+            return Err(Error::from(ctx.take_exception()));
+        }
+
+        buf.with_array_buffer(|data| {
+            data[offset..offset + len]
+                .chunks_exact(8)
+                .map(|b| {
+                    let mut x = [0; 8];
+                    x.copy_from_slice(b);
+                    f64::from_ne_bytes(x)
+                })
+                .collect()
+        })
+        .ok_or_else(|| {
+            Error::Conversion("array buffer is detached".to_string())
+        })
+    }
+
+    /// Copies a `Float64Array` into an `Array1`.
+    pub fn to_array1(&self) -> Result<Array1<f64>, Error> {
+        Ok(Array1::from(self.float64_data()?))
+    }
+
+    /// Copies a `Float64Array` with a `shape` property as created by
+    /// `Context::float64_matrix` into an `Array2`.
+    pub fn to_array2(&self) -> Result<Array2<f64>, Error> {
+        let data = self.float64_data()?;
+        let obj = Object { value: self.clone() };
+        let shape = Object { value: obj.get("shape")? };
+        let rows = shape.get("0")?.as_integer();
+        let cols = shape.get("1")?.as_integer();
+
+        match (rows, cols) {
+            (Some(rows), Some(cols)) if rows >= 0 && cols >= 0 => {
+                Array2::from_shape_vec((rows as usize, cols as usize), data)
+                    .map_err(|err| Error::Conversion(err.to_string()))
+            }
+            _ => Err(Error::Conversion("missing or invalid shape".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{arr1, arr2};
+
+    use crate::Runtime;
+
+    #[test]
+    fn matrix_roundtrip() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let m = arr2(&[[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+
+        let val = ctx.float64_matrix(m.t()).unwrap();
+        let scale = ctx
+            .eval_script(
+                "(m) => { const r = m.map((x) => x * 2); r.shape = m.shape; return r; }",
+                "<test>",
+            )
+            .unwrap();
+        let ret = scale.call(ctx.undefined(), &[val]);
+
+        assert_eq!(ret.to_array2().unwrap(), m.t().to_owned() * 2.0);
+
+        let v = ctx.float64_array(arr1(&[0.5, 1.5]).view()).unwrap();
+
+        assert_eq!(v.to_array1().unwrap(), arr1(&[0.5, 1.5]));
+        assert!(ctx.integer(1).to_array1().is_err());
+    }
+}
@@ -1,11 +1,47 @@
+use std::ops::BitOr;
+use std::os::raw::c_void;
+use std::ptr;
+
 use quickjs_sys as sys;
 
+use crate::convert::type_name;
+use crate::error::Error;
+use crate::runtime::Context;
 use crate::value::Value;
 
 pub struct Object {
     pub(crate) value: Value,
 }
 
+/// Selects the properties returned by `Object::keys_with` and
+/// `Object::entries_with`. Combine with `|`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PropertyFlags(u32);
+
+impl PropertyFlags {
+    /// String-keyed properties.
+    pub const STRING: PropertyFlags = PropertyFlags(sys::JS_GPN_STRING_MASK);
+    /// Symbol-keyed properties.
+    pub const SYMBOL: PropertyFlags = PropertyFlags(sys::JS_GPN_SYMBOL_MASK);
+    /// Only enumerable properties.
+    pub const ENUM_ONLY: PropertyFlags = PropertyFlags(sys::JS_GPN_ENUM_ONLY);
+}
+
+impl BitOr for PropertyFlags {
+    type Output = PropertyFlags;
+
+    fn bitor(self, other: PropertyFlags) -> PropertyFlags {
+        PropertyFlags(self.0 | other.0)
+    }
+}
+
+/// Key of an object property.
+#[derive(Debug)]
+pub enum PropertyKey {
+    String(String),
+    Symbol(Value),
+}
+
 impl Object {
     pub fn set(&mut self, key: &str, val: Value) -> bool {
         let mut cstr = key.as_bytes().to_vec();
@@ -42,17 +78,357 @@ impl Object {
             Ok(val)
         }
     }
+
+    /// Removes the property `key`. Returns `false` if it can't be deleted,
+    /// e.g. because it's not configurable.
+    pub fn delete(&mut self, key: &str) -> bool {
+        let mut cstr = key.as_bytes().to_vec();
+
+        cstr.push(0);
+
+        unsafe {
+            let ctx = self.value.context.as_ptr();
+            let atom = sys::JS_NewAtom(ctx, cstr.as_ptr() as *const i8);
+            let rc = sys::JS_DeleteProperty(ctx, self.value.value, atom, 0);
+
+            sys::JS_FreeAtom(ctx, atom);
+            rc > 0
+        }
+    }
+
+    /// Names of the own enumerable string-keyed properties, in property
+    /// order.
+    pub fn keys(&self) -> Result<Vec<String>, Value> {
+        let names = self
+            .property_names(sys::JS_GPN_STRING_MASK | sys::JS_GPN_ENUM_ONLY)?;
+
+        Ok(names.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Own property keys selected by `flags`, in property order.
+    pub fn keys_with(
+        &self,
+        flags: PropertyFlags,
+    ) -> Result<Vec<PropertyKey>, Value> {
+        let ctx = self.value.context.as_ptr();
+        let mut keys = Vec::new();
+
+        self.own_properties(flags.0, |atom, _| {
+            let key = Value {
+                value: unsafe { sys::JS_AtomToValue(ctx, atom) },
+                context: self.value.context.clone(),
+            };
+
+            if key.is_symbol() {
+                keys.push(PropertyKey::Symbol(key));
+            } else {
+                keys.push(PropertyKey::String(
+                    key.as_string().unwrap_or_default(),
+                ));
+            }
+            Ok(())
+        })?;
+
+        Ok(keys)
+    }
+
+    /// Own properties selected by `flags` and their values.
+    pub fn entries_with(
+        &self,
+        flags: PropertyFlags,
+    ) -> Result<Vec<(PropertyKey, Value)>, Value> {
+        let ctx = self.value.context.as_ptr();
+        let keys = self.keys_with(flags)?;
+
+        keys.into_iter()
+            .map(|key| -> Result<_, Value> {
+                let val = match &key {
+                    &PropertyKey::String(ref key) => self.get(key)?,
+                    &PropertyKey::Symbol(ref sym) => unsafe {
+                        let atom = sys::JS_ValueToAtom(ctx, sym.value);
+                        let val = Value {
+                            value: sys::JS_GetPropertyInternal(
+                                ctx,
+                                self.value.value,
+                                atom,
+                                self.value.value,
+                                0,
+                            ),
+                            context: self.value.context.clone(),
+                        };
+
+                        sys::JS_FreeAtom(ctx, atom);
+                        if val.is_exception() {
+                            let ctx =
+                                Context { ptr: self.value.context.clone() };
+                            return Err(ctx.take_exception());
+                        }
+                        val
+                    },
+                };
+                Ok((key, val))
+            })
+            .collect()
+    }
+
+    /// Own property names and whether they are enumerable. `flags` are the
+    /// `JS_GPN_*` flags of `JS_GetOwnPropertyNames`.
+    pub(crate) fn property_names(
+        &self,
+        flags: u32,
+    ) -> Result<Vec<(String, bool)>, Value> {
+        let ctx = self.value.context.as_ptr();
+        let mut names = Vec::new();
+
+        self.own_properties(flags, |atom, enumerable| {
+            let name = Value {
+                value: unsafe { sys::JS_AtomToString(ctx, atom) },
+                context: self.value.context.clone(),
+            };
+
+            names.push((name.as_string().unwrap_or_default(), enumerable));
+            Ok(())
+        })?;
+
+        Ok(names)
+    }
+
+    /// Calls `f` with the atom and enumerability of each own property
+    /// selected by the `JS_GPN_*` `flags`.
+    fn own_properties<F>(&self, flags: u32, mut f: F) -> Result<(), Value>
+    where
+        F: FnMut(sys::JSAtom, bool) -> Result<(), Value>,
+    {
+        let ctx = self.value.context.as_ptr();
+        let mut tab = ptr::null_mut();
+        let mut len = 0u32;
+        let rc = unsafe {
+            sys::JS_GetOwnPropertyNames(
+                ctx,
+                &mut tab,
+                &mut len,
+                self.value.value,
+                flags as i32,
+            )
+        };
+
+        if rc < 0 {
+            let ctx = Context { ptr: self.value.context.clone() };
+            return Err(ctx.take_exception());
+        }
+
+        let mut ret = Ok(());
+
+        unsafe {
+            for idx in 0..len as isize {
+                let prop = &*tab.offset(idx);
+
+                if ret.is_ok() {
+                    ret = f(prop.atom, prop.is_enumerable != 0);
+                }
+                sys::JS_FreeAtom(ctx, prop.atom);
+            }
+
+            sys::js_free(ctx, tab as *mut c_void);
+        }
+
+        ret
+    }
+
+    /// Own enumerable string-keyed properties and their values.
+    pub fn entries(&self) -> Result<Vec<(String, Value)>, Value> {
+        self.keys()?
+            .into_iter()
+            .map(|key| -> Result<_, Value> {
+                let val = self.get(&key).map_err(|_| {
+                    let ctx = Context { ptr: self.value.context.clone() };
+                    ctx.take_exception()
+                })?;
+                Ok((key, val))
+            })
+            .collect()
+    }
+
+    /// Reads a nested property, e.g. `server.tls.cert`. Like `get`, a
+    /// missing last property is `undefined`, but missing or non-object
+    /// properties on the way there are errors naming them.
+    pub fn get_path(&self, path: &str) -> Result<Value, Error> {
+        let (parent, key) = self.parent(path)?;
+
+        parent.get(key).map_err(|_| parent.exception())
+    }
+
+    /// Sets a nested property, e.g. `server.tls.cert`. The objects on the
+    /// way there must exist.
+    pub fn set_path(&mut self, path: &str, val: Value) -> Result<(), Error> {
+        let (mut parent, key) = self.parent(path)?;
+
+        if parent.set(key, val) {
+            Ok(())
+        } else {
+            Err(parent.exception())
+        }
+    }
+
+    /// Follows `path` up to the last property. Returns the object holding
+    /// it and its name.
+    fn parent<'a>(&self, path: &'a str) -> Result<(Object, &'a str), Error> {
+        let mut keys = path.split('.');
+        let last = keys.next_back().unwrap();
+        let mut obj = Object { value: self.value.clone() };
+        let mut seen = 0;
+
+        for key in keys {
+            seen += key.len() + 1;
+
+            let val = obj.get(key).map_err(|_| obj.exception())?;
+            let prefix = &path[..seen - 1];
+
+            if val.is_undefined() {
+                return Err(Error::MissingProperty(prefix.to_string()));
+            }
+            if !val.is_object() {
+                return Err(Error::Conversion(format!(
+                    "expected object at '{}', got {}",
+                    prefix,
+                    type_name(&val)
+                )));
+            }
+
+            obj = Object { value: val };
+        }
+
+        Ok((obj, last))
+    }
+
+    /// Takes the pending exception of the object's context.
+    fn exception(&self) -> Error {
+        let ctx = Context { ptr: self.value.context.clone() };
+        Error::from(ctx.take_exception())
+    }
+
+    pub(crate) fn define(&mut self, key: &str, val: Value, flags: u32) -> bool {
+        let mut cstr = key.as_bytes().to_vec();
+
+        cstr.push(0);
+
+        unsafe {
+            sys::JS_DefinePropertyValueStr(
+                self.value.context.as_ptr(),
+                self.value.value,
+                cstr.as_ptr() as *const i8,
+                sys::Helper_JS_DupValue(self.value.context.as_ptr(), val.value),
+                flags as i32,
+            ) >= 0
+        }
+    }
+}
+
+impl Value {
+    /// The value as an `Object`, or `None` if it's a primitive.
+    pub fn into_object(self) -> Option<Object> {
+        if self.is_object() {
+            Some(Object { value: self })
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Runtime;
+    use super::{Object, PropertyFlags, PropertyKey};
+    use crate::{Error, Runtime};
+
+    #[test]
+    fn paths() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let val = ctx
+            .eval_script(
+                "({ server: { port: 1, tls: { cert: 'a.pem' } } })",
+                "<test>",
+            )
+            .unwrap();
+        let mut obj = Object { value: val };
+
+        assert_eq!(
+            obj.get_path("server.tls.cert").unwrap().as_string().unwrap(),
+            "a.pem"
+        );
+        assert!(obj.get_path("server.tls.key").unwrap().is_undefined());
+        assert_eq!(
+            obj.get_path("server.http.port"),
+            Err(Error::MissingProperty("server.http".to_string()))
+        );
+        assert_eq!(
+            obj.get_path("server.port.number"),
+            Err(Error::Conversion(
+                "expected object at 'server.port', got number".to_string()
+            ))
+        );
+
+        obj.set_path("server.tls.key", ctx.string("b.pem")).unwrap();
+
+        assert_eq!(
+            obj.get_path("server.tls.key").unwrap().as_string().unwrap(),
+            "b.pem"
+        );
+        assert!(obj.set_path("client.tls", ctx.null()).is_err());
+    }
 
     #[test]
     fn new() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
 
         let _ = ctx.object();
     }
+
+    #[test]
+    fn keys() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let mut obj = ctx.object().unwrap();
+
+        assert!(obj.set("a", ctx.integer(1)));
+        assert!(obj.set("b", ctx.integer(2)));
+
+        assert_eq!(obj.keys().unwrap(), vec!["a", "b"]);
+
+        let entries = obj.entries().unwrap();
+
+        assert_eq!(entries[1].0, "b");
+        assert_eq!(entries[1].1.as_integer(), Some(2));
+    }
+
+    #[test]
+    fn keys_with() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let obj = ctx
+            .eval_script(
+                r#"
+                let o = { a: 1, [Symbol("s")]: 2 };
+                Object.defineProperty(o, "hidden", { value: 3 });
+                o
+                "#,
+                "<test>",
+            )
+            .unwrap();
+        let obj = Object { value: obj };
+
+        assert_eq!(obj.keys_with(PropertyFlags::STRING).unwrap().len(), 2);
+
+        let entries = obj
+            .entries_with(PropertyFlags::SYMBOL | PropertyFlags::ENUM_ONLY)
+            .unwrap();
+
+        assert_eq!(entries.len(), 1);
+        match &entries[0].0 {
+            &PropertyKey::Symbol(_) => {}
+            key => panic!("{:?}", key),
+        }
+        assert_eq!(entries[0].1.as_integer(), Some(2));
+    }
 }
@@ -0,0 +1,173 @@
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Sets the operator set on the prototype of a class, so its instances
+/// pick it up.
+const DEFINE: &str = r#"(function (cls, ops, ...others) {
+    cls.prototype[Symbol.operatorSet] = Operators.create(ops, ...others);
+})"#;
+
+/// Which side of a binary operator the other class is on.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// Overloaded operators of a class, see `Context::define_operators`.
+///
+/// Operators are named like in QuickJS' `Operators.create`: the binary
+/// `"+"`, `"-"`, `"*"`, `"/"`, `"%"`, `"**"`, `"|"`, `"&"`, `"^"`,
+/// `"<<"`, `">>"`, `">>>"`, `"=="` and `"<"`, and the unary `"pos"`,
+/// `"neg"`, `"++"`, `"--"` and `"~"`. `"=="` also gives `!=` and `"<"`
+/// the other comparisons. Implementations are functions taking the
+/// operands, made with `Context::closure` or by a script.
+#[derive(Default)]
+pub struct OperatorSet {
+    ops: Vec<(String, Value)>,
+    others: Vec<(Side, Value, Vec<(String, Value)>)>,
+}
+
+impl OperatorSet {
+    pub fn new() -> Self {
+        OperatorSet::default()
+    }
+
+    /// Implements `op` for operands that are both instances of the class,
+    /// or for the single operand of a unary operator.
+    pub fn op(mut self, op: &str, f: Value) -> Self {
+        self.ops.push((op.to_string(), f));
+        self
+    }
+
+    /// Implements `op` for an instance of `other`, like `Number`, on the
+    /// left and one of the class on the right.
+    pub fn left(self, other: &Value, op: &str, f: Value) -> Self {
+        self.with(Side::Left, other, op, f)
+    }
+
+    /// Implements `op` for an instance of the class on the left and one of
+    /// `other` on the right.
+    pub fn right(self, other: &Value, op: &str, f: Value) -> Self {
+        self.with(Side::Right, other, op, f)
+    }
+
+    fn with(mut self, side: Side, other: &Value, op: &str, f: Value) -> Self {
+        // QuickJS only looks at the first entry for a class
+        let idx = self.others.iter().position(|(s, class, _)| {
+            *s == side && unsafe { class.value.u.ptr == other.value.u.ptr }
+        });
+        let idx = idx.unwrap_or_else(|| {
+            self.others.push((side, other.clone(), Vec::new()));
+            self.others.len() - 1
+        });
+
+        self.others[idx].2.push((op.to_string(), f));
+        self
+    }
+}
+
+impl Context {
+    /// Overloads operators for instances of `class`, a constructor. Mixing
+    /// with another class in `OperatorSet::left` or `right` requires that
+    /// class to have operators defined already. Number, BigInt and the
+    /// other builtin numeric classes have.
+    ///
+    /// Needs the `operators` feature, which builds QuickJS with
+    /// `CONFIG_BIGNUM`.
+    pub fn define_operators(
+        &self,
+        class: &Value,
+        set: OperatorSet,
+    ) -> Result<(), Value> {
+        let mut args = vec![class.clone(), self.operator_table(None, set.ops)?];
+
+        for (side, other, ops) in set.others {
+            args.push(self.operator_table(Some((side, other)), ops)?);
+        }
+
+        let ret = self.without_microtasks(|| {
+            let define = self.eval_script(DEFINE, "<operators>")?;
+
+            Ok(define.call(self.undefined(), &args))
+        })?;
+
+        if ret.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The object `Operators.create` takes for a group of operators.
+    fn operator_table(
+        &self,
+        other: Option<(Side, Value)>,
+        ops: Vec<(String, Value)>,
+    ) -> Result<Value, Value> {
+        let mut table = self.object()?;
+        let side = match other {
+            Some((Side::Left, class)) => table.set("left", class),
+            Some((Side::Right, class)) => table.set("right", class),
+            None => true,
+        };
+
+        if !side || !ops.into_iter().all(|(op, f)| table.set(&op, f)) {
+            return Err(self.take_exception());
+        }
+
+        Ok(table.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::Object;
+    use crate::Runtime;
+
+    #[test]
+    fn units() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let eval = |src: &str| ctx.eval_script(src, "<test>").unwrap();
+        let meters = eval(
+            "globalThis.Meters = class Meters {
+                constructor(v) { this.v = v; }
+            }",
+        );
+        let add = ctx
+            .closure("add", |ctx, _, args| {
+                let v = |idx: usize| {
+                    let obj = Object { value: args[idx].clone() };
+
+                    obj.get("v").unwrap().as_float().unwrap()
+                };
+
+                ctx.eval_script(
+                    &format!("new Meters({})", v(0) + v(1)),
+                    "<add>",
+                )
+                .unwrap_or_else(|ex| ctx.throw(ex))
+            })
+            .unwrap();
+        let set = OperatorSet::new()
+            .op("+", add)
+            .op("<", eval("(a, b) => a.v < b.v"))
+            .right(&eval("Number"), "*", eval("(a, n) => new Meters(a.v * n)"))
+            .left(&eval("Number"), "*", eval("(n, a) => new Meters(n * a.v)"));
+
+        ctx.define_operators(&meters, set).unwrap();
+
+        assert_eq!(
+            eval("(new Meters(1) + new Meters(2)).v").as_integer(),
+            Some(3)
+        );
+        assert_eq!(eval("(2 * new Meters(3) * 2).v").as_integer(), Some(12));
+        assert_eq!(
+            eval("new Meters(1) > new Meters(2)").as_boolean(),
+            Some(false)
+        );
+        assert_eq!(eval("1 + 2").as_integer(), Some(3));
+    }
+}
@@ -0,0 +1,207 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Sub};
+
+use quickjs_sys as sys;
+
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Applies an operator like scripts do. Comparing gives -1, 0 or 1, or
+/// `NaN` for unordered operands.
+const BINARY: &str = concat!(
+    r#"(function (op, a, b) {
+    switch (op) {
+    case 0: return a + b;
+    case 1: return a - b;
+    case 2: return a * b;
+    case 3: return a / b;
+    default: return a < b ? -1 : a > b ? 1 : a <= b ? 0 : NaN;
+    }
+})"#,
+    "\0"
+);
+
+#[derive(Clone, Copy, Debug)]
+enum BinaryOp {
+    Add = 0,
+    Sub = 1,
+    Mul = 2,
+    Div = 3,
+    Compare = 4,
+}
+
+impl Value {
+    /// `self + rhs` in JS, so numbers add, strings concatenate and objects
+    /// are converted with `valueOf`. Returns the exception if there is
+    /// one, like for mixing BigInt and Number.
+    pub fn checked_add(&self, rhs: &Value) -> Result<Value, Value> {
+        self.binary(BinaryOp::Add, rhs)
+    }
+
+    /// `self - rhs` in JS.
+    pub fn checked_sub(&self, rhs: &Value) -> Result<Value, Value> {
+        self.binary(BinaryOp::Sub, rhs)
+    }
+
+    /// `self * rhs` in JS.
+    pub fn checked_mul(&self, rhs: &Value) -> Result<Value, Value> {
+        self.binary(BinaryOp::Mul, rhs)
+    }
+
+    /// `self / rhs` in JS. Dividing by zero gives an infinity or `NaN`,
+    /// for BigInts a `RangeError`.
+    pub fn checked_div(&self, rhs: &Value) -> Result<Value, Value> {
+        self.binary(BinaryOp::Div, rhs)
+    }
+
+    /// Compares with the JS relational operators, which may run `valueOf`
+    /// of objects. Returns `None` for unordered values, like `NaN`, and if
+    /// a conversion throws. Not a `PartialOrd`, as `==` is identity.
+    pub fn js_cmp(&self, rhs: &Value) -> Option<Ordering> {
+        let ret = self.binary(BinaryOp::Compare, rhs).ok()?;
+
+        match ret.as_integer()? {
+            -1 => Some(Ordering::Less),
+            0 => Some(Ordering::Equal),
+            _ => Some(Ordering::Greater),
+        }
+    }
+
+    fn binary(&self, op: BinaryOp, rhs: &Value) -> Result<Value, Value> {
+        let ctx = Context { ptr: self.context.clone() };
+
+        ctx.without_microtasks(|| self.apply(&ctx, op, rhs))
+    }
+
+    fn apply(
+        &self,
+        ctx: &Context,
+        op: BinaryOp,
+        rhs: &Value,
+    ) -> Result<Value, Value> {
+        let state = ctx.ptr.state();
+        let cached = state.binary_ops.borrow().as_ref().map(|f| f.owned(ctx));
+        let f = match cached {
+            Some(f) => f,
+            None => {
+                let f = compile_binary(ctx)?;
+
+                state.binary_ops.replace(Some(f.clone().into_unowned()));
+                f
+            }
+        };
+        let op = ctx.integer(op as i64);
+        let ret = f.call(ctx.undefined(), &[op, self.clone(), rhs.clone()]);
+
+        if ret.is_exception() {
+            Err(ctx.take_exception())
+        } else {
+            Ok(ret)
+        }
+    }
+}
+
+/// Evaluates `BINARY`. Not through `eval_script`, it's no script of the
+/// embedder to count, trace or show in errors.
+fn compile_binary(ctx: &Context) -> Result<Value, Value> {
+    let _enter = ctx.ptr.enter();
+    let f = unsafe {
+        Value {
+            value: sys::JS_Eval(
+                ctx.ptr.as_ptr(),
+                BINARY.as_ptr() as *const i8,
+                BINARY.len() - 1,
+                b"<ops>\0".as_ptr() as *const i8,
+                sys::JS_EVAL_TYPE_GLOBAL as i32,
+            ),
+            context: ctx.ptr.clone(),
+        }
+    };
+
+    if f.is_exception() {
+        Err(ctx.take_exception())
+    } else {
+        Ok(f)
+    }
+}
+
+macro_rules! binary_op {
+    ($trait:ident, $method:ident, $checked:ident) => {
+        impl<'a> $trait<&'a Value> for &'a Value {
+            type Output = Result<Value, Value>;
+
+            fn $method(self, rhs: &'a Value) -> Result<Value, Value> {
+                self.$checked(rhs)
+            }
+        }
+    };
+}
+
+binary_op!(Add, add, checked_add);
+binary_op!(Sub, sub, checked_sub);
+binary_op!(Mul, mul, checked_mul);
+binary_op!(Div, div, checked_div);
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::runtime::ContextPtr;
+    use crate::Runtime;
+
+    #[test]
+    fn arithmetic() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let eval = |src: &str| ctx.eval_script(src, "<test>").unwrap();
+        let (a, b) = (ctx.integer(6), ctx.integer(4));
+
+        assert_eq!((&a + &b).unwrap().as_integer(), Some(10));
+        assert_eq!((&a - &b).unwrap().as_integer(), Some(2));
+        assert_eq!((&a * &b).unwrap().as_integer(), Some(24));
+        assert_eq!((&a / &b).unwrap().as_float(), Some(1.5));
+        assert_eq!(
+            (&ctx.string("n = ") + &a).unwrap().as_string().unwrap(),
+            "n = 6"
+        );
+        assert_eq!(
+            (&eval("({ valueOf: () => 2 })") * &b).unwrap().as_integer(),
+            Some(8)
+        );
+        assert!((&eval("1n") + &a).is_err());
+        assert!(eval("Symbol()").checked_sub(&a).is_err());
+    }
+
+    #[test]
+    fn ordering() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let (one, two) = (ctx.integer(1), ctx.float(2.5));
+        let nan = ctx.float(f64::NAN);
+
+        assert_eq!(one.js_cmp(&two), Some(Ordering::Less));
+        assert_eq!(two.js_cmp(&one), Some(Ordering::Greater));
+        assert_eq!(one.js_cmp(&ctx.float(1.0)), Some(Ordering::Equal));
+        assert_eq!(one.js_cmp(&nan), None);
+        assert_eq!(
+            ctx.string("a").js_cmp(&ctx.string("b")),
+            Some(Ordering::Less)
+        );
+    }
+
+    #[test]
+    fn context_freed() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let state = match ctx.ptr {
+            ContextPtr::Owned(ref owned) => Rc::downgrade(owned),
+            ContextPtr::Borrowed(_) => unreachable!(),
+        };
+
+        (&ctx.integer(1) + &ctx.integer(2)).unwrap();
+        drop(ctx);
+
+        assert!(state.upgrade().is_none());
+    }
+}
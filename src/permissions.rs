@@ -0,0 +1,170 @@
+use std::env;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::sandbox::{FsAccess, FsSandbox};
+
+/// Capabilities other than file access a script can be granted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    /// `std.urlGet`.
+    Net,
+    /// `std.popen`, `os.exec`, `os.kill` and `os.Worker`.
+    Spawn,
+    /// `std.getenv`, `std.setenv`, `std.unsetenv` and `std.getenviron`.
+    Env,
+}
+
+/// What scripts of a context may do through the `std` and `os` modules, see
+/// `ContextBuilder::permissions`. Everything is denied unless allowed.
+#[derive(Clone, Debug, Default)]
+pub struct Permissions {
+    read: Vec<PathBuf>,
+    write: Vec<PathBuf>,
+    net: bool,
+    spawn: bool,
+    env: bool,
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().unwrap_or_default().join(path)
+    };
+    let mut ret = PathBuf::new();
+
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                ret.pop();
+            }
+            comp => ret.push(comp),
+        }
+    }
+
+    ret
+}
+
+impl Permissions {
+    /// Allows reading files below `path`.
+    pub fn allow_read<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.read.push(normalize(path.as_ref()));
+        self
+    }
+
+    /// Allows reading and writing files below `path`.
+    pub fn allow_write<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.write.push(normalize(path.as_ref()));
+        self
+    }
+
+    pub fn allow_net(mut self, enable: bool) -> Self {
+        self.net = enable;
+        self
+    }
+
+    pub fn allow_spawn(mut self, enable: bool) -> Self {
+        self.spawn = enable;
+        self
+    }
+
+    pub fn allow_env(mut self, enable: bool) -> Self {
+        self.env = enable;
+        self
+    }
+
+    pub fn allows(&self, cap: Capability) -> bool {
+        match cap {
+            Capability::Net => self.net,
+            Capability::Spawn => self.spawn,
+            Capability::Env => self.env,
+        }
+    }
+}
+
+impl FsSandbox for Permissions {
+    fn resolve(&self, path: &Path, access: FsAccess) -> io::Result<PathBuf> {
+        let path = normalize(path);
+        let writable = self.write.iter().any(|dir| path.starts_with(dir));
+        let allowed = match access {
+            FsAccess::Read => {
+                writable || self.read.iter().any(|dir| path.starts_with(dir))
+            }
+            FsAccess::Write => writable,
+        };
+
+        if allowed {
+            Ok(path)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("access to {} denied", path.display()),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Permissions;
+    use crate::sandbox::{FsAccess, FsSandbox};
+    use crate::Runtime;
+
+    #[test]
+    fn allow_lists() {
+        let perms = Permissions::default()
+            .allow_read("/srv/scripts")
+            .allow_write("/tmp/out");
+
+        assert!(perms
+            .resolve(Path::new("/srv/scripts/a.js"), FsAccess::Read)
+            .is_ok());
+        assert!(perms
+            .resolve(Path::new("/srv/scripts/a.js"), FsAccess::Write)
+            .is_err());
+        assert!(perms
+            .resolve(Path::new("/srv/scripts/../secret"), FsAccess::Read)
+            .is_err());
+        assert!(perms.resolve(Path::new("/tmp/out/x"), FsAccess::Read).is_ok());
+    }
+
+    #[test]
+    fn capabilities() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt
+            .context_builder()
+            .permissions(Permissions::default().allow_env(true))
+            .build()
+            .unwrap();
+
+        ctx.eval(
+            r#"
+            import * as std from "std";
+            import * as os from "os";
+            std.setenv("QUICKJS_PERMISSIONS_TEST", "1");
+            globalThis.env = std.getenv("QUICKJS_PERMISSIONS_TEST");
+            try {
+                os.exec(["true"]);
+            } catch (e) {
+                globalThis.denied = e.message;
+            }
+            "#,
+            "main.js",
+            false,
+            false,
+        )
+        .unwrap();
+
+        let get = |name| ctx.eval_script(name, "<test>").unwrap();
+
+        assert_eq!(get("env").as_string().unwrap(), "1");
+        assert_eq!(
+            get("denied").as_string().unwrap(),
+            "permission to spawn processes denied"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use crate::error::Error;
+use crate::object::Object;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Objects nested deeper than this are assumed to be cyclic.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+/// A JavaScript value detached from its runtime.
+///
+/// Holds only data, no engine handles, so it can be sent to other threads
+/// and moved between runtimes. Functions, symbols and other values without
+/// a data representation can't be converted.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlainValue {
+    Undefined,
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<PlainValue>),
+    /// Own enumerable properties, in property order.
+    Object(Vec<(String, PlainValue)>),
+}
+
+impl PlainValue {
+    pub fn from_value(val: &Value) -> Result<PlainValue, Error> {
+        Self::convert(val, 0)
+    }
+
+    fn convert(val: &Value, depth: usize) -> Result<PlainValue, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::Conversion(
+                "value is nested too deeply or cyclic".to_string(),
+            ));
+        }
+
+        if val.is_undefined() {
+            Ok(PlainValue::Undefined)
+        } else if val.is_null() {
+            Ok(PlainValue::Null)
+        } else if let Some(b) = val.as_boolean() {
+            Ok(PlainValue::Bool(b))
+        } else if let Some(i) = val.as_integer() {
+            Ok(PlainValue::Int(i))
+        } else if let Some(f) = val.as_float() {
+            Ok(PlainValue::Float(f))
+        } else if let Some(s) = val.as_string() {
+            Ok(PlainValue::String(s))
+        } else if val.is_array() {
+            let ctx = Context { ptr: val.context.clone() };
+            let obj = Object { value: val.clone() };
+            let get = |key: &str| {
+                obj.get(key).map_err(|_| Error::from(ctx.take_exception()))
+            };
+            let len = get("length")?.as_integer().unwrap_or(0);
+
+            (0..len)
+                .map(|idx| {
+                    let item = get(&idx.to_string())?;
+                    Self::convert(&item, depth + 1)
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(PlainValue::Array)
+        } else if val.is_function() {
+            Err(Error::Conversion("functions can't be converted".to_string()))
+        } else if val.is_object() {
+            let obj = Object { value: val.clone() };
+
+            obj.entries()?
+                .into_iter()
+                .map(|(key, val)| -> Result<_, Error> {
+                    Ok((key, Self::convert(&val, depth + 1)?))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(PlainValue::Object)
+        } else {
+            Err(Error::Conversion(format!("can't convert {:?}", val)))
+        }
+    }
+
+    pub fn to_value(&self, ctx: &Context) -> Result<Value, Value> {
+        match self {
+            &PlainValue::Undefined => Ok(ctx.undefined()),
+            &PlainValue::Null => Ok(ctx.null()),
+            &PlainValue::Bool(b) => Ok(ctx.boolean(b)),
+            &PlainValue::Int(i) => Ok(ctx.integer(i)),
+            &PlainValue::Float(f) => Ok(ctx.float(f)),
+            &PlainValue::String(ref s) => Ok(ctx.string(s)),
+            &PlainValue::Array(ref items) => {
+                let items = items
+                    .iter()
+                    .map(|item| item.to_value(ctx))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                ctx.array(&items).map(Value::from)
+            }
+            &PlainValue::Object(ref props) => {
+                let mut obj = ctx.object()?;
+
+                for &(ref key, ref val) in props {
+                    if !obj.set(key, val.to_value(ctx)?) {
+                        return Err(ctx.take_exception());
+                    }
+                }
+
+                Ok(obj.value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn roundtrip() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let val = ctx
+            .eval_script(
+                r#"({ a: 1, b: [true, null, "x"], c: { d: 1.5 } })"#,
+                "<test>",
+            )
+            .unwrap();
+        let plain = PlainValue::from_value(&val).unwrap();
+
+        assert_eq!(
+            plain,
+            PlainValue::Object(vec![
+                ("a".to_string(), PlainValue::Int(1)),
+                (
+                    "b".to_string(),
+                    PlainValue::Array(vec![
+                        PlainValue::Bool(true),
+                        PlainValue::Null,
+                        PlainValue::String("x".to_string()),
+                    ])
+                ),
+                (
+                    "c".to_string(),
+                    PlainValue::Object(vec![(
+                        "d".to_string(),
+                        PlainValue::Float(1.5)
+                    )])
+                ),
+            ])
+        );
+
+        let val = plain.to_value(&ctx).unwrap();
+
+        assert_eq!(PlainValue::from_value(&val).unwrap(), plain);
+    }
+
+    #[test]
+    fn cyclic() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let val = ctx.eval_script("let o = {}; o.o = o; o", "<test>").unwrap();
+
+        assert!(PlainValue::from_value(&val).is_err());
+    }
+
+    #[test]
+    fn throwing_getter() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let val = ctx
+            .eval_script(
+                "({ a: [{ get b() { throw new Error('no b'); } }] })",
+                "<test>",
+            )
+            .unwrap();
+
+        match PlainValue::from_value(&val) {
+            Err(Error::Exception(ex)) => assert_eq!(ex.message, "no b"),
+            ret => panic!("{:?}", ret),
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::task::{self, Poll, Waker};
+use std::thread;
+
+use crate::error::Error;
+use crate::plain::PlainValue;
+use crate::runtime::Runtime;
+
+type Run = Box<dyn FnOnce(&mut Runtime) -> Result<PlainValue, Error> + Send>;
+type Queue = Arc<Mutex<mpsc::Receiver<Job>>>;
+type Workers = Arc<Mutex<Vec<thread::JoinHandle<()>>>>;
+
+struct Job {
+    run: Run,
+    slot: Arc<Mutex<Slot>>,
+}
+
+struct Slot {
+    result: Option<Result<PlainValue, Error>>,
+    waker: Option<Waker>,
+}
+
+/// A fixed set of runtimes, each on its own thread, sharing a queue of
+/// scripts.
+///
+/// A runtime only ever runs one script at a time, so this is the way to
+/// evaluate scripts in parallel. Every script gets a fresh context, nothing
+/// leaks from one evaluation into the next.
+pub struct RuntimePool {
+    sender: Option<mpsc::Sender<Job>>,
+    workers: Workers,
+}
+
+impl RuntimePool {
+    /// Starts `threads` worker threads with a runtime each.
+    pub fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(receiver));
+        let workers = Workers::default();
+
+        for _ in 0..threads {
+            spawn_worker(queue.clone(), workers.clone());
+        }
+
+        RuntimePool { sender: Some(sender), workers }
+    }
+
+    /// Evaluates `input` as a classic script on the next free runtime and
+    /// runs its jobs to completion. Resolves to the completion value of the
+    /// script.
+    pub fn eval(&self, input: &str) -> PoolEval {
+        let input = input.to_string();
+
+        self.submit(Box::new(move |rt| {
+            rt.context().and_then(|mut ctx| {
+                match ctx.eval_script(&input, "<pool>") {
+                    Ok(val) => ctx
+                        .run_until_idle()
+                        .and_then(|_| PlainValue::from_value(&val)),
+                    Err(ex) => Err(Error::from(ex)),
+                }
+            })
+        }))
+    }
+
+    fn submit(&self, run: Run) -> PoolEval {
+        let slot = Arc::new(Mutex::new(Slot { result: None, waker: None }));
+        let job = Job { run, slot: slot.clone() };
+        let sent = match self.sender {
+            Some(ref sender) => sender.send(job).is_ok(),
+            None => false,
+        };
+
+        if !sent {
+            complete(&slot, Err(Error::PoolClosed));
+        }
+
+        PoolEval { slot }
+    }
+}
+
+fn spawn_worker(queue: Queue, workers: Workers) {
+    let handle = {
+        let workers = workers.clone();
+
+        thread::spawn(move || run_worker(queue, workers))
+    };
+
+    workers.lock().unwrap().push(handle);
+}
+
+fn run_worker(queue: Queue, workers: Workers) {
+    let mut rt = Runtime::default();
+
+    loop {
+        let job = queue.lock().unwrap().recv();
+        let job = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let run = job.run;
+
+        match panic::catch_unwind(AssertUnwindSafe(|| run(&mut rt))) {
+            Ok(ret) => complete(&job.slot, ret),
+            Err(_) => {
+                // the runtime may be left half way through something, the
+                // next scripts get a new worker with a fresh one
+                complete(&job.slot, Err(Error::WorkerPanicked));
+                spawn_worker(queue, workers);
+                return;
+            }
+        }
+    }
+}
+
+fn complete(slot: &Mutex<Slot>, ret: Result<PlainValue, Error>) {
+    let mut slot = slot.lock().unwrap();
+
+    slot.result = Some(ret);
+
+    if let Some(waker) = slot.waker.take() {
+        waker.wake();
+    }
+}
+
+impl Drop for RuntimePool {
+    /// Waits for all queued scripts to finish.
+    fn drop(&mut self) {
+        drop(self.sender.take());
+
+        // workers replacing panicked ones are added before those exit
+        loop {
+            let worker = self.workers.lock().unwrap().pop();
+
+            match worker {
+                Some(worker) => {
+                    let _ = worker.join();
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Future returned by `RuntimePool::eval`.
+pub struct PoolEval {
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl Future for PoolEval {
+    type Output = Result<PlainValue, Error>;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap();
+
+        match slot.result.take() {
+            Some(ret) => Poll::Ready(ret),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{self, Poll, Wake};
+    use std::thread::{self, Thread};
+
+    use super::*;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(mut fut: F) -> F::Output {
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = task::Context::from_waker(&waker);
+        let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(ret) => return ret,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn parallel() {
+        let pool = RuntimePool::new(2);
+        let a = pool.eval("1 + 2");
+        let b = pool.eval("'a' + 'b'");
+        let c = pool.eval("throw new Error('nope')");
+
+        assert_eq!(block_on(a).unwrap(), PlainValue::Int(3));
+        assert_eq!(block_on(b).unwrap(), PlainValue::String("ab".to_string()));
+        assert!(block_on(c).is_err());
+    }
+
+    #[test]
+    fn panicking_job() {
+        let pool = RuntimePool::new(1);
+        let failed = pool.submit(Box::new(|_| panic!("job failed")));
+        let next = pool.eval("1 + 2");
+
+        assert_eq!(block_on(failed), Err(Error::WorkerPanicked));
+        assert_eq!(block_on(next).unwrap(), PlainValue::Int(3));
+    }
+}
@@ -0,0 +1,318 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::task::{self, Poll, Waker};
+
+use quickjs_sys as sys;
+
+use crate::object::Object;
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+
+/// A pending JavaScript promise together with the functions settling it.
+pub struct Promise {
+    value: Value,
+    resolve: Value,
+    reject: Value,
+}
+
+impl Promise {
+    /// The promise object handed to scripts.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    fn undefined(&self) -> Value {
+        Context { ptr: self.value.context.clone() }.undefined()
+    }
+
+    /// Fulfills the promise with `val`. Settling an already settled promise
+    /// has no effect.
+    pub fn resolve(&self, val: Value) {
+        let _ = self.resolve.call(self.undefined(), &[val]);
+        self.wake_waiters();
+    }
+
+    /// Rejects the promise with `reason`.
+    pub fn reject(&self, reason: Value) {
+        let _ = self.reject.call(self.undefined(), &[reason]);
+        self.wake_waiters();
+    }
+
+    /// Settling queues jobs `PromiseFuture`s need to run.
+    fn wake_waiters(&self) {
+        unsafe {
+            RuntimeState::from_context(self.value.context.as_ptr())
+                .wake_job_waiters();
+        }
+    }
+
+    /// Waits for all `values` to fulfill, like `Promise.all`. The output is
+    /// an array of their values, or the reason of the first rejection.
+    pub fn all(ctx: &Context, values: &[Value]) -> PromiseFuture {
+        combine(ctx, "all", values)
+    }
+
+    /// Waits for the first of `values` to settle, like `Promise.race`.
+    pub fn race(ctx: &Context, values: &[Value]) -> PromiseFuture {
+        combine(ctx, "race", values)
+    }
+
+    /// Waits for all `values` to settle, like `Promise.allSettled`. The
+    /// output is an array of `{ status, value }` or `{ status, reason }`
+    /// objects and never an error.
+    pub fn all_settled(ctx: &Context, values: &[Value]) -> PromiseFuture {
+        combine(ctx, "allSettled", values)
+    }
+}
+
+/// Calls the `Promise` static `name` with `values`.
+fn combine(ctx: &Context, name: &str, values: &[Value]) -> PromiseFuture {
+    let promise = (|| {
+        let ctor = ctx.global().get("Promise");
+        let ctor = ctor.map_err(|_| ctx.take_exception())?;
+        let func = Object { value: ctor.clone() }.get(name);
+        let func = func.map_err(|_| ctx.take_exception())?;
+        let array = ctx.array(values)?;
+        let ret = func.call(ctor, &[array.value]);
+
+        if ret.is_exception() {
+            Err(ctx.take_exception())
+        } else {
+            Ok(ret)
+        }
+    })();
+
+    PromiseFuture::new(ctx, promise)
+}
+
+type Settled = Rc<RefCell<Option<Result<Value, Value>>>>;
+
+/// Resolves to the outcome of a JavaScript promise.
+///
+/// Polling runs pending jobs, like `Context::run_until_idle`, until the
+/// promise is settled. Errors of unrelated jobs are passed on as the
+/// output. The future is woken when the host settles a `Promise`.
+pub struct PromiseFuture {
+    context: Context,
+    result: Settled,
+}
+
+impl PromiseFuture {
+    pub(crate) fn new(
+        ctx: &Context,
+        promise: Result<Value, Value>,
+    ) -> PromiseFuture {
+        let result: Settled = Rc::new(RefCell::new(None));
+        let subscribed = promise.and_then(|p| subscribe(ctx, &p, &result));
+
+        if let Err(err) = subscribed {
+            *result.borrow_mut() = Some(Err(err));
+        }
+
+        PromiseFuture { context: Context { ptr: ctx.ptr.clone() }, result }
+    }
+}
+
+/// Stores the outcome of `promise` in `result` once it's settled.
+fn subscribe(
+    ctx: &Context,
+    promise: &Value,
+    result: &Settled,
+) -> Result<(), Value> {
+    let handler = |fulfilled: bool| {
+        let result = result.clone();
+
+        ctx.internal_closure("settle", move |ctx, _, args| {
+            let val = args.get(0).cloned().unwrap_or_else(|| ctx.undefined());
+
+            *result.borrow_mut() =
+                Some(if fulfilled { Ok(val) } else { Err(val) });
+            ctx.undefined()
+        })
+    };
+    let on_fulfilled = handler(true)?;
+    let on_rejected = handler(false)?;
+    let then = Object { value: promise.clone() }.get("then");
+    let then = then.map_err(|_| ctx.take_exception())?;
+    let ret = then.call(promise.clone(), &[on_fulfilled, on_rejected]);
+
+    if ret.is_exception() {
+        Err(ctx.take_exception())
+    } else {
+        Ok(())
+    }
+}
+
+impl Future for PromiseFuture {
+    type Output = Result<Value, Value>;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Err(err) = this.context.run_until_idle() {
+            let err = this.context.error_from(&err).unwrap_or_else(|ex| ex);
+            return Poll::Ready(Err(err));
+        }
+
+        let settled = this.result.borrow_mut().take();
+
+        match settled {
+            Some(ret) => Poll::Ready(ret),
+            None => {
+                let state = unsafe {
+                    RuntimeState::from_context(this.context.ptr.as_ptr())
+                };
+
+                state.job_wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Tasks of `PromiseFuture`s waiting for jobs to run. Shared with the
+/// `MessageSender`s and `AbortHandle`s of the runtime, which may be used
+/// from other threads.
+#[derive(Default)]
+pub(crate) struct JobWakers(Mutex<Vec<Waker>>);
+
+impl JobWakers {
+    fn push(&self, waker: Waker) {
+        self.0.lock().unwrap().push(waker);
+    }
+
+    pub(crate) fn wake_all(&self) {
+        let wakers = mem::take(&mut *self.0.lock().unwrap());
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl RuntimeState {
+    pub(crate) fn wake_job_waiters(&self) {
+        self.job_wakers.wake_all();
+    }
+}
+
+impl Context {
+    /// Creates a new pending promise.
+    pub fn promise(&self) -> Result<Promise, Value> {
+        let mut funcs = unsafe { [sys::Helper_JS_NewUndefined(); 2] };
+        let value = unsafe {
+            Value {
+                value: sys::JS_NewPromiseCapability(
+                    self.ptr.as_ptr(),
+                    funcs.as_mut_ptr(),
+                ),
+                context: self.ptr.clone(),
+            }
+        };
+
+        if value.is_exception() {
+            return Err(self.take_exception());
+        }
+
+        let wrap = |v| Value { value: v, context: self.ptr.clone() };
+
+        Ok(Promise { value, resolve: wrap(funcs[0]), reject: wrap(funcs[1]) })
+    }
+}
+
+/// A waker that does nothing, for tests polling futures by hand.
+#[cfg(test)]
+pub(crate) fn noop_waker() -> Waker {
+    use std::ptr;
+    use std::task::{RawWaker, RawWakerVTable};
+
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+
+    static VTABLE: RawWakerVTable =
+        RawWakerVTable::new(clone, noop, noop, noop);
+
+    unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{self, Poll};
+
+    use super::{noop_waker, Promise};
+    use crate::array::Array;
+    use crate::Runtime;
+
+    #[test]
+    fn resolve() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let promise = ctx.promise().unwrap();
+        let then = ctx
+            .eval_script(
+                "(p) => p.then((v) => { globalThis.result = v * 2; })",
+                "<test>",
+            )
+            .unwrap();
+
+        then.call(ctx.undefined(), &[promise.value().clone()]);
+        promise.resolve(ctx.integer(21));
+        ctx.run_until_idle().unwrap();
+
+        assert_eq!(
+            ctx.eval_script("result", "<test>").unwrap().as_integer(),
+            Some(42)
+        );
+    }
+
+    #[test]
+    fn combinators() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let waker = noop_waker();
+        let mut cx = task::Context::from_waker(&waker);
+
+        let a = ctx.promise().unwrap();
+        let b = ctx.promise().unwrap();
+        let values = [a.value().clone(), b.value().clone()];
+        let mut all = Promise::all(&ctx, &values);
+        let mut race = Promise::race(&ctx, &values);
+        let mut settled = Promise::all_settled(&ctx, &values);
+
+        assert!(Pin::new(&mut all).poll(&mut cx).is_pending());
+
+        a.resolve(ctx.integer(1));
+
+        match Pin::new(&mut race).poll(&mut cx) {
+            Poll::Ready(Ok(val)) => assert_eq!(val.as_integer(), Some(1)),
+            _ => panic!("race not won"),
+        }
+        assert!(Pin::new(&mut all).poll(&mut cx).is_pending());
+
+        b.reject(ctx.string("nope"));
+
+        match Pin::new(&mut all).poll(&mut cx) {
+            Poll::Ready(Err(err)) => {
+                assert_eq!(err.as_string().unwrap(), "nope")
+            }
+            _ => panic!("all not rejected"),
+        }
+        match Pin::new(&mut settled).poll(&mut cx) {
+            Poll::Ready(Ok(val)) => {
+                assert_eq!(Array { value: val }.len().unwrap(), 2)
+            }
+            _ => panic!("allSettled not done"),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::{HashMap, HashSet};
+
+use quickjs_sys as sys;
+
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// QuickJS can't remove modules from its registry, so reloading loads the
+/// module under a new name, its name with this and a version appended.
+const VERSION_SEPARATOR: &str = "?v=";
+
+/// How many stale module instances a context may pile up. They are never
+/// freed, so past this reloading fails and a new context is needed.
+pub(crate) const MAX_STALE_MODULES: usize = 1024;
+
+/// Module names as the normalizer hands them out, and who imports whom.
+#[derive(Default)]
+pub(crate) struct ModuleVersions {
+    versions: HashMap<String, u32>,
+    /// Names of the modules importing a module.
+    importers: HashMap<String, HashSet<String>>,
+    /// Modules that came through the module loader, which can load them
+    /// again.
+    loaded: HashSet<String>,
+    /// Instances left behind by reloads.
+    stale: usize,
+}
+
+impl ModuleVersions {
+    /// The name to register `name` under.
+    pub(crate) fn versioned(&self, name: &str) -> String {
+        match self.versions.get(name) {
+            Some(version) => {
+                format!("{}{}{}", name, VERSION_SEPARATOR, version)
+            }
+            None => name.to_string(),
+        }
+    }
+
+    /// Records that `base` imports `name`. Only modules that came through
+    /// the loader count, others can't be reloaded.
+    pub(crate) fn record_import(&mut self, base: &str, name: &str) {
+        if !self.loaded.contains(base) {
+            return;
+        }
+        self.importers
+            .entry(name.to_string())
+            .or_default()
+            .insert(base.to_string());
+    }
+
+    pub(crate) fn record_load(&mut self, name: &str) {
+        self.loaded.insert(name.to_string());
+    }
+
+    pub(crate) fn is_loaded(&self, name: &str) -> bool {
+        self.loaded.contains(name)
+    }
+
+    /// Gives `name` and all modules importing it, directly or not, a new
+    /// version. Returns them, `name` first, or `None` if that would leave
+    /// more than `MAX_STALE_MODULES` instances behind. `name` must have
+    /// come through the loader.
+    pub(crate) fn invalidate(&mut self, name: &str) -> Option<Vec<String>> {
+        debug_assert!(self.is_loaded(name));
+
+        let mut affected = vec![name.to_string()];
+        let mut idx = 0;
+
+        while idx < affected.len() {
+            let mut importers = self
+                .importers
+                .get(&affected[idx])
+                .map(|set| set.iter().cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            importers.sort();
+            for importer in importers {
+                if !affected.contains(&importer) {
+                    affected.push(importer);
+                }
+            }
+            idx += 1;
+        }
+
+        if self.stale + affected.len() > MAX_STALE_MODULES {
+            return None;
+        }
+        self.stale += affected.len();
+
+        for module in affected.iter() {
+            *self.versions.entry(module.clone()).or_insert(0) += 1;
+        }
+
+        Some(affected)
+    }
+}
+
+/// Strips the version `ModuleVersions::versioned` added to a name.
+pub(crate) fn unversioned(name: &str) -> &str {
+    match name.rfind(VERSION_SEPARATOR) {
+        Some(idx)
+            if name[idx + VERSION_SEPARATOR.len()..]
+                .bytes()
+                .all(|b| b.is_ascii_digit()) =>
+        {
+            &name[..idx]
+        }
+        _ => name,
+    }
+}
+
+impl Context {
+    /// Loads the module `name`, which came through the module loader, anew
+    /// and evaluates it. Modules importing it, directly or not, are
+    /// reloaded too, so they see the new exports. Returns the names of the
+    /// modules reloaded.
+    ///
+    /// QuickJS can't unload modules, so old instances stay in memory until
+    /// the context is dropped. Fails once `MAX_STALE_MODULES` piled up.
+    pub fn reload_module(&self, name: &str) -> Result<Vec<String>, Value> {
+        let reloaded = self.invalidate_module(name)?;
+        let source = reloaded
+            .iter()
+            .map(|m| {
+                format!(
+                    "import \"{}\";\n",
+                    m.replace('\\', "\\\\").replace('"', "\\\"")
+                )
+            })
+            .collect::<String>();
+
+        self.eval_flags(
+            source.as_bytes(),
+            "<reload>",
+            sys::JS_EVAL_TYPE_MODULE as i32,
+        )?;
+        Ok(reloaded)
+    }
+
+    /// Versions `name` and the modules importing it anew, see
+    /// `Module::invalidate`.
+    pub(crate) fn invalidate_module(
+        &self,
+        name: &str,
+    ) -> Result<Vec<String>, Value> {
+        let mut modules = self.ptr.state().modules.borrow_mut();
+        let msg: &[u8] = if !modules.is_loaded(name) {
+            b"module wasn't loaded by the module loader\0"
+        } else if let Some(affected) = modules.invalidate(name) {
+            return Ok(affected);
+        } else {
+            b"too many reloaded modules, use a new context\0"
+        };
+
+        drop(modules);
+        unsafe {
+            sys::JS_ThrowReferenceError(
+                self.ptr.as_ptr(),
+                msg.as_ptr() as *const i8,
+            );
+        }
+        Err(self.take_exception())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::*;
+    use crate::{Chroot, Runtime};
+
+    #[test]
+    fn versions() {
+        let mut modules = ModuleVersions::default();
+
+        for name in ["main.js", "lib/a.js", "lib/b.js"].iter() {
+            modules.record_load(name);
+        }
+        modules.record_import("<eval>", "main.js");
+        modules.record_import("main.js", "lib/a.js");
+        modules.record_import("lib/a.js", "lib/b.js");
+        assert_eq!(modules.versioned("lib/b.js"), "lib/b.js");
+        assert_eq!(
+            modules.invalidate("lib/b.js").unwrap(),
+            ["lib/b.js", "lib/a.js", "main.js"]
+        );
+        assert_eq!(modules.versioned("lib/a.js"), "lib/a.js?v=1");
+        assert_eq!(unversioned("lib/a.js?v=1"), "lib/a.js");
+        assert_eq!(unversioned("a.js?v=x"), "a.js?v=x");
+    }
+
+    #[test]
+    fn reload() {
+        let root = env::temp_dir()
+            .join(format!("quickjs-rs-reload-{}", process::id()));
+
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("dep.js"), "export const n = 1;").unwrap();
+        fs::write(
+            root.join("main.js"),
+            "import { n } from './dep.js';
+            globalThis.seen = (globalThis.seen || []).concat(n);",
+        )
+        .unwrap();
+
+        let mut rt = Runtime::default();
+        let mut ctx = rt
+            .context_builder()
+            .fs_sandbox(Chroot::new(&root, false))
+            .build()
+            .unwrap();
+
+        ctx.eval("import './main.js';", "<test>", false, false).unwrap();
+        fs::write(root.join("dep.js"), "export const n = 2;").unwrap();
+
+        assert_eq!(ctx.reload_module("dep.js").unwrap(), ["dep.js", "main.js"]);
+        assert_eq!(
+            ctx.eval_script("seen.join()", "<test>")
+                .unwrap()
+                .as_string()
+                .unwrap(),
+            "1,2"
+        );
+
+        fs::write(root.join("dep.js"), "export const = ;").unwrap();
+        assert!(ctx.reload_module("dep.js").is_err());
+        assert!(ctx.reload_module("<test>").is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use crate::error::Error;
+use crate::runtime::Context;
+
+impl Context {
+    /// Replaces the context with a new one built the same way, running the
+    /// `ContextBuilder::setup` functions again. Nothing scripts did is left:
+    /// globals, including `let`, `const` and `class` declarations, patched
+    /// built-ins and loaded modules are gone. So is host state of the
+    /// context, like services and message channels. Fuel left is kept.
+    ///
+    /// Values of the old context stay usable, it's freed once the last of
+    /// them is dropped.
+    pub fn reset(&mut self) -> Result<(), Error> {
+        let fuel = self.fuel();
+        let mut ctx = self.rebuild()?;
+
+        ctx.set_fuel(fuel);
+        *self = ctx;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use crate::{Chroot, Runtime};
+
+    #[test]
+    fn reset() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+
+        ctx.eval_script(
+            "globalThis.added = 1; Math = null; delete globalThis.JSON;
+            var v = 1; let l = 2; class C {}",
+            "<test>",
+        )
+        .unwrap();
+        ctx.reset().unwrap();
+
+        let typeof_ =
+            |src| ctx.eval_script(src, "<test>").unwrap().as_string().unwrap();
+
+        assert_eq!(typeof_("typeof added"), "undefined");
+        assert_eq!(typeof_("typeof Math.max"), "function");
+        assert_eq!(typeof_("typeof JSON.stringify"), "function");
+        assert_eq!(
+            typeof_("typeof v + typeof l + typeof C"),
+            "undefinedundefinedundefined"
+        );
+        assert!(ctx.eval_script("let l = 3; l", "<test>").is_ok());
+    }
+
+    #[test]
+    fn setup() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt
+            .context_builder()
+            .setup(|ctx| {
+                ctx.eval_script("globalThis.keep = 1;", "<setup>")?;
+                Ok(())
+            })
+            .build()
+            .unwrap();
+
+        ctx.eval("globalThis.drop = 2;", "<test>", false, false).unwrap();
+        ctx.reset().unwrap();
+
+        let val = ctx.eval_script("typeof keep + typeof drop", "<test>");
+
+        assert_eq!(val.unwrap().as_string().unwrap(), "numberundefined");
+    }
+
+    #[test]
+    fn modules() {
+        let root = env::temp_dir()
+            .join(format!("quickjs-reset-test-{}", process::id()));
+
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("counter.js"), "export const n = 1;").unwrap();
+
+        let mut rt = Runtime::default();
+        let mut ctx = rt
+            .context_builder()
+            .fs_sandbox(Chroot::new(&root, true))
+            .build()
+            .unwrap();
+        let import = r#"
+            import { n } from "/counter.js";
+            globalThis.n = n;
+            "#;
+
+        ctx.eval(import, "main.js", false, false).unwrap();
+        fs::write(root.join("counter.js"), "export const n = 2;").unwrap();
+        ctx.reset().unwrap();
+        ctx.eval(import, "main.js", false, false).unwrap();
+
+        let n = ctx.eval_script("n", "<test>").unwrap().as_integer();
+
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(n, Some(2));
+    }
+}
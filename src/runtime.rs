@@ -1,23 +1,260 @@
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::mem;
+use std::os::raw::c_void;
 use std::ptr;
 use std::rc::Rc;
+#[cfg(feature = "async")]
+use std::rc::Weak;
 use std::str;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use quickjs_sys as sys;
 
-use crate::Value;
+use crate::abort::Aborts;
+use crate::allocator::{self, Arena, MemoryAllocator};
+use crate::audit::AuditEvent;
+use crate::channel::Channel;
+#[cfg(feature = "async")]
+use crate::executor::{self, Executor, PendingPromise};
+use crate::gc::{self, GcEvent};
+use crate::interrupt::InterruptHandle;
+use crate::microtask::MicrotaskPolicy;
+use crate::native;
+use crate::permissions::Permissions;
+use crate::promise::JobWakers;
+use crate::reload::ModuleVersions;
+use crate::sandbox::{self, FsSandbox};
+use crate::stats;
+use crate::timers::{Scheduler, Timers};
+use crate::trace;
+use crate::weakref::Finalizers;
+use crate::{Error, ExceptionDetails, Value};
+
+struct Rejection {
+    /// Id of the context the promise was rejected in.
+    context: usize,
+    promise: sys::JSValue,
+    reason: sys::JSValue,
+}
+
+impl Rejection {
+    unsafe fn free(self, rt: *mut sys::JSRuntime) {
+        sys::Helper_JS_FreeValueRT(rt, self.promise);
+        sys::Helper_JS_FreeValueRT(rt, self.reason);
+    }
+}
+
+/// Host side state of a runtime. Reachable from C callbacks through the
+/// runtime opaque pointer.
+#[derive(Default)]
+pub(crate) struct RuntimeState {
+    track_rejections: Cell<bool>,
+    rejections: RefCell<Vec<Rejection>>,
+    pub(crate) record_throw_location: Cell<bool>,
+    /// Fuel left per context id, see `Context::set_fuel`.
+    fuel: RefCell<HashMap<usize, u64>>,
+    pub(crate) interrupts: Cell<u64>,
+    /// Highest value of `allocated` while tracking is enabled.
+    pub(crate) peak_memory: Cell<Option<u64>>,
+    /// Bytes the engine ever allocated, not counting frees.
+    pub(crate) allocated_total: Cell<u64>,
+    /// Calls of host functions by scripts.
+    pub(crate) host_calls: Cell<u64>,
+    /// Id of the context new allocations are charged to, 0 for the runtime.
+    pub(crate) active_context: Cell<usize>,
+    pub(crate) context_memory: RefCell<HashMap<usize, usize>>,
+    /// Bytes allocated by the engine, without bookkeeping overhead.
+    pub(crate) allocated: Cell<usize>,
+    pub(crate) gc_hook: RefCell<Option<Box<dyn FnMut(GcEvent)>>>,
+    /// Object whose class notices collections, see `gc::new_marker`.
+    gc_marker: Cell<Option<sys::JSValue>>,
+    /// When the collection in progress started, and the heap size then.
+    pub(crate) gc_start: Cell<Option<(Instant, usize)>>,
+    /// Time spent collecting garbage, in total.
+    pub(crate) gc_time: Cell<Duration>,
+    /// Bytes last added to the memory gauge, see `stats::memory`.
+    reported_memory: Cell<usize>,
+    /// Collections not reported to the GC hook yet.
+    pub(crate) gc_events: RefCell<Vec<GcEvent>>,
+    pub(crate) deadline: Cell<Option<Instant>>,
+    /// End and length of the slice of a running `eval_cooperative`.
+    pub(crate) slice: Cell<Option<(Instant, Duration)>>,
+    /// Set when the running script was aborted because of `deadline`.
+    pub(crate) timed_out: Cell<bool>,
+    pub(crate) scheduler: RefCell<Option<Rc<dyn Scheduler>>>,
+    pub(crate) audit_hook: RefCell<Option<Box<dyn FnMut(AuditEvent)>>>,
+    /// Slots of `ArrayBuffer`s created from `Bytes`, by data address.
+    #[cfg(feature = "bytes")]
+    pub(crate) shared_buffers: RefCell<HashMap<usize, usize>>,
+    /// Arenas of contexts created with `ContextBuilder::arena`, by id.
+    pub(crate) arenas: RefCell<HashMap<usize, Arena>>,
+    /// Bytes of arena chunks not taken by blocks.
+    pub(crate) arena_slack: Cell<usize>,
+    /// Where memory comes from, the global allocator if `None`.
+    pub(crate) allocator: Option<Box<dyn MemoryAllocator>>,
+    /// Set by `InterruptHandle::interrupt`.
+    pub(crate) interrupt: Arc<AtomicBool>,
+    pub(crate) uncaught_handler: RefCell<Option<Box<dyn FnMut(&Error)>>>,
+    /// Set while `AfterCall` runs jobs, so calls made by them don't.
+    pub(crate) draining_microtasks: Cell<bool>,
+    /// Code by filename, if enabled with `Runtime::enable_source_registry`.
+    pub(crate) sources: RefCell<Option<HashMap<String, Rc<str>>>>,
+    pub(crate) job_wakers: Arc<JobWakers>,
+    #[cfg(feature = "async")]
+    pub(crate) executor: RefCell<Option<Rc<dyn Executor>>>,
+}
+
+impl RuntimeState {
+    pub(crate) unsafe fn from_context<'a>(
+        ctx: *mut sys::JSContext,
+    ) -> &'a RuntimeState {
+        let rt = sys::JS_GetRuntime(ctx);
+        &*(sys::JS_GetRuntimeOpaque(rt) as *const RuntimeState)
+    }
+
+    fn should_interrupt(&self) -> bool {
+        self.interrupts.set(self.interrupts.get() + 1);
+        self.report_collections();
+
+        if self.interrupt.swap(false, Ordering::SeqCst) {
+            return true;
+        }
+
+        if let Some((end, len)) = self.slice.get() {
+            let now = Instant::now();
+
+            // the executor can't get control back from inside the engine,
+            // other threads can at least
+            if now >= end {
+                thread::yield_now();
+                self.slice.set(Some((now + len, len)));
+            }
+        }
+
+        if let Some(deadline) = self.deadline.get() {
+            if Instant::now() >= deadline {
+                self.timed_out.set(true);
+                return true;
+            }
+        }
+
+        let active = self.active_context.get();
+
+        match self.fuel.borrow_mut().get_mut(&active) {
+            Some(0) => return true,
+            Some(n) => *n -= 1,
+            None => {}
+        }
+
+        false
+    }
+
+    fn report_memory(&self) {
+        let allocated = self.allocated.get();
+
+        stats::memory(self.reported_memory.replace(allocated), allocated);
+    }
+
+    fn clear(&self, rt: *mut sys::JSRuntime) {
+        for rej in self.rejections.borrow_mut().drain(..) {
+            unsafe { rej.free(rt) };
+        }
+    }
+
+    /// Removes the rejections tracked for the context `id`.
+    fn take_rejections(&self, id: usize) -> Vec<Rejection> {
+        let mut rejections = self.rejections.borrow_mut();
+        let (taken, kept) =
+            rejections.drain(..).partition(|rej| rej.context == id);
+
+        *rejections = kept;
+        taken
+    }
+}
+
+extern "C" fn promise_rejection_tracker(
+    ctx: *mut sys::JSContext,
+    promise: sys::JSValue,
+    reason: sys::JSValue,
+    is_handled: i32,
+    opaque: *mut c_void,
+) {
+    let state = unsafe { &*(opaque as *const RuntimeState) };
+
+    if !state.track_rejections.get() {
+        return;
+    }
+
+    unsafe {
+        let rt = sys::JS_GetRuntime(ctx);
+        let mut rejections = state.rejections.borrow_mut();
+
+        if is_handled != 0 {
+            // a handler was attached late, the rejection is observed after all
+            if let Some(pos) =
+                rejections.iter().position(|r| r.promise.u.ptr == promise.u.ptr)
+            {
+                rejections.remove(pos).free(rt);
+            }
+        } else {
+            let opaque = sys::JS_GetContextOpaque(ctx) as *const ContextState;
+
+            rejections.push(Rejection {
+                context: if opaque.is_null() { 0 } else { (*opaque).id },
+                promise: sys::Helper_JS_DupValueRT(rt, promise),
+                reason: sys::Helper_JS_DupValueRT(rt, reason),
+            });
+        }
+    }
+}
+
+pub(crate) fn memory_usage(rt: *mut sys::JSRuntime) -> sys::JSMemoryUsage {
+    unsafe {
+        let mut usage = mem::zeroed();
+
+        sys::JS_ComputeMemoryUsage(rt, &mut usage);
+        usage
+    }
+}
+
+extern "C" fn interrupt_handler(
+    _rt: *mut sys::JSRuntime,
+    opaque: *mut c_void,
+) -> i32 {
+    let state = unsafe { &*(opaque as *const RuntimeState) };
+
+    if state.should_interrupt() {
+        1
+    } else {
+        0
+    }
+}
 
 struct RuntimePtr {
     runtime: *mut sys::JSRuntime,
+    state: Box<RuntimeState>,
 }
 
 impl Drop for RuntimePtr {
     fn drop(&mut self) {
         if !self.runtime.is_null() {
+            self.state.clear(self.runtime);
+
             unsafe {
+                if let Some(marker) = self.state.gc_marker.take() {
+                    sys::Helper_JS_FreeValueRT(self.runtime, marker);
+                }
                 sys::JS_FreeRuntime(self.runtime as *mut _);
                 self.runtime = ptr::null::<sys::JSRuntime>() as *mut _;
             }
+            self.state.release_arenas();
+            stats::memory(self.state.reported_memory.get(), 0);
         }
     }
 }
@@ -27,46 +264,459 @@ pub struct Runtime {
 }
 
 impl Default for Runtime {
+    /// Like `new`, but panics if the runtime can't be created.
     fn default() -> Self {
+        Runtime::new().expect("failed to create runtime")
+    }
+}
+
+impl Runtime {
+    /// Creates a new runtime. Fails if the engine can't allocate its
+    /// initial state.
+    pub fn new() -> Result<Runtime, Error> {
+        Runtime::create(None)
+    }
+
+    /// Creates a new runtime allocating all of its memory from `allocator`.
+    pub fn with_allocator<A>(allocator: A) -> Result<Runtime, Error>
+    where
+        A: MemoryAllocator + 'static,
+    {
+        Runtime::create(Some(Box::new(allocator)))
+    }
+
+    fn create(
+        allocator: Option<Box<dyn MemoryAllocator>>,
+    ) -> Result<Runtime, Error> {
         unsafe {
-            let rt = sys::JS_NewRuntime();
+            let state =
+                Box::new(RuntimeState { allocator, ..Default::default() });
+            let opaque = &*state as *const RuntimeState as *mut c_void;
+            let rt = sys::JS_NewRuntime2(&allocator::MALLOC_FUNCTIONS, opaque);
+
+            if rt.is_null() {
+                return Err(Error::OutOfMemory);
+            }
+
+            // frees the runtime on the error paths below
+            let ptr = Rc::new(RuntimePtr { runtime: rt, state });
+
+            sys::JS_SetRuntimeOpaque(rt, opaque);
+            sys::JS_SetHostPromiseRejectionTracker(
+                rt,
+                Some(promise_rejection_tracker),
+                opaque,
+            );
+            sys::JS_SetInterruptHandler(rt, Some(interrupt_handler), opaque);
+            sys::JS_SetModuleLoaderFunc(
+                rt,
+                Some(sandbox::normalize_module),
+                Some(sandbox::load_module),
+                ptr::null_mut(),
+            );
+
+            if !native::register_class(rt) {
+                return Err(Error::OutOfMemory);
+            }
+
+            match gc::new_marker(rt) {
+                Some(marker) => ptr.state.gc_marker.set(Some(marker)),
+                None => return Err(Error::OutOfMemory),
+            }
+
+            Ok(Runtime { ptr })
+        }
+    }
+
+    /// Record where errors thrown through `Context::throw` originate in the
+    /// Rust code. The `file:line:column` is stored in the non-enumerable
+    /// `rustLocation` property of the thrown object.
+    pub fn record_throw_location(&mut self, enable: bool) {
+        self.ptr.state.record_throw_location.set(enable);
+    }
+
+    /// Collect promises that are rejected without a handler. If enabled,
+    /// `Context::run_until_idle` reports the first of them rejected in that
+    /// context as `Error::UnhandledRejection` instead of succeeding
+    /// silently.
+    pub fn track_unhandled_rejections(&mut self, enable: bool) {
+        self.ptr.state.track_rejections.set(enable);
 
-            assert!(!rt.is_null());
-            Runtime { ptr: Rc::new(RuntimePtr { runtime: rt }) }
+        if !enable {
+            self.ptr.state.clear(self.ptr.runtime);
+        }
+    }
+
+    /// Lets `scheduler` run script timers on the host's event loop. New
+    /// contexts get `setTimeout` and `clearTimeout` globals delegating to it
+    /// instead of relying on the `os` module's poll loop.
+    pub fn set_scheduler<S: Scheduler + 'static>(&mut self, scheduler: S) {
+        *self.ptr.state.scheduler.borrow_mut() = Some(Rc::new(scheduler));
+    }
+
+    /// Calls `hook` for every host function call and every operation of the
+    /// `std` and `os` modules scripts make. Only contexts built after
+    /// setting the hook report `std` and `os` operations. Globals the crate
+    /// installs itself, like `setTimeout` or `atob`, aren't reported.
+    pub fn set_audit_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(AuditEvent) + 'static,
+    {
+        *self.ptr.state.audit_hook.borrow_mut() = Some(Box::new(hook));
+    }
+
+    /// Calls `hook` with a `GcEvent::Start` and a `GcEvent::End` for every
+    /// garbage collection.
+    ///
+    /// The hook can't run while the engine is collecting, so collections
+    /// QuickJS triggers while allocating are reported afterwards: the next
+    /// time the engine polls for interrupts or control returns to the host.
+    pub fn set_gc_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(GcEvent) + 'static,
+    {
+        *self.ptr.state.gc_hook.borrow_mut() = Some(Box::new(hook));
+    }
+
+    /// Runs a full garbage collection.
+    pub fn run_gc(&mut self) {
+        self.ptr.state.collect_garbage(self.ptr.runtime);
+    }
+
+    /// Keep the code of scripts and modules by filename, so errors can show
+    /// the offending line, see `ExceptionDetails::render`. Only code run
+    /// after enabling the registry is kept, disabling it forgets all.
+    pub fn enable_source_registry(&mut self, enable: bool) {
+        self.ptr.state.enable_sources(enable);
+    }
+
+    /// Code last evaluated or loaded as `filename`, if the source registry
+    /// is enabled.
+    pub fn source(&self, filename: &str) -> Option<Rc<str>> {
+        self.ptr.state.source(filename)
+    }
+
+    /// Calls `handler` with errors nobody waits for: exceptions thrown by
+    /// jobs, timers and message callbacks, and unhandled rejections if
+    /// tracked. Once set, `Context::run_until_idle` and `Timer::fire` keep
+    /// going after such errors instead of returning them.
+    pub fn set_uncaught_exception_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(&Error) + 'static,
+    {
+        *self.ptr.state.uncaught_handler.borrow_mut() = Some(Box::new(handler));
+    }
+
+    /// Sets the executor futures of async host functions are spawned on,
+    /// see `Context::async_closure`.
+    #[cfg(feature = "async")]
+    pub fn set_executor<E: Executor + 'static>(&mut self, executor: E) {
+        *self.ptr.state.executor.borrow_mut() = Some(Rc::new(executor));
+    }
+
+    /// Returns a handle that aborts scripts of this runtime from other
+    /// threads.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle { flag: self.ptr.state.interrupt.clone() }
+    }
+
+    pub fn context(&mut self) -> Result<Context, Error> {
+        self.context_builder().build()
+    }
+
+    pub fn context_builder(&mut self) -> ContextBuilder<'_> {
+        ContextBuilder {
+            runtime: self,
+            options: ContextOptions {
+                helpers: true,
+                std: true,
+                os: true,
+                fs_sandbox: None,
+                permissions: None,
+                arena: None,
+                strict: false,
+                require: false,
+                microtask_policy: MicrotaskPolicy::default(),
+                setup: Vec::new(),
+            },
         }
     }
 }
 
-impl Runtime {
-    pub fn context(&mut self) -> Context {
+type Setup = Rc<dyn Fn(&mut Context) -> Result<(), Error>>;
+
+/// Configures which host facilities a new context gets.
+pub struct ContextBuilder<'a> {
+    runtime: &'a mut Runtime,
+    options: ContextOptions,
+}
+
+/// What a `ContextBuilder` was told, kept for `Context::reset` to build the
+/// context anew.
+#[derive(Clone)]
+pub(crate) struct ContextOptions {
+    helpers: bool,
+    std: bool,
+    os: bool,
+    fs_sandbox: Option<Rc<dyn FsSandbox>>,
+    permissions: Option<Rc<Permissions>>,
+    arena: Option<usize>,
+    strict: bool,
+    require: bool,
+    microtask_policy: MicrotaskPolicy,
+    setup: Vec<Setup>,
+}
+
+impl<'a> ContextBuilder<'a> {
+    /// Install `print`, `console.log` and `scriptArgs` as globals.
+    pub fn helpers(mut self, enable: bool) -> Self {
+        self.options.helpers = enable;
+        self
+    }
+
+    /// Make the `std` module available for import.
+    pub fn std_module(mut self, enable: bool) -> Self {
+        self.options.std = enable;
+        self
+    }
+
+    /// Make the `os` module available for import.
+    pub fn os_module(mut self, enable: bool) -> Self {
+        self.options.os = enable;
+        self
+    }
+
+    /// Route the file APIs of the `std` and `os` modules and imports of
+    /// file modules through `sandbox`. Without a sandbox, file modules can't
+    /// be imported.
+    pub fn fs_sandbox<S: FsSandbox + 'static>(mut self, sandbox: S) -> Self {
+        self.options.fs_sandbox = Some(Rc::new(sandbox));
+        self
+    }
+
+    /// Restrict what scripts can do through the `std` and `os` modules to
+    /// `perms`. Replaces a sandbox set with `fs_sandbox`.
+    pub fn permissions(mut self, perms: Permissions) -> Self {
+        let perms = Rc::new(perms);
+
+        self.options.fs_sandbox = Some(perms.clone());
+        self.options.permissions = Some(perms);
+        self
+    }
+
+    /// Evaluate all code the host passes in strict mode, whether or not
+    /// callers ask for it. Modules, including those the module loader
+    /// loads, are strict anyway.
+    ///
+    /// Code scripts compile themselves is out of reach: `new Function` and
+    /// indirect `eval` are only strict if their source says so.
+    pub fn strict(mut self, enable: bool) -> Self {
+        self.options.strict = enable;
+        self
+    }
+
+    /// Sets when jobs run after calls into the context. With
+    /// `MicrotaskPolicy::AfterCall`, errors thrown by jobs go to the
+    /// uncaught exception handler if there is one and are returned by the
+    /// context's next `Context::run_until_idle` otherwise.
+    pub fn microtask_policy(mut self, policy: MicrotaskPolicy) -> Self {
+        self.options.microtask_policy = policy;
+        self
+    }
+
+    /// Install a CommonJS `require`. Modules are loaded from files through
+    /// the sandbox set with `fs_sandbox` or `permissions`, so without one
+    /// nothing can be required.
+    pub fn require(mut self, enable: bool) -> Self {
+        self.options.require = enable;
+        self
+    }
+
+    /// Runs `f` on the context once it's set up, and again whenever
+    /// `Context::reset` builds it anew. Globals `f` defines are part of the
+    /// state `reset` goes back to.
+    pub fn setup<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&mut Context) -> Result<(), Error> + 'static,
+    {
+        self.options.setup.push(Rc::new(f));
+        self
+    }
+
+    /// Allocate the context's memory from a bump arena, in chunks of
+    /// `chunk_size` bytes. Freeing is close to free and the arena is
+    /// released as a whole when the context is dropped, at the cost of
+    /// memory freed by scripts only being reused once all of it is. Meant
+    /// for contexts that only live for a request or so.
+    pub fn arena(mut self, chunk_size: usize) -> Self {
+        self.options.arena = Some(chunk_size);
+        self
+    }
+
+    pub fn build(self) -> Result<Context, Error> {
+        self.options.build(&self.runtime.ptr)
+    }
+}
+
+impl ContextOptions {
+    fn build(self, runtime: &Rc<RuntimePtr>) -> Result<Context, Error> {
+        let id = NEXT_CONTEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let rt_state = &*runtime.state;
+
         unsafe {
-            let ctx = sys::JS_NewContext(self.ptr.runtime as *mut _);
-            assert!(!ctx.is_null());
+            // setting up the context is charged to it, so it comes from the
+            // arena too
+            let enter = self.arena.map(|chunk_size| {
+                rt_state.arenas.borrow_mut().insert(id, Arena::new(chunk_size));
+                Enter {
+                    state: rt_state,
+                    prev: rt_state.active_context.replace(id),
+                }
+            });
+            let ctx = sys::JS_NewContext(runtime.runtime as *mut _);
+
+            if ctx.is_null() {
+                drop(enter);
+                rt_state.close_arena(id);
+                return Err(Error::OutOfMemory);
+            }
+
+            #[cfg(feature = "operators")]
+            sys::JS_AddIntrinsicOperators(ctx);
+
+            if self.helpers {
+                sys::js_std_add_helpers(
+                    ctx,
+                    1,
+                    [b"<none>\n".as_ptr() as *mut i8].as_mut_ptr(),
+                );
+            }
+
+            /* system modules, behind wrappers if sandboxed or audited */
+            let wrapped = self.fs_sandbox.is_some()
+                || runtime.state.audit_hook.borrow().is_some();
+
+            if self.std {
+                let name: &[u8] =
+                    if wrapped { sandbox::NATIVE_STD } else { b"std\0" };
+                sys::js_init_module_std(ctx, name.as_ptr() as *const i8);
+            }
+            if self.os {
+                let name: &[u8] =
+                    if wrapped { sandbox::NATIVE_OS } else { b"os\0" };
+                sys::js_init_module_os(ctx, name.as_ptr() as *const i8);
+            }
+            drop(enter);
 
-            sys::js_std_add_helpers(
+            let state = Rc::new(ContextState {
+                id,
+                options: self.clone(),
+                services: RefCell::new(HashMap::new()),
+                channel: RefCell::new(None),
+                timers: Rc::new(Timers::default()),
+                fs_sandbox: self.fs_sandbox,
+                permissions: self.permissions,
+                std_wrapped: self.std && wrapped,
+                os_wrapped: self.os && wrapped,
+                strict: self.strict,
+                aborts: RefCell::new(Aborts::default()),
+                import_log: RefCell::new(None),
+                binary_ops: RefCell::new(None),
+                modules: RefCell::new(ModuleVersions::default()),
+                finalizers: RefCell::new(Finalizers::default()),
+                microtask_policy: Cell::new(MicrotaskPolicy::Manual),
+                microtask_errors: RefCell::new(Vec::new()),
+                #[cfg(feature = "async")]
+                pending_promises: RefCell::new(Vec::new()),
+            });
+
+            sys::JS_SetContextOpaque(
                 ctx,
-                1,
-                [b"<none>\n".as_ptr() as *mut i8].as_mut_ptr(),
+                &*state as *const ContextState as *mut c_void,
             );
 
-            /* system modules */
-            sys::js_init_module_std(ctx, b"std\0".as_ptr() as *const i8);
-            sys::js_init_module_os(ctx, b"os\0".as_ptr() as *const i8);
-
-            Context {
+            let mut ctx = Context {
                 ptr: ContextPtr::Owned(Rc::new(ContextPtrOwned {
                     context: ctx,
-                    runtime: self.ptr.clone(),
+                    runtime: runtime.clone(),
+                    state,
                 })),
+            };
+
+            if wrapped {
+                ctx.install_sandbox()?;
+            }
+            if self.require {
+                ctx.install_require()?;
             }
+            if let Some(scheduler) = ctx.scheduler() {
+                ctx.install_timers(scheduler)?;
+            }
+            ctx.install_base64()?;
+            ctx.install_microtasks()?;
+            ctx.install_abort()?;
+            ctx.install_weakrefs()?;
+            #[cfg(feature = "intl")]
+            ctx.install_intl()?;
+            #[cfg(feature = "url")]
+            ctx.install_url()?;
+
+            for setup in self.setup.iter() {
+                setup(&mut ctx)?;
+            }
+            // not before, jobs queued while setting up run with the first
+            // call
+            ctx.ptr.state().microtask_policy.set(self.microtask_policy);
+            Ok(ctx)
         }
     }
 }
 
+static NEXT_CONTEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Host side state of a context. Reachable from C callbacks through the
+/// context opaque pointer.
+pub(crate) struct ContextState {
+    pub(crate) id: usize,
+    /// How the context was built, for `Context::reset`.
+    pub(crate) options: ContextOptions,
+    /// Host services registered with `Context::provide`.
+    pub(crate) services: RefCell<HashMap<TypeId, Rc<dyn Any>>>,
+    /// Set up by `Context::message_channel`.
+    pub(crate) channel: RefCell<Option<Channel>>,
+    pub(crate) timers: Rc<Timers>,
+    pub(crate) fs_sandbox: Option<Rc<dyn FsSandbox>>,
+    pub(crate) permissions: Option<Rc<Permissions>>,
+    /// Whether `std` and `os` resolve to the sandboxing wrapper modules.
+    pub(crate) std_wrapped: bool,
+    pub(crate) os_wrapped: bool,
+    /// Set by `ContextBuilder::strict`.
+    pub(crate) strict: bool,
+    /// Backs `AbortSignal`s.
+    pub(crate) aborts: RefCell<Aborts>,
+    /// Pairs of importing and imported module names the normalizer sees
+    /// while `Context::compile_module` resolves imports.
+    pub(crate) import_log: RefCell<Option<Vec<(String, String)>>>,
+    /// Applies operators for the `std::ops` impls of `Value`, compiled on
+    /// first use.
+    pub(crate) binary_ops: RefCell<Option<Value>>,
+    /// Versions of reloaded modules and the import graph.
+    pub(crate) modules: RefCell<ModuleVersions>,
+    /// Backs `WeakRef`, `FinalizationRegistry` and `Context::on_collect`.
+    pub(crate) finalizers: RefCell<Finalizers>,
+    pub(crate) microtask_policy: Cell<MicrotaskPolicy>,
+    /// Job errors nobody handled yet, see `Context::take_microtask_error`.
+    pub(crate) microtask_errors: RefCell<Vec<Error>>,
+    /// Promises of async host functions that are still running.
+    #[cfg(feature = "async")]
+    pub(crate) pending_promises: RefCell<Vec<Weak<PendingPromise>>>,
+}
+
 #[derive(Clone)]
 pub struct ContextPtrOwned {
     pub(crate) context: *mut sys::JSContext,
     runtime: Rc<RuntimePtr>,
+    state: Rc<ContextState>,
 }
 
 #[derive(Clone)]
@@ -78,10 +728,31 @@ pub(crate) enum ContextPtr {
 impl Drop for ContextPtrOwned {
     fn drop(&mut self) {
         if !self.context.is_null() {
+            // values kept in the state don't keep the context alive, they
+            // have to go while it's still there
+            self.state.services.borrow_mut().clear();
+            self.state.channel.borrow_mut().take();
+            self.state.timers.callbacks.borrow_mut().clear();
+            self.state.aborts.take();
+            self.state.binary_ops.take();
+            self.state.finalizers.take();
+            #[cfg(feature = "async")]
+            executor::cancel_pending(&self.state.pending_promises);
+
             unsafe {
+                for rej in self.runtime.state.take_rejections(self.state.id) {
+                    rej.free(self.runtime.runtime);
+                }
                 sys::JS_FreeContext(self.context);
             }
             self.context = ptr::null::<sys::JSContext>() as *mut _;
+            self.runtime
+                .state
+                .context_memory
+                .borrow_mut()
+                .remove(&self.state.id);
+            self.runtime.state.fuel.borrow_mut().remove(&self.state.id);
+            self.runtime.state.close_arena(self.state.id);
         }
     }
 }
@@ -93,6 +764,50 @@ impl ContextPtr {
             &ContextPtr::Borrowed(ptr) => ptr,
         }
     }
+
+    pub(crate) fn state(&self) -> &ContextState {
+        unsafe {
+            &*(sys::JS_GetContextOpaque(self.as_ptr()) as *const ContextState)
+        }
+    }
+
+    /// Charges engine allocations to this context until the guard is
+    /// dropped.
+    pub(crate) fn enter(&self) -> Enter<'_> {
+        let state = unsafe { RuntimeState::from_context(self.as_ptr()) };
+        let prev = state.active_context.replace(self.state().id);
+
+        // interrupts are meant for what runs when they're raised
+        if prev == 0 {
+            state.interrupt.store(false, Ordering::SeqCst);
+        }
+
+        Enter { state, prev }
+    }
+}
+
+pub(crate) struct Enter<'a> {
+    state: &'a RuntimeState,
+    prev: usize,
+}
+
+impl<'a> Enter<'a> {
+    /// Whether this is a call from the host rather than from a script
+    /// calling back into the host.
+    pub(crate) fn is_outermost(&self) -> bool {
+        self.prev == 0
+    }
+}
+
+impl<'a> Drop for Enter<'a> {
+    fn drop(&mut self) {
+        self.state.active_context.set(self.prev);
+
+        if self.prev == 0 {
+            self.state.report_collections();
+            self.state.report_memory();
+        }
+    }
 }
 
 pub struct Context {
@@ -107,10 +822,18 @@ impl Context {
         strict: bool,
         strip: bool,
     ) -> Result<Value, Value> {
-        let input =
-            CString::new(input).expect("Eval: input is not a valid string");
-        let filename =
-            CString::new(filename).expect("Eval: filename not a valid string");
+        self.eval_bytes(input.as_bytes(), filename, strict, strip)
+    }
+
+    /// Like `eval`, but takes the source as bytes. They needn't be valid
+    /// UTF-8 nor free of NULs, malformed input is a `SyntaxError`.
+    pub fn eval_bytes(
+        &mut self,
+        input: &[u8],
+        filename: &str,
+        strict: bool,
+        strip: bool,
+    ) -> Result<Value, Value> {
         let mut flags = 0i32;
 
         if strict {
@@ -121,27 +844,262 @@ impl Context {
             flags |= sys::JS_EVAL_FLAG_STRIP as i32;
         }
 
+        self.eval_flags(
+            input,
+            filename,
+            flags | sys::JS_EVAL_TYPE_MODULE as i32,
+        )
+    }
+
+    /// Executes pending jobs, i.e. promise reactions, until the job queue is
+    /// empty. Messages sent through `message_channel`, aborts through
+    /// `AbortHandle`s and collected objects are delivered in between.
+    ///
+    /// Stops at the first job that throws. If unhandled rejections are
+    /// tracked, the first promise left rejected without a handler is
+    /// returned as an error as well. With an uncaught exception handler set,
+    /// these errors go to the handler instead and all jobs are run.
+    pub fn run_until_idle(&mut self) -> Result<(), Error> {
+        self.take_microtask_error()?;
+
+        let mut jobs = 0;
+        let ret = self.drain_jobs(&mut jobs);
+
+        stats::jobs_drained(jobs);
+        ret?;
+
+        self.take_unhandled_rejection()
+    }
+
+    /// Limits how long scripts of this context may run. One unit of fuel
+    /// is used each time the engine polls for interrupts while the context
+    /// is active, which happens about every ten thousand calls or loop
+    /// iterations. Once all fuel is used up, the running script is aborted
+    /// with an uncatchable exception. `None` removes the limit.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+        let mut map = state.fuel.borrow_mut();
+
+        match fuel {
+            Some(fuel) => map.insert(self.ptr.state().id, fuel),
+            None => map.remove(&self.ptr.state().id),
+        };
+    }
+
+    /// Fuel left, see `set_fuel`.
+    pub fn fuel(&self) -> Option<u64> {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+
+        state.fuel.borrow().get(&self.ptr.state().id).cloned()
+    }
+
+    /// Runs jobs and delivers messages until there are none left, counting
+    /// the jobs run in `jobs`.
+    fn drain_jobs(&mut self, jobs: &mut usize) -> Result<(), Error> {
+        loop {
+            loop {
+                match self.run_pending_job() {
+                    Ok(true) => *jobs += 1,
+                    Ok(false) => break,
+                    Err(err) => {
+                        *jobs += 1;
+                        self.report_uncaught(err)?;
+                    }
+                }
+            }
+
+            let dispatched = self.dispatch_messages().and_then(|messages| {
+                Ok(self.dispatch_aborts()?
+                    | self.dispatch_finalizers()?
+                    | messages)
+            });
+
+            match dispatched {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(err) => self.report_uncaught(err)?,
+            }
+        }
+    }
+
+    /// Executes a single pending job. Returns `false` if there was none.
+    pub(crate) fn run_pending_job(&self) -> Result<bool, Error> {
+        self.run_job().map_err(|(_, err)| err)
+    }
+
+    /// Like `run_pending_job`, but fails with the context the job belongs
+    /// to as well, which needn't be this one.
+    pub(crate) fn run_job(&self) -> Result<bool, (Context, Error)> {
+        unsafe {
+            let rt = sys::JS_GetRuntime(self.ptr.as_ptr());
+            let mut ctx = ptr::null_mut();
+
+            match sys::JS_ExecutePendingJob(rt, &mut ctx) {
+                0 => Ok(false),
+                rc if rc < 0 => {
+                    let ex = Value {
+                        value: sys::JS_GetException(ctx),
+                        context: ContextPtr::Borrowed(ctx),
+                    };
+
+                    stats::exception();
+
+                    let job_ctx = Context { ptr: ContextPtr::Borrowed(ctx) };
+
+                    Err((job_ctx, Error::from(ex)))
+                }
+                _ => Ok(true),
+            }
+        }
+    }
+
+    /// Reports the first unhandled rejection tracked for this context, if
+    /// any, and forgets about the rest.
+    pub(crate) fn take_unhandled_rejection(&self) -> Result<(), Error> {
+        unsafe {
+            let rt = sys::JS_GetRuntime(self.ptr.as_ptr());
+            let state = RuntimeState::from_context(self.ptr.as_ptr());
+            let rejections = state.take_rejections(self.ptr.state().id);
+            let report_all = self.has_uncaught_handler();
+            let mut first = None;
+
+            for rej in rejections {
+                if first.is_none() || report_all {
+                    let reason = Value {
+                        value: sys::Helper_JS_DupValueRT(rt, rej.reason),
+                        context: self.ptr.clone(),
+                    };
+                    let ex = ExceptionDetails::from_value(&reason);
+
+                    if report_all {
+                        let _ =
+                            self.report_uncaught(Error::UnhandledRejection(ex));
+                    } else {
+                        first = Some(ex);
+                    }
+                }
+
+                rej.free(rt);
+            }
+
+            match first {
+                Some(ex) => Err(Error::UnhandledRejection(ex)),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Evaluates `input` as a classic script and returns its completion
+    /// value.
+    pub(crate) fn eval_script(
+        &self,
+        input: &str,
+        filename: &str,
+    ) -> Result<Value, Value> {
+        self.eval_flags(
+            input.as_bytes(),
+            filename,
+            sys::JS_EVAL_TYPE_GLOBAL as i32,
+        )
+    }
+
+    /// Evaluates `input` with the `JS_EVAL_*` `flags`.
+    pub(crate) fn eval_flags(
+        &self,
+        input: &[u8],
+        filename: &str,
+        mut flags: i32,
+    ) -> Result<Value, Value> {
+        if self.ptr.state().strict {
+            flags |= sys::JS_EVAL_FLAG_STRICT as i32;
+        }
+
+        unsafe { RuntimeState::from_context(self.ptr.as_ptr()) }
+            .register_source(filename, input);
+
+        let _span = trace::eval(filename);
+
+        if flags & sys::JS_EVAL_FLAG_COMPILE_ONLY as i32 == 0 {
+            stats::eval();
+        }
+
+        // the parser expects a terminated buffer, but stops at the given
+        // length, not the first NUL
+        let mut buf = Vec::with_capacity(input.len() + 1);
+
+        buf.extend_from_slice(input);
+        buf.push(0);
+
+        let filename = match CString::new(filename) {
+            Ok(filename) => filename,
+            Err(err) => {
+                return Err(self.error_from(&err).unwrap_or_else(|ex| ex))
+            }
+        };
+
+        let enter = self.ptr.enter();
         let val = unsafe {
             let v = sys::JS_Eval(
                 self.ptr.as_ptr(),
-                input.as_ptr(),
-                input.as_bytes().len(),
+                buf.as_ptr() as *const i8,
+                input.len(),
                 filename.as_ptr(),
-                flags | sys::JS_EVAL_TYPE_MODULE as i32,
+                flags,
             );
 
             Value { value: v, context: self.ptr.clone() }
         };
+        let val = self.finish_call(enter, val);
 
         if val.is_exception() {
-            unsafe {
-                let ex = sys::JS_GetException(self.ptr.as_ptr());
-                Err(Value { value: ex, context: self.ptr.clone() })
+            stats::exception();
+            Err(self.take_exception())
+        } else {
+            Ok(val)
+        }
+    }
+
+    /// Runs a function or module compiled with `JS_EVAL_FLAG_COMPILE_ONLY`.
+    pub(crate) fn eval_function(&self, func: Value) -> Result<Value, Value> {
+        stats::eval();
+
+        let enter = self.ptr.enter();
+        let val = unsafe {
+            Value {
+                value: sys::JS_EvalFunction(self.ptr.as_ptr(), func.into_raw()),
+                context: self.ptr.clone(),
             }
+        };
+        let val = self.finish_call(enter, val);
+
+        if val.is_exception() {
+            stats::exception();
+            Err(self.take_exception())
         } else {
             Ok(val)
         }
     }
+
+    /// A new context of the same runtime, built with the options this one
+    /// was built with.
+    pub(crate) fn rebuild(&self) -> Result<Context, Error> {
+        let runtime = match self.ptr {
+            ContextPtr::Owned(ref owned) => owned.runtime.clone(),
+            // only host functions get those, and only by reference
+            ContextPtr::Borrowed(_) => {
+                unreachable!("rebuilding a borrowed context")
+            }
+        };
+
+        self.ptr.state().options.clone().build(&runtime)
+    }
+
+    pub(crate) fn take_exception(&self) -> Value {
+        unsafe {
+            let ex = sys::JS_GetException(self.ptr.as_ptr());
+            Value { value: ex, context: self.ptr.clone() }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -152,21 +1110,108 @@ mod tests {
     fn eval_single_ctx() {
         let mut ctx = {
             let mut rt = Runtime::default();
-            rt.context()
+            rt.context().unwrap()
         };
         let _ = ctx
             .eval(r#"print('Hello, World\n');"#, "<test>", false, false)
             .unwrap();
     }
 
+    #[test]
+    fn new() {
+        let mut rt = Runtime::new().unwrap();
+
+        assert!(rt.context().is_ok());
+    }
+
+    #[test]
+    fn eval_nul() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+
+        assert!(ctx.eval("1\0", "<test>", false, false).is_err());
+        assert!(ctx.eval("1", "<te\0st>", false, false).is_err());
+        assert!(ctx
+            .eval_bytes(b"globalThis.s = '\0\xc3\xa9';", "<test>", false, false)
+            .is_ok());
+        assert_eq!(
+            ctx.eval_script("s.length", "<test>").unwrap().as_integer(),
+            Some(2)
+        );
+        assert!(ctx.array(&[ctx.integer(1)]).is_ok());
+    }
+
+    #[test]
+    fn strict() {
+        let mut rt = Runtime::default();
+        let sloppy = rt.context().unwrap();
+        let strict = rt.context_builder().strict(true).build().unwrap();
+
+        assert!(sloppy.eval_script("undeclared = 1", "<test>").is_ok());
+        assert!(strict.eval_script("undeclared = 1", "<test>").is_err());
+    }
+
     #[test]
     fn eval_multiple_ctx() {
         let mut rt = Runtime::default();
-        let _ = rt.context();
-        let mut ctx2 = rt.context();
+        let _ = rt.context().unwrap();
+        let mut ctx2 = rt.context().unwrap();
 
         let _ = ctx2
             .eval(r#"print('Hello, World\n');"#, "<test>", false, false)
             .unwrap();
     }
+
+    #[test]
+    fn unhandled_rejection() {
+        let mut rt = Runtime::default();
+        rt.track_unhandled_rejections(true);
+
+        let mut ctx = rt.context().unwrap();
+        let mut other = rt.context().unwrap();
+
+        ctx.eval(
+            r#"Promise.reject(new Error("boom"));"#,
+            "<test>",
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert!(other.run_until_idle().is_ok());
+        match ctx.run_until_idle() {
+            Err(Error::UnhandledRejection(ex)) => {
+                assert_eq!(&ex.message, "boom")
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        ctx.eval(
+            r#"Promise.reject(1).catch(() => {});"#,
+            "<test>",
+            false,
+            false,
+        )
+        .unwrap();
+        assert!(ctx.run_until_idle().is_ok());
+    }
+
+    #[test]
+    fn fuel() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let other = rt.context().unwrap();
+
+        ctx.set_fuel(Some(10));
+        assert!(ctx.eval_script("for (;;) {}", "<test>").is_err());
+        assert_eq!(ctx.fuel(), Some(0));
+        assert!(other
+            .eval_script("for (let i = 0; i < 1e6; i++) {}", "<test>")
+            .is_ok());
+
+        ctx.set_fuel(None);
+        assert!(ctx
+            .eval_script("for (let i = 0; i < 1e6; i++) {}", "<test>")
+            .is_ok());
+    }
 }
@@ -0,0 +1,647 @@
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
+use std::os::raw::{c_char, c_void};
+use std::path::{Component, Path, PathBuf};
+use std::ptr;
+
+use quickjs_sys as sys;
+
+use crate::array::Array;
+use crate::permissions::Capability;
+use crate::reload;
+use crate::runtime::{Context, ContextPtr, RuntimeState};
+use crate::trace;
+use crate::value::Value;
+
+/// Kind of access a script requests to a path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsAccess {
+    Read,
+    Write,
+}
+
+/// Decides which paths the file APIs of the `std` and `os` modules and
+/// file module imports may touch, see `ContextBuilder::fs_sandbox`.
+pub trait FsSandbox {
+    /// Maps `path` as seen by the script to the host path to use, or fails
+    /// to deny access.
+    fn resolve(&self, path: &Path, access: FsAccess) -> io::Result<PathBuf>;
+}
+
+/// Confines scripts to the directory `root`, which they see as `/`.
+///
+/// Only the paths themselves are checked, symbolic links inside `root` can
+/// still point outside of it.
+pub struct Chroot {
+    root: PathBuf,
+    writable: bool,
+}
+
+impl Chroot {
+    pub fn new<P: Into<PathBuf>>(root: P, writable: bool) -> Chroot {
+        Chroot { root: root.into(), writable }
+    }
+}
+
+impl FsSandbox for Chroot {
+    fn resolve(&self, path: &Path, access: FsAccess) -> io::Result<PathBuf> {
+        if access == FsAccess::Write && !self.writable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is read-only", path.display()),
+            ));
+        }
+
+        let mut parts = Vec::new();
+
+        for comp in path.components() {
+            match comp {
+                Component::Prefix(_) | Component::RootDir => parts.clear(),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    parts.pop();
+                }
+                Component::Normal(part) => parts.push(part),
+            }
+        }
+
+        Ok(parts.into_iter().fold(self.root.clone(), |p, part| p.join(part)))
+    }
+}
+
+/// Names the native `std` and `os` modules are registered under if a
+/// sandbox or audit hook is set. Only the wrapper modules can import them.
+pub(crate) const NATIVE_STD: &[u8] = b"__native_std\0";
+pub(crate) const NATIVE_OS: &[u8] = b"__native_os\0";
+
+/// Globals holding the functions the wrapper modules check paths and
+/// capabilities and report operations with.
+const RESOLVE_GLOBAL: &str = "__fsResolve";
+const PERMIT_GLOBAL: &str = "__permit";
+const AUDIT_GLOBAL: &str = "__audit";
+
+/// Shared start of the wrapper modules. `wrap` reports calls to the audit
+/// hook before forwarding them.
+const WRAPPER_PRELUDE: &str = r#"
+const resolve = globalThis.__fsResolve;
+const permit = globalThis.__permit;
+const audit = globalThis.__audit;
+
+function wrap(name, f) {
+    return function (...args) {
+        audit(name, args);
+        return f.apply(this, args);
+    };
+}
+"#;
+
+/// `std` values exported as they are. Anything not listed here or wrapped
+/// isn't exported, so natives added to QuickJS stay out of reach.
+const STD_CONSTANTS: &[&str] =
+    &["in", "out", "err", "SEEK_SET", "SEEK_CUR", "SEEK_END"];
+
+/// `std` functions without paths or capabilities to check.
+const STD_FORWARD: &[&str] = &[
+    "exit",
+    "gc",
+    "evalScript",
+    "strerror",
+    "parseExtJSON",
+    "fdopen",
+    "tmpfile",
+    "puts",
+    "printf",
+    "sprintf",
+];
+
+const STD_WRAPPER: &str = r#"
+export const open = wrap("std.open", (filename, flags, errorObj) => {
+    flags = `${flags}`;
+    return std.open(resolve(filename, "open", flags), flags, errorObj);
+});
+
+export const loadFile = wrap("std.loadFile", (filename) =>
+    std.loadFile(resolve(filename, "read")));
+
+export const loadScript = wrap("std.loadScript", (filename) =>
+    std.loadScript(resolve(filename, "read")));
+
+export const popen = wrap("std.popen", (command, flags, errorObj) => {
+    permit("spawn");
+    return std.popen(command, flags, errorObj);
+});
+
+export const urlGet = wrap("std.urlGet", (url, options) => {
+    permit("net");
+    return std.urlGet(url, options);
+});
+
+export const getenv = wrap("std.getenv", (name) => {
+    permit("env");
+    return std.getenv(name);
+});
+
+export const setenv = wrap("std.setenv", (name, value) => {
+    permit("env");
+    return std.setenv(name, value);
+});
+
+export const unsetenv = wrap("std.unsetenv", (name) => {
+    permit("env");
+    return std.unsetenv(name);
+});
+
+export const getenviron = wrap("std.getenviron", () => {
+    permit("env");
+    return std.getenviron();
+});
+"#;
+
+/// `os` values exported as they are.
+const OS_CONSTANTS: &[&str] = &[
+    "platform", "O_RDONLY", "O_WRONLY", "O_RDWR", "O_APPEND", "O_CREAT",
+    "O_EXCL", "O_TRUNC", "O_TEXT", "S_IFMT", "S_IFIFO", "S_IFCHR", "S_IFDIR",
+    "S_IFBLK", "S_IFREG", "S_IFSOCK", "S_IFLNK", "S_ISGID", "S_ISUID",
+    "SIGINT", "SIGABRT", "SIGFPE", "SIGILL", "SIGSEGV", "SIGTERM", "SIGQUIT",
+    "SIGPIPE", "SIGALRM", "SIGUSR1", "SIGUSR2", "SIGCHLD", "SIGCONT",
+    "SIGSTOP", "SIGTSTP", "SIGTTIN", "SIGTTOU", "WNOHANG",
+];
+
+/// `os` functions without paths or capabilities to check.
+const OS_FORWARD: &[&str] = &[
+    "close",
+    "seek",
+    "read",
+    "write",
+    "isatty",
+    "ttyGetWinSize",
+    "ttySetRaw",
+    "getcwd",
+    "setReadHandler",
+    "setWriteHandler",
+    "signal",
+    "setTimeout",
+    "clearTimeout",
+    "sleep",
+    "waitpid",
+    "dup",
+    "dup2",
+    "pipe",
+];
+
+const OS_WRAPPER: &str = r#"
+const WRITE = os.O_WRONLY | os.O_RDWR | os.O_CREAT | os.O_TRUNC | os.O_APPEND;
+
+export const open = wrap("os.open", (filename, flags, mode) => {
+    flags = flags | 0;
+    return os.open(resolve(filename, "open", flags, WRITE), flags, mode);
+});
+
+export const remove = wrap("os.remove", (filename) =>
+    os.remove(resolve(filename, "write")));
+
+export const rename = wrap("os.rename", (oldname, newname) =>
+    os.rename(resolve(oldname, "write"), resolve(newname, "write")));
+
+export const chdir = wrap("os.chdir", (path) =>
+    os.chdir(resolve(path, "read")));
+
+export const mkdir = wrap("os.mkdir", (path, mode) =>
+    os.mkdir(resolve(path, "write"), mode));
+
+export const readdir = wrap("os.readdir", (path) =>
+    os.readdir(resolve(path, "read")));
+
+export const stat = wrap("os.stat", (path) => os.stat(resolve(path, "read")));
+
+export const lstat = wrap("os.lstat", (path) =>
+    os.lstat(resolve(path, "read")));
+
+export const utimes = wrap("os.utimes", (path, atime, mtime) =>
+    os.utimes(resolve(path, "write"), atime, mtime));
+
+export const realpath = wrap("os.realpath", (path) =>
+    os.realpath(resolve(path, "read")));
+
+export const readlink = wrap("os.readlink", (path) =>
+    os.readlink(resolve(path, "read")));
+
+export const symlink = wrap("os.symlink", (target, linkpath) =>
+    os.symlink(resolve(target, "read"), resolve(linkpath, "write")));
+
+export const exec = wrap("os.exec", (args, options) => {
+    permit("spawn");
+    return os.exec(args, options);
+});
+
+export const kill = wrap("os.kill", (pid, sig) => {
+    permit("spawn");
+    return os.kill(pid, sig);
+});
+
+export const Worker = os.Worker && class Worker extends os.Worker {
+    constructor(filename) {
+        audit("os.Worker", [filename]);
+        permit("spawn");
+        super(resolve(filename, "read"));
+    }
+};
+"#;
+
+/// Source of the module standing in for the native module `name`.
+fn wrapper_source(name: &str) -> String {
+    let (constants, forward, body) = match name {
+        "std" => (STD_CONSTANTS, STD_FORWARD, STD_WRAPPER),
+        _ => (OS_CONSTANTS, OS_FORWARD, OS_WRAPPER),
+    };
+    let mut src = format!("import * as {0} from \"__native_{0}\";\n", name);
+
+    src.push_str(WRAPPER_PRELUDE);
+    for constant in constants {
+        src.push_str(&format!("export const {1} = {0}.{1};\n", name, constant));
+    }
+    for func in forward {
+        src.push_str(&format!(
+            "export const {1} = wrap(\"{0}.{1}\", {0}.{1});\n",
+            name, func
+        ));
+    }
+    src.push_str(body);
+    src
+}
+
+/// Access needed to open a file with `flags`, as passed to `std.open`, or
+/// to `os.open` followed by the mask of the flags that write.
+fn open_access(flags: &[Value]) -> FsAccess {
+    let writes = match (flags.get(0), flags.get(1)) {
+        (Some(flags), None) => flags
+            .as_string()
+            .map_or(true, |flags| flags.contains(|c| "wa+".contains(c))),
+        (Some(flags), Some(mask)) => {
+            match (flags.as_integer(), mask.as_integer()) {
+                (Some(flags), Some(mask)) => flags & mask != 0,
+                _ => true,
+            }
+        }
+        (None, _) => true,
+    };
+
+    if writes {
+        FsAccess::Write
+    } else {
+        FsAccess::Read
+    }
+}
+
+impl Context {
+    /// Installs the functions the wrapper modules check paths and
+    /// capabilities with. They're read-only so scripts can't swap them out.
+    pub(crate) fn install_sandbox(&self) -> Result<(), Value> {
+        let resolve =
+            self.internal_closure(RESOLVE_GLOBAL, |ctx, _, args| {
+                let path = args.get(0).and_then(|p| p.as_string());
+                let access = match args.get(1).and_then(|a| a.as_string()) {
+                    Some(ref a) if a == "write" => FsAccess::Write,
+                    Some(ref a) if a == "open" => open_access(&args[2..]),
+                    _ => FsAccess::Read,
+                };
+                let path = match path {
+                    Some(path) => path,
+                    None => {
+                        let err = ctx.string("path must be a string");
+                        return ctx.throw(err);
+                    }
+                };
+
+                match ctx.resolve_path(Path::new(&path), access) {
+                    Ok(path) => ctx.string(&path.to_string_lossy()),
+                    Err(err) => {
+                        let err =
+                            ctx.error_from(&err).unwrap_or_else(|err| err);
+                        ctx.throw(err)
+                    }
+                }
+            })?;
+
+        let permit = self.internal_closure(PERMIT_GLOBAL, |ctx, _, args| {
+            let cap = match args.get(0).and_then(|c| c.as_string()) {
+                Some(ref c) if c == "net" => Capability::Net,
+                Some(ref c) if c == "spawn" => Capability::Spawn,
+                _ => Capability::Env,
+            };
+            let allowed = match ctx.ptr.state().permissions.as_ref() {
+                Some(perms) => perms.allows(cap),
+                None => true,
+            };
+
+            if allowed {
+                return ctx.undefined();
+            }
+
+            let msg = match cap {
+                Capability::Net => "permission to access the network denied",
+                Capability::Spawn => "permission to spawn processes denied",
+                Capability::Env => {
+                    "permission to access the environment denied"
+                }
+            };
+            let err = io::Error::new(io::ErrorKind::PermissionDenied, msg);
+            let err = ctx.error_from(&err).unwrap_or_else(|err| err);
+
+            ctx.throw(err)
+        })?;
+        let audit = self.internal_closure(AUDIT_GLOBAL, |ctx, _, args| {
+            let name =
+                args.get(0).and_then(|n| n.as_string()).unwrap_or_default();
+            let args = match args.get(1) {
+                Some(args) => {
+                    let args = Array { value: args.clone() };
+                    let len = args.len().unwrap_or(0) as u32;
+
+                    (0..len).filter_map(|idx| args.get(idx).ok()).collect()
+                }
+                None => Vec::new(),
+            };
+
+            ctx.audit(&name, &args);
+            ctx.undefined()
+        })?;
+        let mut global = self.global();
+
+        if !global.define(RESOLVE_GLOBAL, resolve, 0)
+            || !global.define(PERMIT_GLOBAL, permit, 0)
+            || !global.define(AUDIT_GLOBAL, audit, 0)
+        {
+            return Err(self.take_exception());
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn resolve_path(
+        &self,
+        path: &Path,
+        access: FsAccess,
+    ) -> io::Result<PathBuf> {
+        match self.ptr.state().fs_sandbox.as_ref() {
+            Some(sandbox) => sandbox.resolve(path, access),
+            None => Ok(path.to_path_buf()),
+        }
+    }
+
+    /// Compiles a module without evaluating it. Returns `None` with the
+    /// exception pending on errors.
+    unsafe fn compile_module_def(
+        &self,
+        source: &str,
+        name: &CStr,
+    ) -> Option<*mut sys::JSModuleDef> {
+        let source = match CString::new(source) {
+            Ok(source) => source,
+            Err(_) => {
+                let err = self.string("module source contains a NUL byte");
+                self.throw(err);
+                return None;
+            }
+        };
+        RuntimeState::from_context(self.ptr.as_ptr())
+            .register_source(&name.to_string_lossy(), source.as_bytes());
+
+        let val = sys::JS_Eval(
+            self.ptr.as_ptr(),
+            source.as_ptr(),
+            source.as_bytes().len(),
+            name.as_ptr(),
+            (sys::JS_EVAL_TYPE_MODULE | sys::JS_EVAL_FLAG_COMPILE_ONLY) as i32,
+        );
+
+        if sys::Helper_JS_IsException(val) != 0 {
+            None
+        } else {
+            let m = val.u.ptr as *mut sys::JSModuleDef;
+
+            sys::Helper_JS_FreeValue(self.ptr.as_ptr(), val);
+            Some(m)
+        }
+    }
+}
+
+unsafe fn throw_not_found(ctx: *mut sys::JSContext, name: &CStr) {
+    sys::JS_ThrowReferenceError(
+        ctx,
+        b"could not load module '%s'\0".as_ptr() as *const c_char,
+        name.as_ptr(),
+    );
+}
+
+/// Resolves `name`, imported by the module `base`, like QuickJS' default
+/// normalizer: names starting with a dot are relative to the directory of
+/// `base`, others are taken as they are.
+pub(crate) fn resolve_module_name(base: &str, name: &str) -> String {
+    if !name.starts_with('.') {
+        return name.to_string();
+    }
+
+    let mut dir = match base.rfind('/') {
+        Some(idx) => base[..idx].to_string(),
+        None => String::new(),
+    };
+    let mut rest = name;
+
+    loop {
+        if rest.starts_with("./") {
+            rest = &rest[2..];
+        } else if rest.starts_with("../") && !dir.is_empty() {
+            let last = dir.rfind('/').map(|idx| idx + 1).unwrap_or(0);
+
+            if &dir[last..] == "." || &dir[last..] == ".." {
+                break;
+            }
+            dir.truncate(last.saturating_sub(1));
+            rest = &rest[3..];
+        } else {
+            break;
+        }
+    }
+
+    if dir.is_empty() {
+        rest.to_string()
+    } else {
+        format!("{}/{}", dir, rest)
+    }
+}
+
+/// Resolves relative module names with `resolve_module_name`, but
+/// refuses to hand out the native modules to anyone but their wrappers.
+pub(crate) extern "C" fn normalize_module(
+    ctx: *mut sys::JSContext,
+    base: *const c_char,
+    name: *const c_char,
+    _opaque: *mut c_void,
+) -> *mut c_char {
+    unsafe {
+        let base = CStr::from_ptr(base).to_string_lossy();
+        let cname = CStr::from_ptr(name);
+        let name = cname.to_string_lossy();
+
+        if name.starts_with("__native_") && base != "std" && base != "os" {
+            throw_not_found(ctx, cname);
+            return ptr::null_mut();
+        }
+
+        // reloaded modules import like the original
+        let base = reload::unversioned(&base);
+        let normalized = resolve_module_name(base, &name);
+        let context = Context { ptr: ContextPtr::Borrowed(ctx) };
+        let state = context.ptr.state();
+
+        if let Some(log) = state.import_log.borrow_mut().as_mut() {
+            log.push((base.to_string(), normalized.clone()));
+        }
+
+        let mut modules = state.modules.borrow_mut();
+
+        modules.record_import(base, &normalized);
+
+        let normalized = modules.versioned(&normalized);
+
+        let ret = sys::js_malloc(ctx, normalized.len() + 1) as *mut u8;
+
+        if !ret.is_null() {
+            ptr::copy_nonoverlapping(
+                normalized.as_ptr(),
+                ret,
+                normalized.len(),
+            );
+            *ret.add(normalized.len()) = 0;
+        }
+        ret as *mut c_char
+    }
+}
+
+/// Loads the `std`/`os` wrapper modules and, if the context has a sandbox,
+/// file modules through it.
+pub(crate) extern "C" fn load_module(
+    ctx: *mut sys::JSContext,
+    name: *const c_char,
+    _opaque: *mut c_void,
+) -> *mut sys::JSModuleDef {
+    unsafe {
+        let context = Context { ptr: ContextPtr::Borrowed(ctx) };
+        let cname = CStr::from_ptr(name);
+        let state = context.ptr.state();
+        let lossy = cname.to_string_lossy();
+        let _span = trace::load_module(&lossy);
+        // a reloaded module comes from the same file
+        let name = reload::unversioned(&lossy);
+        let source = match name.as_bytes() {
+            b"std" if state.std_wrapped => wrapper_source("std"),
+            b"os" if state.os_wrapped => wrapper_source("os"),
+            _ if state.fs_sandbox.is_none() => {
+                throw_not_found(ctx, cname);
+                return ptr::null_mut();
+            }
+            _ => {
+                let path = Path::new(name).to_path_buf();
+                let source = context
+                    .resolve_path(&path, FsAccess::Read)
+                    .and_then(fs::read_to_string);
+
+                match source {
+                    Ok(source) => source,
+                    Err(_) => {
+                        throw_not_found(ctx, cname);
+                        return ptr::null_mut();
+                    }
+                }
+            }
+        };
+
+        match context.compile_module_def(&source, cname) {
+            Some(def) => {
+                state.modules.borrow_mut().record_load(name);
+                def
+            }
+            None => ptr::null_mut(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::process;
+
+    use super::{Chroot, FsAccess, FsSandbox};
+    use crate::Runtime;
+
+    #[test]
+    fn chroot_paths() {
+        let jail = Chroot::new("/srv/jail", false);
+
+        assert_eq!(
+            jail.resolve(Path::new("/../etc/passwd"), FsAccess::Read).unwrap(),
+            Path::new("/srv/jail/etc/passwd")
+        );
+        assert_eq!(
+            jail.resolve(Path::new("a/./b/../c"), FsAccess::Read).unwrap(),
+            Path::new("/srv/jail/a/c")
+        );
+        assert!(jail.resolve(Path::new("x"), FsAccess::Write).is_err());
+    }
+
+    #[test]
+    fn sandboxed_std() {
+        let root = env::temp_dir()
+            .join(format!("quickjs-sandbox-test-{}", process::id()));
+
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("data.txt"), "jailed").unwrap();
+        fs::write(root.join("lib.js"), "export const x = 42;").unwrap();
+
+        let mut rt = Runtime::default();
+        let mut ctx = rt
+            .context_builder()
+            .fs_sandbox(Chroot::new(&root, false))
+            .build()
+            .unwrap();
+
+        ctx.eval(
+            r#"
+            import * as std from "std";
+            import { x } from "/lib.js";
+            globalThis.data = std.loadFile("/../data.txt");
+            globalThis.x = x;
+            try {
+                std.open("/out.txt", "w");
+            } catch (e) {
+                globalThis.denied = true;
+            }
+            RegExp.prototype.test = () => false;
+            let n = 0;
+            const flags = { toString: () => (n++ ? "w" : "r") };
+            try {
+                std.open("/out.txt", flags);
+                std.open("/out.txt", "a");
+            } catch (e) {}
+            "#,
+            "main.js",
+            false,
+            false,
+        )
+        .unwrap();
+
+        let get = |name| ctx.eval_script(name, "<test>").unwrap();
+
+        assert_eq!(get("data").as_string().unwrap(), "jailed");
+        assert_eq!(get("x").as_integer(), Some(42));
+        assert_eq!(get("denied").as_boolean(), Some(true));
+        assert!(!root.join("out.txt").exists());
+        assert!(ctx
+            .eval(r#"import "__native_std";"#, "evil.js", false, false)
+            .is_err());
+    }
+}
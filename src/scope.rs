@@ -0,0 +1,192 @@
+use std::marker::PhantomData;
+
+use quickjs_sys as sys;
+
+use crate::object::Object;
+use crate::runtime::{Context, ContextPtr};
+use crate::value::Value;
+
+/// Creates values that can't outlive a call to `Context::with`. See there.
+pub struct Scope<'s> {
+    context: &'s Context,
+    // invariant, so scopes of different calls can't be mixed
+    _scope: PhantomData<fn(&'s ()) -> &'s ()>,
+}
+
+/// A value bound to a `Scope`. Only `Scope::escape` turns it into a
+/// `Value`.
+pub struct Local<'s> {
+    value: Value,
+    _scope: PhantomData<fn(&'s ()) -> &'s ()>,
+}
+
+/// Forwards to the `Value` methods of the same name, which don't hand out
+/// the value itself.
+macro_rules! forward {
+    ($($name:ident -> $ret:ty),*) => {
+        $(
+            pub fn $name(&self) -> $ret {
+                self.value.$name()
+            }
+        )*
+    };
+}
+
+impl<'s> Local<'s> {
+    forward!(
+        is_undefined -> bool,
+        is_null -> bool,
+        is_boolean -> bool,
+        is_number -> bool,
+        is_string -> bool,
+        is_object -> bool,
+        is_function -> bool,
+        as_boolean -> Option<bool>,
+        as_integer -> Option<i64>,
+        as_float -> Option<f64>,
+        as_string -> Option<String>
+    );
+
+    /// Looks up the property `key`.
+    pub fn get(&self, key: &str) -> Result<Local<'s>, Local<'s>> {
+        let obj = Object { value: self.value.clone() };
+
+        obj.get(key).map(Local::new).map_err(Local::new)
+    }
+
+    /// Calls this function with `this` and `args`.
+    pub fn call(
+        &self,
+        this: &Local<'s>,
+        args: &[Local<'s>],
+    ) -> Result<Local<'s>, Local<'s>> {
+        let args = args.iter().map(|arg| arg.value.clone()).collect::<Vec<_>>();
+        let ret = self.value.call(this.value.clone(), &args);
+
+        if ret.is_exception() {
+            let ctx = Context { ptr: self.value.context.clone() };
+            Err(Local::new(ctx.take_exception()))
+        } else {
+            Ok(Local::new(ret))
+        }
+    }
+
+    fn new(value: Value) -> Local<'s> {
+        // the scope keeps the context alive, no need to count references
+        let ctx = value.context.as_ptr();
+        let value = Value {
+            value: value.into_raw(),
+            context: ContextPtr::Borrowed(ctx),
+        };
+
+        Local { value, _scope: PhantomData }
+    }
+}
+
+impl<'s> Scope<'s> {
+    /// Binds `value` to this scope.
+    pub fn local(&self, value: Value) -> Local<'s> {
+        Local::new(value)
+    }
+
+    /// Turns `local` back into a `Value` that may outlive the scope.
+    pub fn escape(&self, local: &Local<'s>) -> Value {
+        unsafe {
+            Value {
+                value: sys::Helper_JS_DupValue(
+                    self.context.ptr.as_ptr(),
+                    local.value.value,
+                ),
+                context: self.context.ptr.clone(),
+            }
+        }
+    }
+
+    pub fn undefined(&self) -> Local<'s> {
+        Local::new(self.context.undefined())
+    }
+
+    pub fn null(&self) -> Local<'s> {
+        Local::new(self.context.null())
+    }
+
+    pub fn boolean(&self, val: bool) -> Local<'s> {
+        Local::new(self.context.boolean(val))
+    }
+
+    pub fn integer(&self, val: i64) -> Local<'s> {
+        Local::new(self.context.integer(val))
+    }
+
+    pub fn float(&self, val: f64) -> Local<'s> {
+        Local::new(self.context.float(val))
+    }
+
+    pub fn string(&self, val: &str) -> Local<'s> {
+        Local::new(self.context.string(val))
+    }
+
+    pub fn object(&self) -> Result<Local<'s>, Local<'s>> {
+        self.context
+            .object()
+            .map(|obj| Local::new(obj.value))
+            .map_err(Local::new)
+    }
+
+    pub fn global(&self) -> Local<'s> {
+        Local::new(self.context.global().value)
+    }
+
+    /// Evaluates `input` as a classic script and returns its completion
+    /// value.
+    pub fn eval(
+        &self,
+        input: &str,
+        filename: &str,
+    ) -> Result<Local<'s>, Local<'s>> {
+        self.context
+            .eval_script(input, filename)
+            .map(Local::new)
+            .map_err(Local::new)
+    }
+}
+
+impl Context {
+    /// Runs `f` with a scope creating values that can't escape it, so
+    /// scoped code can't leak engine handles. Use `Scope::escape` to keep a
+    /// value on purpose.
+    pub fn with<R, F>(&self, f: F) -> R
+    where
+        F: for<'s> FnOnce(&Scope<'s>) -> R,
+    {
+        let scope = Scope { context: self, _scope: PhantomData };
+
+        f(&scope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    #[test]
+    fn scoped() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let (sum, kept) = ctx.with(|scope| {
+            let add = scope.eval("(a, b) => a + b", "<test>").unwrap();
+            let ret = add
+                .call(
+                    &scope.undefined(),
+                    &[scope.integer(40), scope.integer(2)],
+                )
+                .unwrap();
+
+            (ret.as_integer(), scope.escape(&scope.string("kept")))
+        });
+
+        assert_eq!(sum, Some(42));
+        assert_eq!(kept.as_string().unwrap(), "kept");
+    }
+}
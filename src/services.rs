@@ -0,0 +1,58 @@
+use std::any::{Any, TypeId};
+use std::rc::Rc;
+
+use crate::runtime::Context;
+
+impl Context {
+    /// Registers `service` with this context, replacing an earlier service
+    /// of the same type. Native functions called from this context can get
+    /// it back with `resolve`.
+    pub fn provide<T: 'static>(&self, service: T) {
+        let service: Rc<dyn Any> = Rc::new(service);
+
+        self.ptr
+            .state()
+            .services
+            .borrow_mut()
+            .insert(TypeId::of::<T>(), service);
+    }
+
+    /// Returns the service of type `T` registered with `provide`.
+    pub fn resolve<T: 'static>(&self) -> Option<Rc<T>> {
+        let service =
+            self.ptr.state().services.borrow().get(&TypeId::of::<T>())?.clone();
+
+        service.downcast::<T>().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::Runtime;
+
+    struct Db(RefCell<Vec<i64>>);
+
+    #[test]
+    fn provide_resolve() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        assert!(ctx.resolve::<Db>().is_none());
+        ctx.provide(Db(RefCell::new(Vec::new())));
+
+        let f = ctx
+            .closure("insert", |ctx, _, args| {
+                let db = ctx.resolve::<Db>().unwrap();
+
+                db.0.borrow_mut().push(args[0].as_integer().unwrap());
+                ctx.undefined()
+            })
+            .unwrap();
+
+        f.call(ctx.undefined(), &[ctx.integer(42)]);
+
+        assert_eq!(*ctx.resolve::<Db>().unwrap().0.borrow(), vec![42]);
+    }
+}
@@ -0,0 +1,135 @@
+use std::os::raw::c_void;
+use std::slice;
+
+use bytes::{Bytes, BytesMut};
+use quickjs_sys as sys;
+
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+
+/// Owner of the memory of an `ArrayBuffer` created from `Bytes`. Emptied if
+/// the memory is handed back to Rust by `Value::take_bytes`.
+type Slot = Option<BytesMut>;
+
+extern "C" fn free_shared(
+    rt: *mut sys::JSRuntime,
+    opaque: *mut c_void,
+    ptr: *mut c_void,
+) {
+    unsafe {
+        let state = &*(sys::JS_GetRuntimeOpaque(rt) as *const RuntimeState);
+
+        state.shared_buffers.borrow_mut().remove(&(ptr as usize));
+        drop(Box::from_raw(opaque as *mut Slot));
+    }
+}
+
+impl Context {
+    /// Creates an `ArrayBuffer` backed by the memory of `data`. This doesn't
+    /// copy if `data` is the only handle to its memory, otherwise the
+    /// contents are copied once.
+    pub fn array_buffer_from_bytes(&self, data: Bytes) -> Result<Value, Value> {
+        // empty buffers share a dangling address, nothing to save there
+        if data.is_empty() {
+            return self.array_buffer(&[]);
+        }
+
+        let mut buf = match data.try_into_mut() {
+            Ok(buf) => buf,
+            Err(data) => BytesMut::from(&data[..]),
+        };
+        let ptr = buf.as_mut_ptr();
+        let len = buf.len();
+        let slot = Box::into_raw(Box::new(Some(buf)));
+        let val = unsafe {
+            Value {
+                value: sys::JS_NewArrayBuffer(
+                    self.ptr.as_ptr(),
+                    ptr,
+                    len,
+                    Some(free_shared),
+                    slot as *mut c_void,
+                    0,
+                ),
+                context: self.ptr.clone(),
+            }
+        };
+
+        if val.is_exception() {
+            unsafe {
+                drop(Box::from_raw(slot));
+            }
+            return Err(self.take_exception());
+        }
+
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+
+        state.shared_buffers.borrow_mut().insert(ptr as usize, slot as usize);
+        Ok(val)
+    }
+}
+
+impl Value {
+    /// Returns the contents of this `ArrayBuffer` as `Bytes`. Buffers created
+    /// by `Context::array_buffer_from_bytes` are detached and their memory
+    /// is returned without copying, others are copied.
+    pub fn take_bytes(&self) -> Option<Bytes> {
+        let ctx = self.context.as_ptr();
+        let (ptr, len) =
+            self.with_array_buffer(|data| (data.as_ptr(), data.len()))?;
+        let state = unsafe { RuntimeState::from_context(ctx) };
+        let slot = state.shared_buffers.borrow().get(&(ptr as usize)).cloned();
+
+        match slot {
+            Some(slot) => unsafe {
+                let buf = (*(slot as *mut Slot)).take()?;
+
+                // frees the now empty slot
+                sys::JS_DetachArrayBuffer(ctx, self.value);
+                Some(buf.freeze())
+            },
+            None => unsafe {
+                Some(Bytes::copy_from_slice(slice::from_raw_parts(ptr, len)))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::Runtime;
+
+    #[test]
+    fn shared() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let data = Bytes::from(vec![1u8, 2, 3]);
+        let addr = data.as_ptr();
+
+        let buf = ctx.array_buffer_from_bytes(data).unwrap();
+        let sum = ctx
+            .eval_script(
+                "(b) => new Uint8Array(b).reduce((a, x) => a + x, 0)",
+                "<test>",
+            )
+            .unwrap();
+
+        assert_eq!(
+            sum.call(ctx.undefined(), &[buf.clone()]).as_integer(),
+            Some(6)
+        );
+
+        let back = buf.take_bytes().unwrap();
+
+        assert_eq!(back.as_ptr(), addr);
+        assert_eq!(&back[..], &[1, 2, 3]);
+        // detached now
+        assert_eq!(buf.take_bytes(), None);
+
+        let copied = ctx.array_buffer(&[4, 5]).unwrap().take_bytes().unwrap();
+
+        assert_eq!(&copied[..], &[4, 5]);
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+
+/// The line of a registered source an exception was thrown at, see
+/// `Runtime::enable_source_registry`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceExcerpt {
+    pub filename: String,
+    /// 1-based.
+    pub line: u32,
+    /// 1-based, if the engine reports it.
+    pub column: Option<u32>,
+    /// The text of the line.
+    pub text: String,
+}
+
+impl RuntimeState {
+    /// Remembers `source` as the code of `filename` if the registry is
+    /// enabled.
+    pub(crate) fn register_source(&self, filename: &str, source: &[u8]) {
+        if let Some(ref mut sources) = *self.sources.borrow_mut() {
+            let source = String::from_utf8_lossy(source);
+            sources.insert(filename.to_string(), Rc::from(source.as_ref()));
+        }
+    }
+
+    pub(crate) fn source(&self, filename: &str) -> Option<Rc<str>> {
+        self.sources.borrow().as_ref()?.get(filename).cloned()
+    }
+
+    pub(crate) fn enable_sources(&self, enable: bool) {
+        let mut sources = self.sources.borrow_mut();
+
+        match (enable, sources.is_some()) {
+            (true, false) => *sources = Some(HashMap::new()),
+            (false, _) => *sources = None,
+            _ => {}
+        }
+    }
+}
+
+impl Context {
+    /// Code last evaluated or loaded as `filename`, if the runtime's source
+    /// registry is enabled.
+    pub fn source(&self, filename: &str) -> Option<Rc<str>> {
+        unsafe { RuntimeState::from_context(self.ptr.as_ptr()) }
+            .source(filename)
+    }
+}
+
+/// Parses the `(file:line)` or `(file:line:column)` of a stack frame.
+fn frame_location(frame: &str) -> Option<(&str, u32, Option<u32>)> {
+    let start = frame.find('(')?;
+    let end = frame.rfind(')')?;
+    let loc = frame.get(start + 1..end)?;
+    let mut parts = loc.rsplitn(3, ':');
+    let last = parts.next()?.parse::<u32>().ok()?;
+    let rest = parts.next()?;
+
+    match (rest.parse::<u32>(), parts.next()) {
+        (Ok(line), Some(file)) => Some((file, line, Some(last))),
+        _ => Some((&loc[..loc.len() - last.to_string().len() - 1], last, None)),
+    }
+}
+
+/// Looks up the line of the innermost frame of `stack` with a location in
+/// the source registry of `val`'s runtime.
+pub(crate) fn excerpt(val: &Value, stack: &str) -> Option<SourceExcerpt> {
+    let state = unsafe { RuntimeState::from_context(val.context.as_ptr()) };
+    let (filename, line, column) = stack.lines().find_map(frame_location)?;
+    let source = state.source(filename)?;
+    let text = source.lines().nth(line.checked_sub(1)? as usize)?;
+
+    Some(SourceExcerpt {
+        filename: filename.to_string(),
+        line,
+        column,
+        text: text.to_string(),
+    })
+}
+
+impl SourceExcerpt {
+    /// Appends the line with a caret under the column, or under its start
+    /// if the column isn't known.
+    pub(crate) fn render(&self, out: &mut String) {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let column = match self.column {
+            Some(column) => column.saturating_sub(1) as usize,
+            None => self.text.len() - self.text.trim_start().len(),
+        };
+        let indent = self
+            .text
+            .chars()
+            .take(column)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        out.push_str(&format!("\n{}--> {}:{}", gutter, self.filename, number));
+        if let Some(column) = self.column {
+            out.push_str(&format!(":{}", column));
+        }
+        out.push_str(&format!("\n{} |", gutter));
+        out.push_str(&format!("\n{} | {}", number, self.text));
+        out.push_str(&format!("\n{} | {}^", gutter, indent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::frame_location;
+    use crate::{ExceptionDetails, Runtime};
+
+    #[test]
+    fn locations() {
+        assert_eq!(
+            frame_location("    at f (a.js:3)"),
+            Some(("a.js", 3, None))
+        );
+        assert_eq!(
+            frame_location("    at f (c:\\a.js:3:7)"),
+            Some(("c:\\a.js", 3, Some(7)))
+        );
+        assert_eq!(frame_location("    at push (native)"), None);
+    }
+
+    #[test]
+    fn registry() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        ctx.eval_script("1", "before.js").unwrap();
+        rt.enable_source_registry(true);
+        assert!(ctx.source("before.js").is_none());
+
+        let src = "function check(v) {\n    v();\n}\ncheck(1);";
+        let ex = ctx.eval_script(src, "config.js").unwrap_err();
+
+        assert_eq!(&*ctx.source("config.js").unwrap(), src);
+
+        let details = ExceptionDetails::from_value(&ex);
+        let excerpt = details.excerpt.as_ref().unwrap();
+
+        assert_eq!(excerpt.filename, "config.js");
+        assert_eq!(excerpt.line, 2);
+        assert_eq!(excerpt.text, "    v();");
+
+        let report = details.render();
+        let lines = report.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "TypeError: not a function");
+        assert!(lines[1].starts_with(" --> config.js:2"));
+        assert_eq!(lines[3], "2 |     v();");
+        assert!(lines[4].starts_with("  |     ^"));
+        assert!(lines[5].starts_with("    at check (config.js:2"));
+    }
+}
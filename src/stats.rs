@@ -0,0 +1,167 @@
+//! Engine metrics, reported through the `metrics` facade behind the
+//! `metrics` feature. Without it the functions compile away.
+//!
+//! | Name                        | Kind      | Meaning                         |
+//! |-----------------------------|-----------|---------------------------------|
+//! | `quickjs_evals_total`       | counter   | scripts and modules evaluated   |
+//! | `quickjs_exceptions_total`  | counter   | exceptions reaching the host    |
+//! | `quickjs_jobs_drained`      | histogram | jobs run per `run_until_idle`   |
+//! | `quickjs_memory_bytes`      | gauge     | bytes allocated, all runtimes   |
+//! | `quickjs_gc_pause_seconds`  | histogram | duration of collections         |
+
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+pub(crate) fn eval() {
+    metrics::counter!("quickjs_evals_total").increment(1);
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn exception() {
+    metrics::counter!("quickjs_exceptions_total").increment(1);
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn jobs_drained(jobs: usize) {
+    metrics::histogram!("quickjs_jobs_drained").record(jobs as f64);
+}
+
+/// Moves a runtime's share of the memory gauge from `old` to `new` bytes.
+#[cfg(feature = "metrics")]
+pub(crate) fn memory(old: usize, new: usize) {
+    let gauge = metrics::gauge!("quickjs_memory_bytes");
+
+    if new >= old {
+        gauge.increment((new - old) as f64);
+    } else {
+        gauge.decrement((old - new) as f64);
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn gc_pause(pause: Duration) {
+    metrics::histogram!("quickjs_gc_pause_seconds").record(pause);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn eval() {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn exception() {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn jobs_drained(_: usize) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn memory(_: usize, _: usize) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn gc_pause(_: Duration) {}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use metrics::{
+        Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata,
+        Recorder, SharedString, Unit,
+    };
+
+    use crate::Runtime;
+
+    /// Counts increments, gauge updates and histogram samples by name.
+    #[derive(Default)]
+    struct Tally(Mutex<HashMap<String, Arc<Samples>>>);
+
+    #[derive(Default)]
+    struct Samples(AtomicU64);
+
+    impl HistogramFn for Samples {
+        fn record(&self, _: f64) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Tally {
+        fn samples(&self, key: &Key) -> Arc<Samples> {
+            let mut map = self.0.lock().unwrap();
+
+            map.entry(key.name().to_string()).or_default().clone()
+        }
+
+        fn get(&self, name: &str) -> u64 {
+            let map = self.0.lock().unwrap();
+
+            map.get(name).map_or(0, |s| s.0.load(Ordering::SeqCst))
+        }
+    }
+
+    impl Recorder for Tally {
+        fn describe_counter(
+            &self,
+            _: KeyName,
+            _: Option<Unit>,
+            _: SharedString,
+        ) {
+        }
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {
+        }
+        fn describe_histogram(
+            &self,
+            _: KeyName,
+            _: Option<Unit>,
+            _: SharedString,
+        ) {
+        }
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let samples = self.samples(key);
+
+            Counter::from_arc(Arc::new(AtomicCounter(samples)))
+        }
+
+        fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+            self.samples(key);
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.samples(key))
+        }
+    }
+
+    struct AtomicCounter(Arc<Samples>);
+
+    impl metrics::CounterFn for AtomicCounter {
+        fn increment(&self, value: u64) {
+            (self.0).0.fetch_add(value, Ordering::SeqCst);
+        }
+
+        fn absolute(&self, value: u64) {
+            (self.0).0.store(value, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn counters() {
+        let tally = Tally::default();
+
+        metrics::with_local_recorder(&tally, || {
+            let mut rt = Runtime::default();
+            let mut ctx = rt.context().unwrap();
+
+            ctx.eval_script("Promise.resolve(1).then(x => x)", "<test>")
+                .unwrap();
+            ctx.eval_script("throw 1", "<test>").unwrap_err();
+            ctx.run_until_idle().unwrap();
+            rt.run_gc();
+        });
+
+        assert_eq!(tally.get("quickjs_evals_total"), 2);
+        assert_eq!(tally.get("quickjs_exceptions_total"), 1);
+        assert_eq!(tally.get("quickjs_jobs_drained"), 1);
+        assert!(tally.get("quickjs_gc_pause_seconds") >= 1);
+    }
+}
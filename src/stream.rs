@@ -0,0 +1,194 @@
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Most bytes a single `read` returns, however many scripts ask for.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "stream is closed")
+}
+
+impl Context {
+    fn throw_io(&self, err: io::Error) -> Value {
+        let err = self.error_from(&err).unwrap_or_else(|err| err);
+        self.throw(err)
+    }
+
+    /// Wraps `reader` in a stream object for scripts. `read(n)` returns an
+    /// `ArrayBuffer` of at most `n` bytes, and never more than 64 KiB, or
+    /// `null` at the end of the stream. `close()` drops `reader`.
+    pub fn reader<R: Read + 'static>(&self, reader: R) -> Result<Value, Value> {
+        let reader = Rc::new(RefCell::new(Some(reader)));
+        let mut obj = self.object()?;
+
+        let r = reader.clone();
+        let read = self.internal_closure("read", move |ctx, _, args| {
+            let len = args
+                .get(0)
+                .and_then(|n| n.as_float())
+                .unwrap_or(CHUNK_SIZE as f64);
+            let len = if len >= 0.0 { len.min(CHUNK_SIZE as f64) } else { 0.0 };
+            let mut buf = vec![0; len as usize];
+            let ret = match r.borrow_mut().as_mut() {
+                Some(r) => r.read(&mut buf),
+                None => Err(closed()),
+            };
+
+            match ret {
+                Ok(0) if !buf.is_empty() => ctx.null(),
+                Ok(n) => match ctx.array_buffer(&buf[..n]) {
+                    Ok(val) => val,
+                    Err(err) => ctx.throw(err),
+                },
+                Err(err) => ctx.throw_io(err),
+            }
+        })?;
+        let close = self.internal_closure("close", move |ctx, _, _| {
+            reader.borrow_mut().take();
+            ctx.undefined()
+        })?;
+
+        if !obj.set("read", read) || !obj.set("close", close) {
+            return Err(self.take_exception());
+        }
+
+        Ok(obj.value)
+    }
+
+    /// Wraps `writer` in a stream object for scripts. `write(data)` writes
+    /// all of an `ArrayBuffer` or string and returns the number of bytes,
+    /// `flush()` flushes and `close()` flushes and drops `writer`.
+    pub fn writer<W: Write + 'static>(
+        &self,
+        writer: W,
+    ) -> Result<Value, Value> {
+        let writer = Rc::new(RefCell::new(Some(writer)));
+        let mut obj = self.object()?;
+
+        let w = writer.clone();
+        let write = self.internal_closure("write", move |ctx, _, args| {
+            let data = match args.get(0) {
+                Some(val) => match val.as_string() {
+                    Some(s) => Some(s.into_bytes()),
+                    None => val.array_buffer_data(),
+                },
+                None => None,
+            };
+            let data = match data {
+                Some(data) => data,
+                None => {
+                    let err = ctx.string("expected an ArrayBuffer or string");
+                    return ctx.throw(err);
+                }
+            };
+            let ret = match w.borrow_mut().as_mut() {
+                Some(w) => w.write_all(&data),
+                None => Err(closed()),
+            };
+
+            match ret {
+                Ok(()) => ctx.integer(data.len() as i64),
+                Err(err) => ctx.throw_io(err),
+            }
+        })?;
+        let w = writer.clone();
+        let flush = self.internal_closure("flush", move |ctx, _, _| {
+            let ret = match w.borrow_mut().as_mut() {
+                Some(w) => w.flush(),
+                None => Err(closed()),
+            };
+
+            match ret {
+                Ok(()) => ctx.undefined(),
+                Err(err) => ctx.throw_io(err),
+            }
+        })?;
+        let close = self.internal_closure("close", move |ctx, _, _| {
+            let ret = match writer.borrow_mut().take() {
+                Some(mut w) => w.flush(),
+                None => Ok(()),
+            };
+
+            match ret {
+                Ok(()) => ctx.undefined(),
+                Err(err) => ctx.throw_io(err),
+            }
+        })?;
+
+        if !obj.set("write", write)
+            || !obj.set("flush", flush)
+            || !obj.set("close", close)
+        {
+            return Err(self.take_exception());
+        }
+
+        Ok(obj.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use super::CHUNK_SIZE;
+    use crate::Runtime;
+
+    #[derive(Clone, Default)]
+    struct Sink(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn copy() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let sink = Sink::default();
+        let input =
+            ctx.reader(io::Cursor::new(b"hello world".to_vec())).unwrap();
+        let output = ctx.writer(sink.clone()).unwrap();
+        let copy = ctx
+            .eval_script(
+                r#"
+                (input, output) => {
+                    let chunks = 0;
+                    for (let buf; (buf = input.read(4)) !== null; chunks++)
+                        output.write(buf);
+                    input.close();
+                    output.close();
+                    return chunks;
+                }
+                "#,
+                "<test>",
+            )
+            .unwrap();
+
+        let ret = copy.call(ctx.undefined(), &[input, output]);
+
+        assert_eq!(ret.as_integer(), Some(3));
+        assert_eq!(&*sink.0.borrow(), b"hello world");
+
+        let input = ctx.reader(io::repeat(0)).unwrap();
+        let read = ctx
+            .eval_script("(input) => input.read(1e12).byteLength", "<test>")
+            .unwrap();
+
+        assert_eq!(
+            read.call(ctx.undefined(), &[input]).as_integer(),
+            Some(CHUNK_SIZE as i64)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::char;
+use std::slice;
+
+use quickjs_sys as sys;
+
+use crate::runtime::Context;
+use crate::value::Value;
+
+impl Context {
+    /// Creates a string with one character per byte in `data`, i.e. a
+    /// Latin-1 decoded string. QuickJS stores these as narrow strings.
+    pub fn byte_string(&self, data: &[u8]) -> Value {
+        let s = data.iter().map(|&b| b as char).collect::<String>();
+
+        self.string(&s)
+    }
+
+    /// Creates a string from UTF-16 code units. Unpaired surrogates are
+    /// kept as they are instead of being replaced.
+    pub fn string_utf16(&self, data: &[u16]) -> Value {
+        // QuickJS accepts surrogates encoded as 3 byte sequences, so encode
+        // as WTF-8 instead of going through a lossy `String`.
+        let mut buf = Vec::with_capacity(data.len() * 3);
+
+        for c in char::decode_utf16(data.iter().cloned()) {
+            match c {
+                Ok(c) => {
+                    let mut tmp = [0; 4];
+                    buf.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+                }
+                Err(err) => {
+                    let u = err.unpaired_surrogate();
+                    buf.push(0xe0 | (u >> 12) as u8);
+                    buf.push(0x80 | ((u >> 6) & 0x3f) as u8);
+                    buf.push(0x80 | (u & 0x3f) as u8);
+                }
+            }
+        }
+
+        unsafe {
+            Value {
+                value: sys::JS_NewStringLen(
+                    self.ptr.as_ptr(),
+                    buf.as_ptr() as *const i8,
+                    buf.len() as _,
+                ),
+                context: self.ptr.clone(),
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Converts like the spec's ToString: calls `toString` on objects and
+    /// throws a `TypeError` for Symbols. Returns the exception if the
+    /// conversion throws.
+    pub(crate) fn to_js_string(&self) -> Result<String, Value> {
+        let ctx = self.context.as_ptr();
+
+        unsafe {
+            let mut len = 0;
+            let p = sys::JS_ToCStringLen(ctx, &mut len, self.value, 0);
+
+            if p.is_null() {
+                return Err(
+                    Context { ptr: self.context.clone() }.take_exception()
+                );
+            }
+
+            let bytes = slice::from_raw_parts(p as *const u8, len as usize);
+            let s = String::from_utf8_lossy(bytes).into_owned();
+
+            sys::JS_FreeCString(ctx, p);
+            Ok(s)
+        }
+    }
+
+    /// Reads a string created by `Context::byte_string` back. Returns `None`
+    /// if this isn't a string or any character is above U+00FF.
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        self.as_string()?
+            .chars()
+            .map(|c| if (c as u32) < 0x100 { Some(c as u8) } else { None })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    #[test]
+    fn byte_string() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let data = (0..=255).collect::<Vec<u8>>();
+        let s = ctx.byte_string(&data);
+        let f = ctx
+            .eval_script("(s) => s.length + ':' + s.charCodeAt(255)", "<test>")
+            .unwrap();
+
+        assert_eq!(s.as_bytes().unwrap(), data);
+        assert_eq!(
+            f.call(ctx.undefined(), &[s]).as_string().unwrap(),
+            "256:255"
+        );
+        assert_eq!(ctx.string("\u{20ac}").as_bytes(), None);
+    }
+
+    #[test]
+    fn utf16() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+
+        let s = ctx.string_utf16(&[0x61, 0xd83d, 0xde00, 0xd800]);
+        let f = ctx
+            .eval_script(
+                "(s) => s.length + ':' + s.codePointAt(1) + ':' + s.charCodeAt(3)",
+                "<test>",
+            )
+            .unwrap();
+
+        assert_eq!(
+            f.call(ctx.undefined(), &[s]).as_string().unwrap(),
+            "4:128512:55296"
+        );
+    }
+}
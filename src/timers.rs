@@ -0,0 +1,250 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+
+use quickjs_sys as sys;
+
+use crate::error::Error;
+use crate::runtime::{Context, ContextPtr, RuntimeState};
+use crate::value::Value;
+
+/// Longest delay in milliseconds, what browsers allow.
+const MAX_DELAY: f64 = i32::MAX as f64;
+
+/// Converts a `setTimeout` delay. Like in browsers, delays that aren't
+/// numbers run right away, and long ones are capped.
+fn delay(millis: f64) -> Duration {
+    let millis = if millis.is_finite() { millis } else { 0.0 };
+
+    Duration::from_secs_f64(millis.max(0.0).min(MAX_DELAY) / 1000.0)
+}
+
+/// Host event loop firing the timers scripts set up with `setTimeout`, see
+/// `Runtime::set_scheduler`.
+pub trait Scheduler {
+    /// Arranges for `timer.fire()` to be called after `delay`.
+    fn schedule(&self, delay: Duration, timer: Timer);
+
+    /// Called on `clearTimeout`. Firing a cancelled timer does nothing, so
+    /// implementing this is optional.
+    fn cancel(&self, _id: u64) {}
+}
+
+/// Callbacks of the timers of a context that haven't fired yet.
+#[derive(Default)]
+pub(crate) struct Timers {
+    next_id: Cell<u64>,
+    pub(crate) callbacks: RefCell<HashMap<u64, Value>>,
+}
+
+/// A timer set up by a script.
+pub struct Timer {
+    id: u64,
+    context: *mut sys::JSContext,
+    timers: Weak<Timers>,
+}
+
+impl Timer {
+    /// Id returned to the script by `setTimeout`.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Calls the timer's callback. Does nothing if the timer was cancelled
+    /// or its context is gone. Exceptions go to the uncaught exception
+    /// handler if one is set.
+    pub fn fire(self) -> Result<(), Error> {
+        let timers = match self.timers.upgrade() {
+            Some(timers) => timers,
+            None => return Ok(()),
+        };
+        let cb = match timers.callbacks.borrow_mut().remove(&self.id) {
+            Some(cb) => cb,
+            None => return Ok(()),
+        };
+        let ctx = Context { ptr: ContextPtr::Borrowed(self.context) };
+        // `call` enters the context and finishes the call like `eval` does,
+        // so the callback runs on the context's fuel and memory, and jobs
+        // run after it with `MicrotaskPolicy::AfterCall`
+        let ret = cb.call(ctx.undefined(), &[]);
+
+        unsafe {
+            RuntimeState::from_context(self.context).wake_job_waiters();
+        }
+
+        if ret.is_exception() {
+            ctx.report_uncaught(Error::from(ctx.take_exception()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Context {
+    /// Installs `setTimeout` and `clearTimeout` delegating to the runtime's
+    /// scheduler.
+    pub(crate) fn install_timers(
+        &self,
+        scheduler: Rc<dyn Scheduler>,
+    ) -> Result<(), Value> {
+        let mut global = self.global();
+        let s = scheduler.clone();
+        let set_timeout =
+            self.internal_closure("setTimeout", move |ctx, _, args| {
+                let cb = match args.get(0) {
+                    Some(cb) if cb.is_function() => cb.clone(),
+                    _ => {
+                        let err = ctx.string("setTimeout expects a function");
+                        return ctx.throw(err);
+                    }
+                };
+                let millis =
+                    args.get(1).and_then(|d| d.as_float()).unwrap_or(0.0);
+                let timers = ctx.ptr.state().timers.clone();
+                let id = timers.next_id.get() + 1;
+
+                timers.next_id.set(id);
+                timers.callbacks.borrow_mut().insert(id, cb);
+                s.schedule(
+                    delay(millis),
+                    Timer {
+                        id,
+                        context: ctx.ptr.as_ptr(),
+                        timers: Rc::downgrade(&timers),
+                    },
+                );
+
+                ctx.integer(id as i64)
+            })?;
+        let clear_timeout =
+            self.internal_closure("clearTimeout", move |ctx, _, args| {
+                if let Some(id) = args.get(0).and_then(|id| id.as_integer()) {
+                    let id = id as u64;
+
+                    if ctx
+                        .ptr
+                        .state()
+                        .timers
+                        .callbacks
+                        .borrow_mut()
+                        .remove(&id)
+                        .is_some()
+                    {
+                        scheduler.cancel(id);
+                    }
+                }
+                ctx.undefined()
+            })?;
+
+        if !global.set("setTimeout", set_timeout)
+            || !global.set("clearTimeout", clear_timeout)
+        {
+            return Err(self.take_exception());
+        }
+
+        Ok(())
+    }
+
+    /// The scheduler set with `Runtime::set_scheduler`.
+    pub(crate) fn scheduler(&self) -> Option<Rc<dyn Scheduler>> {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+
+        state.scheduler.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::{Scheduler, Timer};
+    use crate::{MicrotaskPolicy, Runtime};
+
+    #[derive(Clone, Default)]
+    struct Loop(Rc<RefCell<Vec<(Duration, Timer)>>>);
+
+    impl Scheduler for Loop {
+        fn schedule(&self, delay: Duration, timer: Timer) {
+            self.0.borrow_mut().push((delay, timer));
+        }
+    }
+
+    #[test]
+    fn set_timeout() {
+        let timers = Loop::default();
+        let mut rt = Runtime::default();
+
+        rt.set_scheduler(timers.clone());
+
+        let ctx = rt.context().unwrap();
+
+        ctx.eval_script(
+            r#"
+            globalThis.fired = [];
+            setTimeout(() => fired.push("a"), 100);
+            clearTimeout(setTimeout(() => fired.push("b"), 10));
+            setTimeout(() => {}, Infinity);
+            setTimeout(() => {}, 1e300);
+            "#,
+            "<test>",
+        )
+        .unwrap();
+
+        let pending = timers.0.borrow_mut().drain(..).collect::<Vec<_>>();
+
+        assert_eq!(pending[0].0, Duration::from_millis(100));
+        assert_eq!(pending[2].0, Duration::from_millis(0));
+        assert_eq!(pending[3].0.as_secs(), i32::MAX as u64 / 1000);
+        for (_, timer) in pending {
+            timer.fire().unwrap();
+        }
+
+        assert_eq!(
+            ctx.eval_script("fired.join()", "<test>")
+                .unwrap()
+                .as_string()
+                .unwrap(),
+            "a"
+        );
+    }
+
+    #[test]
+    fn jobs_after_fire() {
+        let timers = Loop::default();
+        let mut rt = Runtime::default();
+
+        rt.set_scheduler(timers.clone());
+
+        let ctx = rt
+            .context_builder()
+            .microtask_policy(MicrotaskPolicy::AfterCall)
+            .build()
+            .unwrap();
+
+        ctx.eval_script(
+            r#"
+            globalThis.fired = [];
+            setTimeout(() => {
+                Promise.resolve().then(() => fired.push("job"));
+                fired.push("timer");
+            }, 0);
+            "#,
+            "<test>",
+        )
+        .unwrap();
+
+        let (_, timer) = timers.0.borrow_mut().pop().unwrap();
+
+        timer.fire().unwrap();
+        assert_eq!(
+            ctx.eval_script("fired.join()", "<test>")
+                .unwrap()
+                .as_string()
+                .unwrap(),
+            "timer,job"
+        );
+    }
+}
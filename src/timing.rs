@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use quickjs_sys as sys;
+
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+
+/// Time spent in the phases of an evaluation, see `Context::eval_timed`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// Parsing and compiling to bytecode.
+    pub compile: Duration,
+    /// Running the compiled code, without `gc`.
+    pub execution: Duration,
+    /// Garbage collections QuickJS ran while the code was running.
+    pub gc: Duration,
+}
+
+impl Context {
+    /// Like `eval`, but also reports where the time went. The pauses of
+    /// collections triggered by the code show up in `Timing::gc` instead of
+    /// being mixed into `Timing::execution`.
+    pub fn eval_timed(
+        &mut self,
+        input: &str,
+        filename: &str,
+        strict: bool,
+        strip: bool,
+    ) -> (Result<Value, Value>, Timing) {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+        let mut flags =
+            (sys::JS_EVAL_TYPE_MODULE | sys::JS_EVAL_FLAG_COMPILE_ONLY) as i32;
+        let mut timing = Timing::default();
+
+        if strict {
+            flags |= sys::JS_EVAL_FLAG_STRICT as i32;
+        }
+        if strip {
+            flags |= sys::JS_EVAL_FLAG_STRIP as i32;
+        }
+
+        let start = Instant::now();
+        let func = self.eval_flags(input.as_bytes(), filename, flags);
+
+        timing.compile = start.elapsed();
+
+        let func = match func {
+            Ok(func) => func,
+            Err(err) => return (Err(err), timing),
+        };
+
+        let gc_time = state.gc_time.get();
+        let start = Instant::now();
+        let ret = self.eval_function(func);
+        let elapsed = start.elapsed();
+
+        timing.gc = state.gc_time.get() - gc_time;
+        timing.execution = elapsed.saturating_sub(timing.gc);
+
+        (ret, timing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::Runtime;
+
+    #[test]
+    fn timed() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+
+        let (ret, timing) = ctx.eval_timed(
+            r#"
+            for (let i = 0; i < 100000; i++) {
+                let a = {};
+                a.self = a;
+            }
+            "#,
+            "<test>",
+            false,
+            false,
+        );
+
+        assert!(ret.is_ok());
+        assert!(timing.compile > Duration::from_secs(0));
+        assert!(timing.execution > Duration::from_secs(0));
+        assert!(timing.gc > Duration::from_secs(0));
+
+        let (ret, timing) =
+            ctx.eval_timed("syntax error(", "<test>", false, false);
+
+        assert!(ret.is_err());
+        assert_eq!(timing.execution, Duration::from_secs(0));
+    }
+}
@@ -0,0 +1,144 @@
+//! Spans for engine operations, behind the `tracing` feature. Without it
+//! the guards are empty and everything compiles away.
+
+use std::time::Duration;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Ends the span of an operation when dropped, recording its duration in
+/// the `duration_us` field.
+pub(crate) struct SpanGuard {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "tracing")]
+    start: Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl SpanGuard {
+    fn enter(span: tracing::Span) -> SpanGuard {
+        SpanGuard { span: span.entered(), start: Instant::now() }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        let micros = self.start.elapsed().as_micros() as u64;
+        self.span.record("duration_us", micros);
+    }
+}
+
+/// Compiling and running a script or module.
+#[cfg(feature = "tracing")]
+pub(crate) fn eval(filename: &str) -> SpanGuard {
+    SpanGuard::enter(tracing::info_span!(
+        "eval",
+        filename,
+        duration_us = tracing::field::Empty
+    ))
+}
+
+/// Loading an imported module.
+#[cfg(feature = "tracing")]
+pub(crate) fn load_module(name: &str) -> SpanGuard {
+    SpanGuard::enter(tracing::info_span!(
+        "load_module",
+        name,
+        duration_us = tracing::field::Empty
+    ))
+}
+
+/// A script calling a host function.
+#[cfg(feature = "tracing")]
+pub(crate) fn native_call(name: &str) -> SpanGuard {
+    SpanGuard::enter(tracing::debug_span!(
+        "native_call",
+        name,
+        duration_us = tracing::field::Empty
+    ))
+}
+
+/// A garbage collection. Nothing can run while the engine collects, so
+/// the span is only created afterwards, when the collection is reported
+/// to the GC hook.
+#[cfg(feature = "tracing")]
+pub(crate) fn gc(pause: Duration) {
+    let _span =
+        tracing::debug_span!("gc", duration_us = pause.as_micros() as u64);
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn eval(_: &str) -> SpanGuard {
+    SpanGuard {}
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn load_module(_: &str) -> SpanGuard {
+    SpanGuard {}
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn native_call(_: &str) -> SpanGuard {
+    SpanGuard {}
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn gc(_: Duration) {}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::Runtime;
+
+    /// Collects the names of the spans created.
+    #[derive(Default)]
+    struct Names {
+        next_id: AtomicU64,
+        names: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Subscriber for Names {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.names.lock().unwrap().push(span.metadata().name().into());
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn spans() {
+        let subscriber = Names::default();
+        let names = subscriber.names.clone();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut rt = Runtime::default();
+            let ctx = rt.context().unwrap();
+            let f = ctx.closure("f", |ctx, _, _| ctx.undefined()).unwrap();
+
+            ctx.global().set("f", f);
+            ctx.eval_script("f()", "<test>").unwrap();
+            rt.run_gc();
+        });
+
+        let names = names.lock().unwrap();
+
+        for name in &["eval", "native_call", "gc"] {
+            assert!(names.iter().any(|n| n == name), "no {} span", name);
+        }
+    }
+}
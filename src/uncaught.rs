@@ -0,0 +1,114 @@
+use crate::error::Error;
+use crate::runtime::{Context, RuntimeState};
+
+impl Context {
+    /// Passes `err`, which no caller is waiting for, to the runtime's
+    /// uncaught exception handler. Returns it back if there is none.
+    pub(crate) fn report_uncaught(&self, err: Error) -> Result<(), Error> {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+        // taken out while running so the handler can run scripts itself
+        let handler = state.uncaught_handler.borrow_mut().take();
+
+        match handler {
+            Some(mut handler) => {
+                handler(&err);
+
+                let mut slot = state.uncaught_handler.borrow_mut();
+
+                if slot.is_none() {
+                    *slot = Some(handler);
+                }
+                Ok(())
+            }
+            None => Err(err),
+        }
+    }
+
+    /// Whether `Runtime::set_uncaught_exception_handler` was called.
+    pub(crate) fn has_uncaught_handler(&self) -> bool {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+
+        state.uncaught_handler.borrow().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::{Error, Runtime};
+
+    #[test]
+    fn uncaught() {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let mut rt = Runtime::default();
+        let errs = errors.clone();
+
+        rt.track_unhandled_rejections(true);
+        rt.set_uncaught_exception_handler(move |err| {
+            errs.borrow_mut().push(err.clone())
+        });
+
+        let mut ctx = rt.context().unwrap();
+
+        ctx.eval(
+            r#"
+            Promise.resolve().then(() => { throw new Error("in job"); });
+            Promise.reject(new TypeError("rejected"));
+            Promise.resolve().then(() => { globalThis.ran = true; });
+            "#,
+            "<test>",
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert!(ctx.run_until_idle().is_ok());
+        assert_eq!(
+            ctx.eval_script("ran", "<test>").unwrap().as_boolean(),
+            Some(true)
+        );
+
+        let messages = errors
+            .borrow()
+            .iter()
+            .map(|err| match err {
+                &Error::UnhandledRejection(ref ex) => ex.message.clone(),
+                err => panic!("{:?}", err),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(messages, vec!["rejected", "in job"]);
+    }
+
+    #[test]
+    fn no_errors() {
+        let calls = Rc::new(RefCell::new(0));
+        let mut rt = Runtime::default();
+        let c = calls.clone();
+
+        rt.track_unhandled_rejections(true);
+        rt.set_uncaught_exception_handler(move |_| *c.borrow_mut() += 1);
+
+        let mut ctx = rt.context().unwrap();
+
+        ctx.eval(
+            r#"
+            Promise.reject(new Error("handled")).catch(() => {});
+            Promise.resolve(1).then((n) => { globalThis.n = n; });
+            "#,
+            "<test>",
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert!(ctx.run_until_idle().is_ok());
+        assert_eq!(
+            ctx.eval_script("n", "<test>").unwrap().as_integer(),
+            Some(1)
+        );
+        assert_eq!(*calls.borrow(), 0);
+    }
+}
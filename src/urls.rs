@@ -0,0 +1,364 @@
+//! `URL` and `URLSearchParams` globals over the `url` crate, behind the
+//! `url` feature.
+//!
+//! Parsing, serializing and the component setters are the crate's, which
+//! follows the WHATWG URL standard. The classes themselves are thin
+//! JavaScript wrappers keeping the components of the parsed URL.
+
+use url::{form_urlencoded, quirks, Url};
+
+use crate::convert::arg;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Installs `URL` and `URLSearchParams` unless `URL` already exists.
+const PRELUDE: &str = r#"(function (parse, update, parseQuery, serializeQuery) {
+    if (typeof URL !== "undefined") {
+        return;
+    }
+
+    class URLSearchParams {
+        constructor(init = "") {
+            this._list = [];
+            this._url = null;
+
+            if (typeof init === "string") {
+                this._list = parseQuery(init.startsWith("?") ? init.slice(1) : init);
+            } else if (init instanceof URLSearchParams) {
+                this._list = init._list.map(([k, v]) => [k, v]);
+            } else if (init != null && typeof init[Symbol.iterator] === "function") {
+                for (const [k, v] of init) {
+                    this._list.push([String(k), String(v)]);
+                }
+            } else if (init != null) {
+                for (const k of Object.keys(init)) {
+                    this._list.push([k, String(init[k])]);
+                }
+            }
+        }
+
+        _changed() {
+            if (this._url !== null) {
+                this._url._set("search", this.toString());
+            }
+        }
+
+        get size() {
+            return this._list.length;
+        }
+
+        append(name, value) {
+            this._list.push([String(name), String(value)]);
+            this._changed();
+        }
+
+        delete(name) {
+            name = String(name);
+            this._list = this._list.filter(([k]) => k !== name);
+            this._changed();
+        }
+
+        get(name) {
+            name = String(name);
+            const entry = this._list.find(([k]) => k === name);
+
+            return entry === undefined ? null : entry[1];
+        }
+
+        getAll(name) {
+            name = String(name);
+            return this._list.filter(([k]) => k === name).map(([, v]) => v);
+        }
+
+        has(name) {
+            name = String(name);
+            return this._list.some(([k]) => k === name);
+        }
+
+        set(name, value) {
+            name = String(name);
+            value = String(value);
+
+            const idx = this._list.findIndex(([k]) => k === name);
+
+            if (idx < 0) {
+                this._list.push([name, value]);
+            } else {
+                this._list[idx][1] = value;
+                this._list = this._list.filter(([k], i) => k !== name || i === idx);
+            }
+            this._changed();
+        }
+
+        sort() {
+            this._list.sort(([a], [b]) => a < b ? -1 : a > b ? 1 : 0);
+            this._changed();
+        }
+
+        forEach(callback, thisArg) {
+            for (const [k, v] of this._list) {
+                callback.call(thisArg, v, k, this);
+            }
+        }
+
+        keys() {
+            return this._list.map(([k]) => k)[Symbol.iterator]();
+        }
+
+        values() {
+            return this._list.map(([, v]) => v)[Symbol.iterator]();
+        }
+
+        entries() {
+            return this._list.map(([k, v]) => [k, v])[Symbol.iterator]();
+        }
+
+        [Symbol.iterator]() {
+            return this.entries();
+        }
+
+        toString() {
+            return serializeQuery(this._list);
+        }
+    }
+
+    class URL {
+        constructor(url, base) {
+            this._fields = parse(String(url),
+                base === undefined ? undefined : String(base));
+            this._params = new URLSearchParams(this._fields.search);
+            this._params._url = this;
+        }
+
+        static canParse(url, base) {
+            try {
+                new URL(url, base);
+                return true;
+            } catch {
+                return false;
+            }
+        }
+
+        _set(name, value) {
+            this._fields = update(this._fields.href, name, String(value));
+            this._params._list = parseQuery(this._fields.search.slice(1));
+        }
+
+        get searchParams() {
+            return this._params;
+        }
+
+        toString() {
+            return this._fields.href;
+        }
+
+        toJSON() {
+            return this._fields.href;
+        }
+    }
+
+    for (const name of ["href", "origin", "protocol", "username", "password",
+                        "host", "hostname", "port", "pathname", "search",
+                        "hash"]) {
+        const desc = {
+            get() {
+                return this._fields[name];
+            },
+            configurable: true,
+            enumerable: true,
+        };
+
+        if (name !== "origin") {
+            desc.set = function (value) {
+                this._set(name, value);
+            };
+        }
+        Object.defineProperty(URL.prototype, name, desc);
+    }
+
+    globalThis.URL = URL;
+    globalThis.URLSearchParams = URLSearchParams;
+})"#;
+
+impl Context {
+    /// Installs `URL` and `URLSearchParams`.
+    pub(crate) fn install_url(&self) -> Result<(), Value> {
+        let parse = self.internal_closure("parse", |ctx, _, args| {
+            let input = arg::<String>(args, 0).unwrap_or_default();
+            let url = match arg::<String>(args, 1) {
+                Some(base) => Url::parse(&base).and_then(|b| b.join(&input)),
+                None => Url::parse(&input),
+            };
+
+            match url {
+                Ok(url) => components(ctx, &url),
+                Err(err) => {
+                    ctx.throw_type_error(&format!("{}: {}", err, input))
+                }
+            }
+        })?;
+        let update = self.internal_closure("update", |ctx, _, args| {
+            let href = arg::<String>(args, 0).unwrap_or_default();
+            let name = arg::<String>(args, 1).unwrap_or_default();
+            let value = arg::<String>(args, 2).unwrap_or_default();
+            let mut url = match Url::parse(&href) {
+                Ok(url) => url,
+                Err(err) => return ctx.throw_type_error(&err.to_string()),
+            };
+
+            // like in browsers, only invalid hrefs throw, other invalid
+            // components are ignored
+            match name.as_str() {
+                "href" => {
+                    if let Err(err) = quirks::set_href(&mut url, &value) {
+                        let msg = format!("{}: {}", err, value);
+
+                        return ctx.throw_type_error(&msg);
+                    }
+                }
+                "protocol" => {
+                    let _ = quirks::set_protocol(&mut url, &value);
+                }
+                "username" => {
+                    let _ = quirks::set_username(&mut url, &value);
+                }
+                "password" => {
+                    let _ = quirks::set_password(&mut url, &value);
+                }
+                "host" => {
+                    let _ = quirks::set_host(&mut url, &value);
+                }
+                "hostname" => {
+                    let _ = quirks::set_hostname(&mut url, &value);
+                }
+                "port" => {
+                    let _ = quirks::set_port(&mut url, &value);
+                }
+                "pathname" => quirks::set_pathname(&mut url, &value),
+                "search" => quirks::set_search(&mut url, &value),
+                "hash" => quirks::set_hash(&mut url, &value),
+                _ => {}
+            }
+
+            components(ctx, &url)
+        })?;
+        let parse_query =
+            self.internal_closure("parseQuery", |ctx, _, args| {
+                let query = arg::<String>(args, 0).unwrap_or_default();
+                let pairs = form_urlencoded::parse(query.as_bytes())
+                    .map(|(k, v)| vec![k.into_owned(), v.into_owned()])
+                    .collect::<Vec<_>>();
+
+                crate::IntoJs::into_js(pairs, ctx)
+                    .unwrap_or_else(|ex| ctx.throw(ex))
+            })?;
+        let serialize_query =
+            self.internal_closure("serializeQuery", |ctx, _, args| {
+                let pairs =
+                    arg::<Vec<Vec<String>>>(args, 0).unwrap_or_default();
+                let mut query = form_urlencoded::Serializer::new(String::new());
+
+                for pair in &pairs {
+                    if let [k, v] = &pair[..] {
+                        query.append_pair(k, v);
+                    }
+                }
+
+                ctx.string(&query.finish())
+            })?;
+        let install = self.eval_script(PRELUDE, "<url>")?;
+        let ret = install.call(
+            self.undefined(),
+            &[parse, update, parse_query, serialize_query],
+        );
+
+        if ret.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The components of `url` as the `URL` class keeps them.
+fn components(ctx: &Context, url: &Url) -> Value {
+    let obj = crate::js_obj!(ctx, {
+        href: quirks::href(url),
+        origin: quirks::origin(url),
+        protocol: quirks::protocol(url),
+        username: quirks::username(url),
+        password: quirks::password(url),
+        host: quirks::host(url),
+        hostname: quirks::hostname(url),
+        port: quirks::port(url),
+        pathname: quirks::pathname(url),
+        search: quirks::search(url),
+        hash: quirks::hash(url),
+    });
+
+    obj.unwrap_or_else(|ex| ctx.throw(ex))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Runtime;
+
+    #[test]
+    fn url() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let eval = |src: &str| {
+            ctx.eval_script(src, "<test>").unwrap().as_string().unwrap()
+        };
+
+        ctx.eval_script(
+            "globalThis.u = new URL('../b?x=1#top', 'https://user@example.com:8080/a/c')",
+            "<test>",
+        )
+        .unwrap();
+
+        assert_eq!(eval("u.href"), "https://user@example.com:8080/b?x=1#top");
+        assert_eq!(eval("u.origin"), "https://example.com:8080");
+        assert_eq!(eval("u.host"), "example.com:8080");
+        assert_eq!(eval("u.pathname"), "/b");
+        assert_eq!(eval("u.hash"), "#top");
+
+        assert_eq!(
+            eval("u.port = '443'; u.protocol = 'http'; u.href"),
+            "http://user@example.com:443/b?x=1#top"
+        );
+        assert!(ctx.eval_script("new URL('not a url')", "<test>").is_err());
+        assert!(ctx.eval_script("u.href = 'nope'", "<test>").is_err());
+    }
+
+    #[test]
+    fn search_params() {
+        let mut rt = Runtime::default();
+        let ctx = rt.context().unwrap();
+        let eval = |src: &str| {
+            ctx.eval_script(src, "<test>").unwrap().as_string().unwrap()
+        };
+
+        ctx.eval_script(
+            "globalThis.u = new URL('https://example.com/?q=a+b&n=1&n=2')",
+            "<test>",
+        )
+        .unwrap();
+
+        assert_eq!(eval("u.searchParams.get('q')"), "a b");
+        assert_eq!(eval("u.searchParams.getAll('n').join()"), "1,2");
+
+        assert_eq!(
+            eval("u.searchParams.set('n', '3'); u.searchParams.append('t', 'x&y'); u.href"),
+            "https://example.com/?q=a+b&n=3&t=x%26y"
+        );
+        assert_eq!(
+            eval("u.search = '?z=1'; [...u.searchParams.keys()].join()"),
+            "z"
+        );
+        assert_eq!(
+            eval("new URLSearchParams({ a: 1, b: 'é' }).toString()"),
+            "a=1&b=%C3%A9"
+        );
+    }
+}
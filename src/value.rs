@@ -1,6 +1,8 @@
 use std::f64;
 use std::fmt;
 use std::i64;
+use std::mem::ManuallyDrop;
+use std::ptr;
 use std::slice;
 use std::str;
 
@@ -31,11 +33,19 @@ impl fmt::Debug for Value {
                 self.value,
                 0,
             );
-            assert!(sz >= 0);
+            if p.is_null() {
+                return f.write_str("<exception>");
+            }
 
             let s = slice::from_raw_parts(p as *const u8, sz as usize);
+            let ret = write!(
+                f,
+                "{}",
+                str::from_utf8(s).unwrap_or("<encoding error>")
+            );
 
-            write!(f, "{}", str::from_utf8(s).unwrap_or("<encoding error>"))
+            sys::JS_FreeCString(self.context.as_ptr(), p);
+            ret
         }
     }
 }
@@ -86,6 +96,10 @@ impl Value {
         unsafe { sys::JS_IsNumber(self.value) != 0 }
     }
 
+    pub fn is_symbol(&self) -> bool {
+        unsafe { sys::Helper_JS_IsSymbol(self.value) != 0 }
+    }
+
     pub fn is_undefined(&self) -> bool {
         unsafe { sys::Helper_JS_IsUndefined(self.value) != 0 }
     }
@@ -94,6 +108,18 @@ impl Value {
         unsafe { sys::Helper_JS_IsNull(self.value) != 0 }
     }
 
+    pub fn is_object(&self) -> bool {
+        unsafe { sys::Helper_JS_IsObject(self.value) != 0 }
+    }
+
+    pub fn is_array(&self) -> bool {
+        unsafe { sys::JS_IsArray(self.context.as_ptr(), self.value) > 0 }
+    }
+
+    pub fn is_function(&self) -> bool {
+        unsafe { sys::JS_IsFunction(self.context.as_ptr(), self.value) != 0 }
+    }
+
     pub fn as_string(&self) -> Option<String> {
         if self.is_string() {
             Some(format!("{:?}", self))
@@ -154,8 +180,39 @@ impl Value {
         }
     }
 
-    pub fn call(&self, this: Value, args: &[Value]) -> Value {
+    /// Gives up ownership of the underlying `JSValue` without freeing it.
+    pub(crate) fn into_raw(self) -> sys::JSValue {
+        let this = ManuallyDrop::new(self);
+
+        unsafe {
+            drop(ptr::read(&this.context));
+        }
+        this.value
+    }
+
+    /// The value without keeping its context alive, for values kept in the
+    /// context's own state. Those have to be dropped before the context is
+    /// freed.
+    pub(crate) fn into_unowned(self) -> Value {
+        let ctx = self.context.as_ptr();
+
+        Value { value: self.into_raw(), context: ContextPtr::Borrowed(ctx) }
+    }
+
+    /// A new reference to the value keeping `ctx` alive, to hand out values
+    /// kept by `into_unowned`.
+    pub(crate) fn owned(&self, ctx: &Context) -> Value {
         unsafe {
+            Value {
+                value: sys::Helper_JS_DupValue(ctx.ptr.as_ptr(), self.value),
+                context: ctx.ptr.clone(),
+            }
+        }
+    }
+
+    pub fn call(&self, this: Value, args: &[Value]) -> Value {
+        let enter = self.context.enter();
+        let ret = unsafe {
             let c = self.context.as_ptr();
             let mut v = args
                 .into_iter()
@@ -166,7 +223,10 @@ impl Value {
                 sys::JS_Call(c, self.value, t, v.len() as i32, v.as_mut_ptr());
 
             Value { context: self.context.clone(), value: ret }
-        }
+        };
+        let ctx = Context { ptr: self.context.clone() };
+
+        ctx.finish_call(enter, ret)
     }
 }
 
@@ -256,7 +316,9 @@ impl Context {
             let mut ary = Array { value: val };
 
             for (i, v) in vals.into_iter().enumerate() {
-                assert!(ary.set(i as u32, v.clone()));
+                if !ary.set(i as u32, v.clone()) {
+                    return Err(self.take_exception());
+                }
             }
 
             Ok(ary)
@@ -278,6 +340,17 @@ impl Context {
         }
     }
 
+    pub fn global(&self) -> Object {
+        let val = unsafe {
+            Value {
+                value: sys::JS_GetGlobalObject(self.ptr.as_ptr()),
+                context: self.ptr.clone(),
+            }
+        };
+
+        Object { value: val }
+    }
+
     pub fn function(
         &self,
         nam: &str,
@@ -338,7 +411,14 @@ macro_rules! wrap_fn {
                 args.push(arg);
             }
 
-            $target(&ctx, this, &args).value
+            $crate::__audit(&ctx, stringify!($target), &args);
+
+            let ret =
+                ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(
+                    || $target(&ctx, this, &args).value,
+                ));
+
+            ret.unwrap_or_else(|_| $crate::__throw_panic(&ctx))
         }
     };
 }
@@ -351,7 +431,7 @@ mod tests {
     #[test]
     fn unit() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
 
         let undef = ctx.undefined();
         let null = ctx.null();
@@ -365,7 +445,7 @@ mod tests {
     #[test]
     fn strings() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
 
         // strings
         let s1 = ctx.string("Hello, World");
@@ -388,7 +468,7 @@ mod tests {
     #[test]
     fn integer() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
 
         // int
         let i1 = ctx.integer(42);
@@ -416,7 +496,7 @@ mod tests {
     #[test]
     fn float() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
 
         // float
         let f1 = ctx.float(42.0);
@@ -448,7 +528,7 @@ mod tests {
     #[test]
     fn bool() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
 
         // bool
         let b1 = ctx.boolean(true);
@@ -464,7 +544,7 @@ mod tests {
     #[test]
     fn arrays() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
 
         let _ = ctx.array(&[]).unwrap();
 
@@ -490,7 +570,7 @@ mod tests {
     #[test]
     fn func() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
         let this = ctx.integer(3);
         let exp = ctx.integer(0);
         let f = ctx.function("testFunc", js_test_func1).unwrap();
@@ -509,7 +589,7 @@ mod tests {
     #[test]
     fn func2() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
         let this = ctx.integer(3);
         let i = ctx.integer(23);
         let j = ctx.integer(42);
@@ -522,7 +602,7 @@ mod tests {
     #[test]
     fn object() {
         let mut rt = Runtime::default();
-        let ctx = rt.context();
+        let ctx = rt.context().unwrap();
 
         let mut obj = ctx.object().unwrap();
 
@@ -0,0 +1,115 @@
+use std::time::Instant;
+
+use crate::error::Error;
+use crate::runtime::{Context, RuntimeState};
+use crate::value::Value;
+
+impl Context {
+    /// Like `eval`, but aborts the script with `Error::Timeout` once
+    /// `deadline` has passed. Time spent in native callbacks isn't
+    /// interrupted, nested calls keep the earlier deadline.
+    pub fn eval_with_deadline(
+        &mut self,
+        input: &str,
+        filename: &str,
+        strict: bool,
+        strip: bool,
+        deadline: Instant,
+    ) -> Result<Value, Error> {
+        let state = unsafe { RuntimeState::from_context(self.ptr.as_ptr()) };
+        let outer = state.deadline.get();
+        let deadline = match outer {
+            Some(outer) if outer < deadline => outer,
+            _ => deadline,
+        };
+
+        state.deadline.set(Some(deadline));
+
+        let ret = self.eval(input, filename, strict, strip);
+
+        state.deadline.set(outer);
+
+        match ret {
+            Ok(val) => Ok(val),
+            Err(_) if state.timed_out.replace(false) => Err(Error::Timeout),
+            Err(ex) => Err(Error::from(ex)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::Runtime;
+
+    #[test]
+    fn deadline() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let ret = ctx.eval_with_deadline(
+            "for (;;) {}",
+            "<test>",
+            false,
+            false,
+            deadline,
+        );
+
+        assert_eq!(ret.unwrap_err(), Error::Timeout);
+        assert!(Instant::now() >= deadline);
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let ret = ctx.eval_with_deadline(
+            "globalThis.x = 1;",
+            "<test>",
+            false,
+            false,
+            deadline,
+        );
+
+        assert!(ret.is_ok());
+    }
+
+    #[test]
+    fn nested() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let inner = Rc::new(Cell::new(None));
+        let seen = inner.clone();
+        let f = ctx
+            .closure("f", move |ctx, _, _| {
+                let mut ctx = Context { ptr: ctx.ptr.clone() };
+                let deadline = Instant::now() + Duration::from_secs(60);
+                let ret = ctx.eval_with_deadline(
+                    "for (;;) {}",
+                    "<inner>",
+                    false,
+                    false,
+                    deadline,
+                );
+
+                seen.set(ret.err());
+                ctx.undefined()
+            })
+            .unwrap();
+
+        ctx.global().set("f", f);
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let ret = ctx.eval_with_deadline(
+            "f(); for (;;) {}",
+            "<test>",
+            false,
+            false,
+            deadline,
+        );
+
+        assert_eq!(inner.take(), Some(Error::Timeout));
+        assert_eq!(ret.unwrap_err(), Error::Timeout);
+    }
+}
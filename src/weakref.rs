@@ -0,0 +1,403 @@
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::rc::Rc;
+
+use quickjs_sys as sys;
+
+use crate::array::Array;
+use crate::error::Error;
+use crate::runtime::Context;
+use crate::value::Value;
+
+/// Installs `WeakRef` and `FinalizationRegistry` unless the engine has
+/// them. Returns a function watching an object for collection and one
+/// running the callback of a collected registry entry.
+const PRELUDE: &str = r#"(function (weakTarget, deref, register) {
+    /* sentinels, dropped with the object they're set for */
+    const watched = new WeakMap();
+    /* internal state of WeakRefs and registries, out of reach of scripts */
+    const targets = new WeakMap();
+    const registries = new WeakMap();
+    /* registry entries by id, not keeping the registry alive */
+    const cells = new Map();
+    let nextId = 0;
+
+    function watch(target, sentinel) {
+        let sentinels = watched.get(target);
+
+        if (sentinels === undefined) {
+            sentinels = [];
+            watched.set(target, sentinels);
+        }
+        sentinels.push(sentinel);
+    }
+
+    function cleanup(id) {
+        const cell = cells.get(id);
+
+        if (cell !== undefined) {
+            const registry = deref(cell.registry);
+
+            cells.delete(id);
+            if (registry !== undefined) {
+                registries.get(registry).callback.call(undefined, cell.held);
+            }
+        }
+    }
+
+    function checkTarget(target, what) {
+        if (Object(target) !== target) {
+            throw new TypeError(what + ": target must be an object");
+        }
+    }
+
+    function registryState(registry) {
+        const state = registries.get(registry);
+
+        if (state === undefined) {
+            throw new TypeError("not a FinalizationRegistry");
+        }
+        return state;
+    }
+
+    class WeakRef {
+        constructor(target) {
+            checkTarget(target, "WeakRef");
+            targets.set(this, weakTarget(target));
+        }
+
+        deref() {
+            const target = targets.get(this);
+
+            if (target === undefined) {
+                throw new TypeError("WeakRef.prototype.deref: not a WeakRef");
+            }
+            return deref(target);
+        }
+    }
+
+    class FinalizationRegistry {
+        constructor(callback) {
+            if (typeof callback !== "function") {
+                throw new TypeError("FinalizationRegistry: callback must be "
+                    + "a function");
+            }
+            registries.set(this, {
+                callback,
+                self: weakTarget(this),
+                tokens: new WeakMap(),
+            });
+        }
+
+        register(target, held, token) {
+            const state = registryState(this);
+
+            checkTarget(target, "FinalizationRegistry");
+            if (target === held) {
+                throw new TypeError("FinalizationRegistry: target and held "
+                    + "value must not be the same");
+            }
+
+            const id = nextId++;
+
+            cells.set(id, { registry: state.self, held });
+            if (token !== undefined) {
+                let ids = state.tokens.get(token);
+
+                if (ids === undefined) {
+                    ids = [];
+                    state.tokens.set(token, ids);
+                }
+                ids.push(id);
+            }
+            register(target, id);
+        }
+
+        unregister(token) {
+            const state = registryState(this);
+            const ids = state.tokens.get(token);
+
+            if (ids === undefined) {
+                return false;
+            }
+            state.tokens.delete(token);
+            return ids.filter((id) => cells.delete(id)).length > 0;
+        }
+    }
+
+    if (typeof globalThis.WeakRef === "undefined") {
+        globalThis.WeakRef = WeakRef;
+    }
+    if (typeof globalThis.FinalizationRegistry === "undefined") {
+        globalThis.FinalizationRegistry = FinalizationRegistry;
+    }
+
+    return [watch, cleanup];
+})"#;
+
+/// What to do once a watched object is collected.
+enum Collected {
+    Host(Box<dyn FnOnce()>),
+    /// Id of a `FinalizationRegistry` entry.
+    Script(u32),
+}
+
+/// Put next to a watched object, and dropped with it. Must not touch the
+/// engine when dropped, it may be in the middle of a collection.
+struct Sentinel {
+    alive: Option<Rc<Cell<bool>>>,
+    collected: Option<Collected>,
+    queue: Rc<RefCell<Vec<Collected>>>,
+}
+
+impl Drop for Sentinel {
+    fn drop(&mut self) {
+        if let Some(alive) = self.alive.take() {
+            alive.set(false);
+        }
+        if let Some(collected) = self.collected.take() {
+            self.queue.borrow_mut().push(collected);
+        }
+    }
+}
+
+/// Reference to an object without a reference count of its own, like the
+/// target of a `WeakRef`. See `Context::downgrade`.
+pub(crate) struct WeakTarget {
+    value: sys::JSValue,
+    alive: Rc<Cell<bool>>,
+}
+
+impl WeakTarget {
+    pub(crate) fn is_alive(&self) -> bool {
+        self.alive.get()
+    }
+
+    /// The object, unless it was collected.
+    pub(crate) fn upgrade(&self, ctx: &Context) -> Option<Value> {
+        if !self.is_alive() {
+            return None;
+        }
+
+        unsafe {
+            Some(Value {
+                value: sys::Helper_JS_DupValue(ctx.ptr.as_ptr(), self.value),
+                context: ctx.ptr.clone(),
+            })
+        }
+    }
+}
+
+/// Per context state of the collection notifications.
+#[derive(Default)]
+pub(crate) struct Finalizers {
+    /// Functions of the prelude. Don't keep the context alive.
+    watch: Option<Value>,
+    cleanup: Option<Value>,
+    /// Objects collected since the last `dispatch_finalizers`.
+    queue: Rc<RefCell<Vec<Collected>>>,
+}
+
+impl Context {
+    /// Installs `WeakRef` and `FinalizationRegistry`, if missing.
+    pub(crate) fn install_weakrefs(&self) -> Result<(), Value> {
+        let weak_target =
+            self.internal_closure("weakTarget", |ctx, _, args| {
+                ctx.downgrade(&args[0])
+                    .and_then(|target| ctx.wrap_native(target))
+                    .unwrap_or_else(|ex| ctx.throw(ex))
+            })?;
+        let deref = self.internal_closure("deref", |ctx, _, args| {
+            let target = args.get(0).and_then(|t| t.native_ref::<WeakTarget>());
+
+            target
+                .and_then(|target| target.upgrade(ctx))
+                .unwrap_or_else(|| ctx.undefined())
+        })?;
+        let register = self.internal_closure("register", |ctx, _, args| {
+            let id = args.get(1).and_then(|id| id.as_integer()).unwrap_or(0);
+            let sentinel =
+                ctx.sentinel(None, Some(Collected::Script(id as u32)));
+
+            match ctx.watch_collect(&args[0], sentinel) {
+                Ok(()) => ctx.undefined(),
+                Err(ex) => ctx.throw(ex),
+            }
+        })?;
+        let install = self.eval_script(PRELUDE, "<weakref>")?;
+        let ret =
+            install.call(self.undefined(), &[weak_target, deref, register]);
+
+        if ret.is_exception() {
+            return Err(self.take_exception());
+        }
+
+        let ret = Array { value: ret };
+        let mut finalizers = self.ptr.state().finalizers.borrow_mut();
+
+        finalizers.watch = Some(ret.get(0)?.into_unowned());
+        finalizers.cleanup = Some(ret.get(1)?.into_unowned());
+        Ok(())
+    }
+
+    /// Calls `f` once `obj` is collected, so host state keyed by script
+    /// objects can be cleaned up. It runs in `run_until_idle`, not right
+    /// when the object is freed, and not at all if the context is dropped
+    /// first. Fails if `obj` isn't an object.
+    ///
+    /// `f` is kept by the engine until then, so it must not capture `obj`
+    /// or any other `Value` of this context. Those would keep `obj` alive,
+    /// or the context, as the engine can't see references held by `f`.
+    pub fn on_collect<F>(&self, obj: &Value, f: F) -> Result<(), Value>
+    where
+        F: FnOnce() + 'static,
+    {
+        if !obj.is_object() {
+            unsafe {
+                sys::JS_ThrowTypeError(
+                    self.ptr.as_ptr(),
+                    b"on_collect: target must be an object\0".as_ptr()
+                        as *const i8,
+                );
+            }
+            return Err(self.take_exception());
+        }
+
+        let sentinel = self.sentinel(None, Some(Collected::Host(Box::new(f))));
+
+        self.watch_collect(obj, sentinel)
+    }
+
+    /// A reference to the object `obj` that doesn't keep it alive.
+    pub(crate) fn downgrade(&self, obj: &Value) -> Result<WeakTarget, Value> {
+        let alive = Rc::new(Cell::new(true));
+        let sentinel = self.sentinel(Some(alive.clone()), None);
+
+        self.watch_collect(obj, sentinel)?;
+        Ok(WeakTarget { value: obj.value, alive })
+    }
+
+    fn sentinel(
+        &self,
+        alive: Option<Rc<Cell<bool>>>,
+        collected: Option<Collected>,
+    ) -> Sentinel {
+        let queue = self.ptr.state().finalizers.borrow().queue.clone();
+
+        Sentinel { alive, collected, queue }
+    }
+
+    /// Keeps `sentinel` until `target` is collected.
+    fn watch_collect(
+        &self,
+        target: &Value,
+        sentinel: Sentinel,
+    ) -> Result<(), Value> {
+        let watch = match self.ptr.state().finalizers.borrow().watch {
+            Some(ref watch) => watch.owned(self),
+            None => panic!("weak references not installed"),
+        };
+        let sentinel = self.wrap_native(sentinel)?;
+        let ret = self.without_microtasks(|| {
+            watch.call(self.undefined(), &[target.clone(), sentinel])
+        });
+
+        if ret.is_exception() {
+            Err(self.take_exception())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs the callbacks for objects collected since the last call.
+    /// Returns whether there were any.
+    pub(crate) fn dispatch_finalizers(&self) -> Result<bool, Error> {
+        let (queue, cleanup) = {
+            let finalizers = self.ptr.state().finalizers.borrow();
+
+            let cleanup = finalizers.cleanup.as_ref().map(|f| f.owned(self));
+
+            (finalizers.queue.clone(), cleanup)
+        };
+        let collected = mem::take(&mut *queue.borrow_mut());
+        let mut ret = Ok(!collected.is_empty());
+
+        for collected in collected {
+            match (collected, cleanup.as_ref()) {
+                (Collected::Host(f), _) => f(),
+                (Collected::Script(id), Some(cleanup)) => {
+                    let id = self.integer(i64::from(id));
+
+                    if cleanup.call(self.undefined(), &[id]).is_exception()
+                        && ret.is_ok()
+                    {
+                        ret = Err(Error::from(self.take_exception()));
+                    }
+                }
+                (Collected::Script(_), None) => {}
+            }
+        }
+
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::Runtime;
+
+    #[test]
+    fn script_classes() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+
+        ctx.eval_script(
+            "globalThis.held = [];
+            globalThis.registry = new FinalizationRegistry((h) => held.push(h));
+            globalThis.kept = {};
+            globalThis.ref = new WeakRef(kept);
+            let gone = {};
+            globalThis.goneRef = new WeakRef(gone);
+            registry.register(gone, 'gone');
+            registry.register(kept, 'kept', kept);
+            gone = null;",
+            "<test>",
+        )
+        .unwrap();
+        rt.run_gc();
+        ctx.run_until_idle().unwrap();
+
+        let ret = ctx
+            .eval_script(
+                "[ref.deref() === kept, goneRef.deref(), held.join(),
+                registry.unregister(kept),
+                Object.getOwnPropertyNames(registry).length].join()",
+                "<test>",
+            )
+            .unwrap();
+
+        assert_eq!(ret.as_string().unwrap(), "true,,gone,true,0");
+        assert!(ctx.eval_script("new WeakRef(1)", "<test>").is_err());
+    }
+
+    #[test]
+    fn host_callback() {
+        let mut rt = Runtime::default();
+        let mut ctx = rt.context().unwrap();
+        let collected = Rc::new(Cell::new(false));
+        let obj = ctx.eval_script("({ key: 1 })", "<test>").unwrap();
+        let flag = collected.clone();
+
+        ctx.on_collect(&obj, move || flag.set(true)).unwrap();
+        ctx.run_until_idle().unwrap();
+        assert!(!collected.get());
+
+        drop(obj);
+        ctx.run_until_idle().unwrap();
+        assert!(collected.get());
+        assert!(ctx.on_collect(&ctx.integer(1), || {}).is_err());
+    }
+}